@@ -1,6 +1,16 @@
 use criterion::{criterion_group, criterion_main, Criterion, BenchmarkId};
-use gol_rs::{args::Args, gol::{self, event::Event}};
+use gol_rs::{args::Args, gol::{self, engine::naive::calculate_next_strip, event::Event}};
+use gol_rs::gol::engine::{distributed::Distributed, parallel::Parallel, Balance, Engine};
+#[cfg(target_os = "linux")]
+use gol_rs::gol::engine::shared::SharedMemory;
+use gol_rs::gol::{rule::Rule, topology::Topology};
+use gol_rs::util::{bitworld::BitWorld, cell::{CellState, CellValue}};
 use sdl2::keyboard::Keycode;
+use std::time::Duration;
+use utils::world::glider_gun;
+
+#[path = "../tests/utils.rs"]
+mod utils;
 
 fn bench_gol(c: &mut Criterion) {
     let mut group = c.benchmark_group("Gol Benchmark");
@@ -38,6 +48,152 @@ fn bench_gol(c: &mut Criterion) {
     group.finish();
 }
 
+/// A reproducible random soup with roughly a quarter of the cells alive.
+fn soup(width: usize, height: usize) -> Vec<Vec<CellState>> {
+    let mut state = 0x9E37_79B9_7F4A_7C15_u64;
+    (0..height).map(|_| (0..width).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        if state.is_multiple_of(4) { CellState::ALIVE } else { CellState::DEAD }
+    }).collect()).collect()
+}
+
+fn bench_representation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("World Representation");
+    group
+        .sampling_mode(criterion::SamplingMode::Flat)
+        .sample_size(10);
+    for size in [512_usize, 8192] {
+        let world = soup(size, size);
+        group.bench_with_input(
+            BenchmarkId::new("Bytes", size),
+            &world,
+            |bencher, world| {
+                let mut next = world.clone();
+                bencher.iter(|| calculate_next_strip(&Rule::CONWAY, Topology::Torus, world, 0, &mut next))
+            }
+        );
+        let cells = world.concat().into_iter().map(CellValue::from).collect::<Vec<_>>();
+        let bits = BitWorld::from_cells(size, size, &cells);
+        group.bench_with_input(
+            BenchmarkId::new("Bits", size),
+            &bits,
+            |bencher, bits| bencher.iter(|| bits.step(&Rule::CONWAY, Topology::Torus))
+        );
+    }
+    group.finish();
+}
+
+/// Larger than Life steps should cost about the same at every range.
+fn bench_range(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Larger than Life Range");
+    group
+        .sampling_mode(criterion::SamplingMode::Flat)
+        .sample_size(10);
+    let world = soup(512, 512);
+    for range in [1_u8, 2, 5, 10] {
+        let rule = format!("R{},C0,M1,S0..{},B0..0,NM", range, range).parse::<Rule>().unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("Moore", range),
+            &rule,
+            |bencher, rule| {
+                let mut next = world.clone();
+                bencher.iter(|| calculate_next_strip(rule, Topology::Torus, &world, 0, &mut next))
+            }
+        );
+    }
+    group.finish();
+}
+
+/// Distributed steps on local workers, printing the bytes moved per turn, which should grow
+/// with the width of the world rather than its area.
+fn bench_distributed(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Distributed");
+    group
+        .sampling_mode(criterion::SamplingMode::Flat)
+        .sample_size(10);
+    for size in [512_usize, 5120] {
+        let mut engine = Distributed::new(&[], 4, Duration::from_secs(10), Rule::CONWAY, Topology::Torus).unwrap();
+        engine.load(&soup(size, size)).unwrap();
+        group.bench_function(BenchmarkId::new("Workers", size), |bencher| bencher.iter(|| engine.step(1).unwrap()));
+        let traffic = engine.traffic();
+        println!(
+            "{0}x{0}: {1} broker bytes and {2} halo bytes per turn",
+            size,
+            traffic.broker / traffic.turns.max(1),
+            traffic.halo / traffic.turns.max(1)
+        );
+        engine.shutdown().unwrap();
+    }
+    group.finish();
+}
+
+/// Strips stepped on child processes through shared memory against the same strips on threads.
+#[cfg(target_os = "linux")]
+fn bench_processes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Processes vs Threads");
+    group
+        .sampling_mode(criterion::SamplingMode::Flat)
+        .sample_size(10);
+    for size in [512_usize, 2048] {
+        let world = soup(size, size);
+        for workers in [1, num_cpus::get()] {
+            let engines: [(&str, Box<dyn Engine>); 2] = [
+                ("Threads", Box::new(Parallel::new(workers, Rule::CONWAY, Topology::Torus).unwrap())),
+                ("Processes", Box::new(SharedMemory::new(workers, Rule::CONWAY, Topology::Torus).unwrap())),
+            ];
+            for (name, mut engine) in engines {
+                engine.load(&world).unwrap();
+                group.bench_function(
+                    BenchmarkId::new(name, format!("{}x{} on {}", size, size, workers)),
+                    |bencher| bencher.iter(|| engine.step(1).unwrap())
+                );
+            }
+        }
+    }
+    group.finish();
+}
+
+/// Strips recut by time and by work against equal strips, on a sparse world where
+/// the glider gun and its gliders are the only things happening.
+fn bench_balance(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Balanced Strips");
+    group
+        .sampling_mode(criterion::SamplingMode::Flat)
+        .sample_size(10);
+    let threads = num_cpus::get();
+    for size in [512_usize, 2048] {
+        let world = glider_gun(size, size);
+        for balance in [Balance::Off, Balance::Time, Balance::Work] {
+            let mut engine = Parallel::new(threads, Rule::CONWAY, Topology::Plane).unwrap().balance(balance, 16);
+            engine.load(&world).unwrap();
+            group.bench_function(
+                BenchmarkId::new(format!("{:?}", balance), format!("glider gun {}x{}", size, size)),
+                |bencher| bencher.iter(|| engine.step(1).unwrap())
+            );
+        }
+    }
+    group.finish();
+}
 
-criterion_group!(benches, bench_gol);
+#[cfg(target_os = "linux")]
+criterion_group!(
+    benches,
+    bench_gol,
+    bench_representation,
+    bench_range,
+    bench_distributed,
+    bench_processes,
+    bench_balance
+);
+#[cfg(not(target_os = "linux"))]
+criterion_group!(
+    benches,
+    bench_gol,
+    bench_representation,
+    bench_range,
+    bench_distributed,
+    bench_balance
+);
 criterion_main!(benches);
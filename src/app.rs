@@ -0,0 +1,91 @@
+use crate::args::Args;
+use crate::gol::{self, event::Event, remote};
+use crate::sdl;
+use anyhow::Result;
+use flume::{Receiver, Sender};
+use sdl2::keyboard::Keycode;
+use std::net::{SocketAddr, TcpListener};
+use tokio::try_join;
+
+/// Run the Game of Life with `args`, in an SDL window or headless, until it finishes or is quit.
+/// This is everything the `gol-rs` and `broker` binaries do once their arguments are parsed.
+pub async fn run(args: Args) -> Result<()> {
+    if let Some(address) = args.attach {
+        return attach(args, address).await;
+    }
+    // Settle the size before the window is made, as it may come from the input image.
+    let (width, height) = args.size()?;
+    let args = args.image_width(width).image_height(height);
+    log::info!(target: "Main", "{:<10} {}", "Threads", args.threads);
+    if let Some(input) = &args.input {
+        log::info!(target: "Main", "{:<10} {}", "Input", input.display());
+    }
+    log::info!(target: "Main", "{:<10} {}", "Width", width);
+    log::info!(target: "Main", "{:<10} {}", "Height", height);
+    log::info!(target: "Main", "{:<10} {}", "Turns", args.turns);
+    log::info!(target: "Main", "{:<10} {}", "Rule", args.rule);
+    log::info!(target: "Main", "{:<10} {}", "Topology", args.topology);
+    log::info!(target: "Main", "{:<10} {}", "Unbounded", args.unbounded);
+    log::info!(target: "Main", "{:<10} {:?}", "Engine", args.engine);
+    log::info!(target: "Main", "{:<10} {}", "History", args.history);
+    log::info!(target: "Main", "{:<10} {}", "StopStable", args.stop_on_stable);
+    log::info!(target: "Main", "{:<10} {}", "Snapshots", args.snapshot_every);
+    if let Some(resume) = &args.resume {
+        log::info!(target: "Main", "{:<10} {}", "Resume", resume.display());
+    }
+    if !args.workers.is_empty() {
+        let workers = args.workers.iter().map(ToString::to_string).collect::<Vec<_>>();
+        log::info!(target: "Main", "{:<10} {}", "Workers", workers.join(","));
+    }
+
+    let (key_presses_tx, key_presses_rx) = flume::bounded::<Keycode>(10);
+    let (events_tx, events_rx) = flume::bounded::<Event>(1000);
+
+    tokio::spawn(sigint(key_presses_tx.clone()));
+
+    if let Some(address) = args.serve {
+        let listener = TcpListener::bind(address)?;
+        log::info!(target: "Main", "{:<10} {}", "Serving", listener.local_addr()?);
+        try_join!(
+            remote::run(args.clone(), listener, events_tx, key_presses_rx),
+            control(args, events_rx, key_presses_tx)
+        )?;
+    } else {
+        try_join!(
+            gol::run(args.clone(), events_tx, key_presses_rx),
+            control(args, events_rx, key_presses_tx)
+        )?;
+    }
+    Ok(())
+}
+
+/// Attach to the run served at `address`, and show it in an SDL window or headless until `Q` detaches again.
+async fn attach(args: Args, address: SocketAddr) -> Result<()> {
+    let (hello, events_rx, key_presses_tx) = remote::attach(address)?;
+    log::info!(target: "Main", "{:<10} {}", "Attached", address);
+    log::info!(target: "Main", "{:<10} {}", "Width", hello.width);
+    log::info!(target: "Main", "{:<10} {}", "Height", hello.height);
+    log::info!(target: "Main", "{:<10} {}", "Rule", hello.rule);
+    log::info!(target: "Main", "{:<10} {}", "Unbounded", hello.unbounded);
+    tokio::spawn(sigint(key_presses_tx.clone()));
+    let args = args
+        .image_width(hello.width)
+        .image_height(hello.height)
+        .rule(hello.rule)
+        .unbounded(hello.unbounded);
+    control(args, events_rx, key_presses_tx).await
+}
+
+/// Show the events of a run in an SDL window, or log them when headless.
+async fn control(args: Args, events: Receiver<Event>, key_presses: Sender<Keycode>) -> Result<()> {
+    if args.headless {
+        sdl::r#loop::run_headless(events).await
+    } else {
+        sdl::r#loop::run(args, events, key_presses).await
+    }
+}
+
+async fn sigint(key_presses_tx: Sender<Keycode>) {
+    tokio::signal::ctrl_c().await.unwrap();
+    key_presses_tx.send_async(Keycode::Q).await.unwrap();
+}
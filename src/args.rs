@@ -1,4 +1,13 @@
+use crate::gol::engine::{Balance, EngineKind};
+use crate::gol::rule::Rule;
+use crate::gol::topology::Topology;
+use crate::gol::io::{self, pattern::{Format, Placement}, SizeError};
 use clap::{ArgAction, Parser};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+/// The width and height of a world left out of the arguments with no input image to take them from.
+pub(crate) const DEFAULT_SIZE: usize = 512;
 
 #[derive(Clone, Debug, Parser)]
 #[clap(disable_help_flag = true)]
@@ -14,18 +23,16 @@ pub struct Args {
     #[arg(
         short = 'w',
         long = "width",
-        default_value_t = 512,
-        help = "Specify the width of the image."
+        help = "Specify the width of the image, or leave it out to take it from --input. Defaults to 512."
     )]
-    pub image_width: usize,
+    pub image_width: Option<usize>,
 
     #[arg(
         short = 'h',
         long = "height",
-        default_value_t = 512,
-        help = "Specify the height of the image."
+        help = "Specify the height of the image, or leave it out to take it from --input. Defaults to 512."
     )]
-    pub image_height: usize,
+    pub image_height: Option<usize>,
 
     #[arg(
         short = 'f',
@@ -49,6 +56,147 @@ pub struct Args {
     )]
     pub headless: bool,
 
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Read the initial world from this image or .rle, .cells, .lif or .mc pattern instead of images/{width}x{height}.pgm."
+    )]
+    pub input: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value_t = Placement::Centre,
+        help = "Specify where an input pattern is put on the board: centre, or x,y for its top left cell."
+    )]
+    pub place: Placement,
+
+    #[arg(
+        long,
+        default_value = "out",
+        value_name = "DIR",
+        help = "Specify the directory images and snapshots are written to."
+    )]
+    pub output_dir: PathBuf,
+
+    #[arg(
+        long,
+        default_value = "{w}x{h}x{turn}",
+        value_name = "TEMPLATE",
+        help = "Specify the name of output images, filling in {w}, {h}, {turn} and {timestamp}. End it in .rle, .cells, .lif or .mc to write patterns."
+    )]
+    pub output_name: String,
+
+    #[arg(
+        long,
+        default_value_t = Rule::CONWAY,
+        help = "Specify the rule, for example B36/S23, 23/36, B2-a/S12, B2/S/C3 or R5,C0,M1,S34..58,B34..45,NM."
+    )]
+    pub rule: Rule,
+
+    #[arg(
+        long,
+        default_value_t = Topology::Torus,
+        help = "Specify how the edges join: torus, plane, klein, cross-surface or twisted-torus:<offset>."
+    )]
+    pub topology: Topology,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Grow the world in tiles as cells reach its edges instead of joining them."
+    )]
+    pub unbounded: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = EngineKind::Parallel,
+        help = "Specify the backend used to advance the world."
+    )]
+    pub engine: EngineKind,
+
+    #[arg(
+        long,
+        default_value_t = 1024,
+        help = "Specify the memory cap of the HashLife node cache in MiB."
+    )]
+    pub hashlife_memory: usize,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Balance::Off,
+        help = "Specify how the parallel engine cuts the world into strips as the run goes."
+    )]
+    pub balance: Balance,
+
+    #[arg(
+        long,
+        default_value_t = 16,
+        value_name = "TURNS",
+        help = "Specify how many turns the parallel engine steps between recutting its strips."
+    )]
+    pub balance_interval: u64,
+
+    #[arg(
+        long,
+        default_value_t = 0,
+        help = "Specify how many turns can be stepped back with B while paused. Keeping any history makes the run step one turn at a time instead of jumping several turns at once, so the default is 0."
+    )]
+    pub history: usize,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Finish the run as soon as the world repeats itself instead of running every turn."
+    )]
+    pub stop_on_stable: bool,
+
+    #[arg(
+        long,
+        default_value_t = 0,
+        help = "Write a snapshot to the output directory every this many turns, or 0 to write none."
+    )]
+    pub snapshot_every: usize,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Carry on from a snapshot instead of the input image, with the same options it was taken with."
+    )]
+    pub resume: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "ADDRESSES",
+        help = "Specify the workers of the distributed engine, for example 127.0.0.1:8030,127.0.0.1:8031."
+    )]
+    pub workers: Vec<SocketAddr>,
+
+    #[arg(
+        long,
+        default_value_t = 2000,
+        value_name = "MILLISECONDS",
+        help = "Specify how long the distributed engine waits on a silent worker before giving its strip to the others."
+    )]
+    pub worker_timeout: u64,
+
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        help = "Let the controller detach with Q and leave the run going, and let others attach on this address."
+    )]
+    pub serve: Option<SocketAddr>,
+
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        conflicts_with = "serve",
+        help = "Attach to a run started with --serve instead of starting one."
+    )]
+    pub attach: Option<SocketAddr>,
+
     #[arg(
         long,
         action = ArgAction::HelpLong
@@ -63,18 +211,33 @@ impl Default for Args {
 }
 
 impl Args {
+    /// The width and height of the world. With an input image, any left out are taken from its header
+    /// and any given must agree with it. With an input pattern, any left out are taken from its bounding box
+    /// and any given are the board it is placed on. Without either, any left out are 512.
+    pub fn size(&self) -> Result<(usize, usize), SizeError> {
+        let Some(path) = &self.input else {
+            return Ok((self.image_width.unwrap_or(DEFAULT_SIZE), self.image_height.unwrap_or(DEFAULT_SIZE)));
+        };
+        let found = io::read_size(path)?;
+        let given = (self.image_width.unwrap_or(found.0), self.image_height.unwrap_or(found.1));
+        if given != found && !matches!(Format::of_file(path), Ok(Some(_))) {
+            return Err(SizeError::Mismatch { path: path.clone(), given, found });
+        }
+        Ok(given)
+    }
+
     pub fn threads(mut self, threads: usize) -> Self {
         self.threads = threads;
         self
     }
 
     pub fn image_width(mut self, image_width: usize) -> Self {
-        self.image_width = image_width;
+        self.image_width = Some(image_width);
         self
     }
 
     pub fn image_height(mut self, image_height: usize) -> Self {
-        self.image_height = image_height;
+        self.image_height = Some(image_height);
         self
     }
 
@@ -92,4 +255,99 @@ impl Args {
         self.headless = headless;
         self
     }
+
+    pub fn input<P: Into<PathBuf>>(mut self, input: P) -> Self {
+        self.input = Some(input.into());
+        self
+    }
+
+    pub fn place(mut self, place: Placement) -> Self {
+        self.place = place;
+        self
+    }
+
+    pub fn output_dir<P: Into<PathBuf>>(mut self, output_dir: P) -> Self {
+        self.output_dir = output_dir.into();
+        self
+    }
+
+    pub fn output_name<S: Into<String>>(mut self, output_name: S) -> Self {
+        self.output_name = output_name.into();
+        self
+    }
+
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rule = rule;
+        self
+    }
+
+    pub fn topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn unbounded(mut self, unbounded: bool) -> Self {
+        self.unbounded = unbounded;
+        self
+    }
+
+    pub fn engine(mut self, engine: EngineKind) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    pub fn hashlife_memory(mut self, hashlife_memory: usize) -> Self {
+        self.hashlife_memory = hashlife_memory;
+        self
+    }
+
+    pub fn balance(mut self, balance: Balance) -> Self {
+        self.balance = balance;
+        self
+    }
+
+    pub fn balance_interval(mut self, balance_interval: u64) -> Self {
+        self.balance_interval = balance_interval;
+        self
+    }
+
+    pub fn history(mut self, history: usize) -> Self {
+        self.history = history;
+        self
+    }
+
+    pub fn stop_on_stable(mut self, stop_on_stable: bool) -> Self {
+        self.stop_on_stable = stop_on_stable;
+        self
+    }
+
+    pub fn snapshot_every(mut self, snapshot_every: usize) -> Self {
+        self.snapshot_every = snapshot_every;
+        self
+    }
+
+    pub fn resume<P: Into<PathBuf>>(mut self, resume: P) -> Self {
+        self.resume = Some(resume.into());
+        self
+    }
+
+    pub fn workers(mut self, workers: Vec<SocketAddr>) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    pub fn worker_timeout(mut self, worker_timeout: u64) -> Self {
+        self.worker_timeout = worker_timeout;
+        self
+    }
+
+    pub fn serve(mut self, serve: SocketAddr) -> Self {
+        self.serve = Some(serve);
+        self
+    }
+
+    pub fn attach(mut self, attach: SocketAddr) -> Self {
+        self.attach = Some(attach);
+        self
+    }
 }
@@ -0,0 +1,17 @@
+use clap::{CommandFactory, FromArgMatches};
+use log::Level;
+use gol_rs::app;
+use gol_rs::args::Args;
+use gol_rs::gol::engine::EngineKind;
+use gol_rs::util::logger;
+
+/// The broker runs the Game of Life like `gol-rs`, but has the strips of the world stepped
+/// by the workers given with `--workers`. Pressing `K` shuts the workers down along with it.
+#[tokio::main(flavor = "multi_thread")]
+async fn main() {
+    // The same arguments as `gol-rs`, except that the broker cannot run without workers.
+    let command = Args::command().name("broker").mut_arg("workers", |workers| workers.required(true));
+    let args = Args::from_arg_matches(&command.get_matches()).unwrap_or_else(|error| error.exit());
+    logger::init(Level::Info, false);
+    app::run(args.engine(EngineKind::Distributed)).await.unwrap();
+}
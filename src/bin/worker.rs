@@ -0,0 +1,28 @@
+use clap::Parser;
+use log::Level;
+use gol_rs::gol::worker;
+use gol_rs::util::logger;
+use std::io::Write;
+use std::net::{SocketAddr, TcpListener};
+
+#[derive(Parser)]
+struct WorkerArgs {
+    #[arg(
+        short = 'a',
+        long,
+        default_value = "127.0.0.1:8030",
+        help = "Specify the address to listen for the broker on, with port 0 for any free port."
+    )]
+    address: SocketAddr,
+}
+
+/// The worker steps strips of the world for one broker at a time, until a broker shuts it down with `K`.
+/// It prints the address it listens on once it is ready, so a port of 0 can be used.
+fn main() {
+    let args = WorkerArgs::parse();
+    logger::init(Level::Info, false);
+    let listener = TcpListener::bind(args.address).unwrap();
+    println!("Worker listening on {}", listener.local_addr().unwrap());
+    std::io::stdout().flush().unwrap();
+    worker::serve(listener).unwrap();
+}
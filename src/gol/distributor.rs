@@ -1,10 +1,29 @@
 use crate::gol::event::{Event, State};
+use crate::gol::engine::Engine;
+use crate::gol::history::{Delta, History};
 use crate::gol::Params;
-use crate::gol::io::IoCommand;
-use crate::util::cell::CellValue;
-use anyhow::Result;
+use crate::gol::io::{self, IoCommand};
+use crate::gol::snapshot::SnapshotHeader;
+use crate::gol::stability::Stability;
+use crate::gol::topology::Topology;
+use crate::util::cell::{CellCoord, CellState};
+use anyhow::{bail, Result};
 use flume::{Receiver, Sender};
 use sdl2::keyboard::Keycode;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const ALIVE_CELLS_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Engines that can take many turns per step grow their jumps while a step takes
+/// less than this, and shrink them while it takes more, so that key presses and
+/// alive cell reports are still served promptly.
+const STEP_TARGET: Duration = Duration::from_millis(100);
+
+/// An unbounded world grows by this many cells on each side that an alive cell comes within reach of.
+/// It is at least the furthest reach of any rule, so one tile always keeps the next turn inside the world,
+/// and even, so hexagon rows and triangle directions keep their parity.
+const TILE: usize = 64;
 
 pub struct DistributorChannels {
     pub events: Option<Sender<Event>>,
@@ -12,8 +31,39 @@ pub struct DistributorChannels {
     pub io_command: Option<Sender<IoCommand>>,
     pub io_idle: Option<Receiver<bool>>,
     pub io_filename: Option<Sender<String>>,
-    pub io_input: Option<Receiver<CellValue>>,
-    pub io_output: Option<Sender<CellValue>>,
+    pub io_input: Option<Receiver<CellState>>,
+    pub io_output: Option<Sender<CellState>>,
+    pub io_snapshot: Option<Receiver<Result<SnapshotHeader>>>,
+}
+
+/// What the turn loop should do after a key press has been handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Control {
+    Continue,
+    Quit,
+    /// Quit and shut the engine down, as when `K` is pressed.
+    Kill,
+}
+
+struct Distributor {
+    params: Params,
+    events: Sender<Event>,
+    key_presses: Receiver<Keycode>,
+    io_command: Sender<IoCommand>,
+    io_idle: Receiver<bool>,
+    io_filename: Sender<String>,
+    io_input: Receiver<CellState>,
+    io_output: Sender<CellState>,
+    io_snapshot: Receiver<Result<SnapshotHeader>>,
+    engine: Box<dyn Engine>,
+    turns_per_step: u64,
+    turn: u32,
+    /// The world coordinate of the engine's top left cell, which moves as an unbounded world grows.
+    origin: CellCoord<i64>,
+    width: usize,
+    height: usize,
+    history: History,
+    stability: Stability,
 }
 
 pub fn distributor(
@@ -24,23 +74,411 @@ pub fn distributor(
     let key_presses = channels.key_presses.take().unwrap();
     let io_command = channels.io_command.take().unwrap();
     let io_idle = channels.io_idle.take().unwrap();
+    let io_filename = channels.io_filename.take().unwrap();
+    let io_input = channels.io_input.take().unwrap();
+    let io_output = channels.io_output.take().unwrap();
+    let io_snapshot = channels.io_snapshot.take().unwrap();
 
-    // TODO: Create a 2D vector to store the world.
+    // Find a bad output name now rather than once every turn has been run.
+    io::output_name(&params.output_name, params.image_width, params.image_height, 0)?;
 
-    let turn = 0;
-    events.send(
-        Event::StateChange { completed_turns: turn, new_state: State::Executing })?;
+    let mut distributor = Distributor {
+        engine: build_engine(&params)?,
+        turns_per_step: 1,
+        origin: CellCoord::new(0, 0),
+        width: params.image_width,
+        height: params.image_height,
+        history: History::new(params.history),
+        stability: Stability::new(),
+        params,
+        events,
+        key_presses,
+        io_command,
+        io_idle,
+        io_filename,
+        io_input,
+        io_output,
+        io_snapshot,
+        turn: 0,
+    };
 
-    // TODO: Execute all turns of the Game of Life.
+    let world = distributor.read_world()?;
+    distributor.engine.load(&world)?;
+    let initial = world.iter().enumerate()
+        .flat_map(|(y, row)|
+            row.iter().enumerate()
+                .filter(|(_, state)| !state.is_dead())
+                .map(move |(x, _)| CellCoord::new(x, y)))
+        .collect::<Vec<_>>();
+    for &cell in &initial {
+        distributor.stability.change(cell.offset(distributor.origin), CellState::DEAD, distributor.engine.state(cell));
+    }
+    distributor.stability.seen(distributor.turn, 0);
+    let edges = distributor.edges_reached(&initial);
+    distributor.report_changes(initial)?;
+    distributor.grow(edges)?;
+    distributor.events.send(
+        Event::StateChange { completed_turns: distributor.turn, new_state: State::Executing })?;
 
-    // TODO: Report the final state using FinalTurnCompleteEvent.
+    let mut last_report = Instant::now();
+    let mut control = Control::Continue;
+    while (distributor.turn as usize) < distributor.params.turns {
+        control = distributor.handle_key_presses()?;
+        if control != Control::Continue {
+            break;
+        }
+        if last_report.elapsed() >= ALIVE_CELLS_INTERVAL {
+            last_report = Instant::now();
+            distributor.report_alive_cells()?;
+        }
+        control = distributor.step()?;
+        if control != Control::Continue {
+            break;
+        }
+    }
 
+    let alive = distributor.engine.alive_cells().into_iter()
+        .map(|cell| cell.offset(distributor.origin))
+        .collect();
+    distributor.events.send(
+        Event::FinalTurnComplete { completed_turns: distributor.turn, alive })?;
+    distributor.write_world()?;
 
     // Make sure that the Io has finished any output before exiting.
-    io_command.send(IoCommand::IoCheckIdle)?;
-    io_idle.recv()?;
+    distributor.wait_io_idle()?;
 
-    events.send(
-        Event::StateChange { completed_turns: turn, new_state: State::Quitting })?;
+    if control == Control::Kill {
+        distributor.engine.shutdown()?;
+    }
+    distributor.events.send(
+        Event::StateChange { completed_turns: distributor.turn, new_state: State::Quitting })?;
     Ok(())
 }
+
+/// Build the selected engine. An unbounded world is stepped as a plane that grows before any
+/// alive cell can reach past its edges, so the engine must support a plane and the rule must
+/// keep empty space empty.
+fn build_engine(params: &Params) -> Result<Box<dyn Engine>> {
+    if !params.unbounded {
+        return params.engine.build(params);
+    }
+    if !matches!(params.topology, Topology::Torus | Topology::Plane) {
+        bail!("An unbounded world has no edges to join, so it cannot use the {} topology", params.topology);
+    }
+    if !params.engine.supports_topology(Topology::Plane) {
+        bail!("The {:?} engine does not support an unbounded world", params.engine);
+    }
+    if params.rule.transition(0) {
+        bail!("The rule {} gives birth with no neighbours, which would fill an unbounded world", params.rule);
+    }
+    params.engine.build(&Params { topology: Topology::Plane, ..params.clone() })
+}
+
+/// The sides of a world that alive cells came within reach of.
+#[derive(Debug, Default, Clone, Copy)]
+struct Edges {
+    north: bool,
+    east: bool,
+    south: bool,
+    west: bool,
+}
+
+impl Distributor {
+    fn filename(&self) -> String {
+        format!("{}x{}", self.params.image_width, self.params.image_height)
+    }
+
+    /// Ask the Io for the input image, or for the snapshot to resume from.
+    fn read_world(&mut self) -> Result<Vec<Vec<CellState>>> {
+        if let Some(path) = self.params.resume.clone() {
+            return self.resume(&path);
+        }
+        self.io_command.send(IoCommand::IoInput)?;
+        self.io_filename.send(self.filename())?;
+        let mut world = vec![vec![CellState::DEAD; self.params.image_width]; self.params.image_height];
+        for row in world.iter_mut() {
+            for cell in row.iter_mut() {
+                *cell = self.io_input.recv()?;
+            }
+        }
+        Ok(world)
+    }
+
+    /// Ask the Io for a snapshot and carry on from the turn it was taken at,
+    /// with the world grown as it was if it is unbounded.
+    fn resume(&mut self, path: &Path) -> Result<Vec<Vec<CellState>>> {
+        self.io_command.send(IoCommand::IoResume)?;
+        self.io_filename.send(path.display().to_string())?;
+        let header = self.io_snapshot.recv()??;
+        header.check(&self.params)?;
+        self.turn = header.turn;
+        self.origin = header.origin;
+        (self.width, self.height) = (header.width, header.height);
+        let mut world = vec![vec![CellState::DEAD; self.width]; self.height];
+        for row in world.iter_mut() {
+            for cell in row.iter_mut() {
+                *cell = self.io_input.recv()?;
+            }
+        }
+        Ok(world)
+    }
+
+    /// Ask the Io to write the whole world and what is needed to resume it
+    /// to `{output directory}/{image width}x{image height}x{turn}.snap`.
+    fn write_snapshot(&self) -> Result<()> {
+        let header = SnapshotHeader::new(&self.params, self.turn, self.origin, self.width, self.height);
+        self.io_command.send(IoCommand::IoSnapshot(Box::new(header)))?;
+        self.io_filename.send(format!("{}x{}", self.filename(), self.turn))?;
+        for row in self.engine.world() {
+            for cell in row {
+                self.io_output.send(cell)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Ask the Io to write the current world to the output directory, named by the output name template.
+    /// An unbounded world is cropped to the bounding box of its cells, whose size names the file.
+    fn write_world(&self) -> Result<()> {
+        let world = self.engine.world();
+        let (corner, width, height) = if self.params.unbounded {
+            bounding_box(&world)
+        } else {
+            (CellCoord::new(0, 0), self.width, self.height)
+        };
+        let filename = io::output_name(&self.params.output_name, width, height, self.turn)?;
+        self.io_command.send(if self.params.unbounded {
+            IoCommand::IoOutputRegion { origin: corner.offset(self.origin), width, height }
+        } else {
+            IoCommand::IoOutput
+        })?;
+        self.io_filename.send(filename.clone())?;
+        for row in &world[corner.y..corner.y + height] {
+            for &cell in &row[corner.x..corner.x + width] {
+                self.io_output.send(cell)?;
+            }
+        }
+        self.wait_io_idle()?;
+        self.events.send(Event::ImageOutputComplete { completed_turns: self.turn, filename })?;
+        Ok(())
+    }
+
+    fn wait_io_idle(&self) -> Result<()> {
+        self.io_command.send(IoCommand::IoCheckIdle)?;
+        self.io_idle.recv()?;
+        Ok(())
+    }
+
+    fn report_alive_cells(&self) -> Result<()> {
+        self.events.send(Event::AliveCellsCount {
+            completed_turns: self.turn,
+            cells_count: self.engine.population() as u32,
+        })?;
+        Ok(())
+    }
+
+    /// Tell the GUI about cells that changed state in world coordinates: as flips under Life-like rules,
+    /// or with the state each cell is now in under Generations rules.
+    fn report_changes(&self, cells: Vec<CellCoord>) -> Result<()> {
+        if cells.is_empty() {
+            return Ok(());
+        }
+        let event = if self.params.rule.is_generations() {
+            let cells = cells.into_iter()
+                .map(|cell| (cell.offset(self.origin), self.engine.state(cell)))
+                .collect();
+            Event::CellsChanged { completed_turns: self.turn, cells }
+        } else {
+            let cells = cells.into_iter().map(|cell| cell.offset(self.origin)).collect();
+            Event::CellsFlipped { completed_turns: self.turn, cells }
+        };
+        self.events.send(event)?;
+        Ok(())
+    }
+
+    /// Handle any pending key presses without blocking the turn loop.
+    fn handle_key_presses(&mut self) -> Result<Control> {
+        while let Ok(key) = self.key_presses.try_recv() {
+            let control = self.handle_key(key)?;
+            if control != Control::Continue {
+                return Ok(control);
+            }
+        }
+        Ok(Control::Continue)
+    }
+
+    fn handle_key(&mut self, key: Keycode) -> Result<Control> {
+        match key {
+            Keycode::S => self.write_world()?,
+            Keycode::Q => return Ok(Control::Quit),
+            Keycode::K => return Ok(Control::Kill),
+            Keycode::P => return self.pause(),
+            _ => (),
+        }
+        Ok(Control::Continue)
+    }
+
+    /// Block until `P` is pressed again, still serving `S`, `Q` and `K` in the meantime,
+    /// and stepping back a turn on every `B`.
+    fn pause(&mut self) -> Result<Control> {
+        self.events.send(
+            Event::StateChange { completed_turns: self.turn, new_state: State::Pause })?;
+        loop {
+            match self.key_presses.recv()? {
+                Keycode::P => break,
+                Keycode::B => self.step_back()?,
+                key => {
+                    let control = self.handle_key(key)?;
+                    if control != Control::Continue {
+                        return Ok(control);
+                    }
+                },
+            }
+        }
+        self.events.send(
+            Event::StateChange { completed_turns: self.turn, new_state: State::Executing })?;
+        Ok(Control::Continue)
+    }
+
+    /// Advance the world with the selected engine and report the cells that changed,
+    /// and whether the world has stabilised and the run should stop early.
+    fn step(&mut self) -> Result<Control> {
+        let remaining = (self.params.turns - self.turn as usize) as u64;
+        // The state a cell changed from is worked out from the state it changed to, which only
+        // holds over more than one turn while cells have no more than two states.
+        let max_turns = match self.params.rule.states() {
+            2 => self.engine.max_turns_per_step(),
+            _ => 1,
+        };
+        let turns = self.turns_per_step.min(max_turns).min(remaining);
+        let started = Instant::now();
+        self.engine.step(turns)?;
+        // Every step kept in the history is a single turn, so that B steps back exactly one.
+        let jump = !self.stability.is_settling() && !self.history.is_enabled();
+        if started.elapsed() < STEP_TARGET && turns == self.turns_per_step && jump {
+            self.turns_per_step = self.turns_per_step.saturating_mul(2);
+        } else if started.elapsed() > STEP_TARGET * 2 && self.turns_per_step > 1 {
+            self.turns_per_step /= 2;
+        }
+
+        self.turn += turns as u32;
+        let changed = self.engine.diff();
+        self.record(turns, &changed);
+        for &cell in &changed {
+            let state = self.engine.state(cell);
+            self.stability.change(cell.offset(self.origin), self.params.rule.previous_state(state), state);
+        }
+        let period = self.stability.seen(self.turn, turns);
+        if self.stability.is_settling() {
+            self.turns_per_step = 1;
+        }
+        let edges = self.edges_reached(&changed);
+        self.report_changes(changed)?;
+        self.grow(edges)?;
+        self.events.send(Event::TurnComplete { completed_turns: self.turn })?;
+        let every = self.params.snapshot_every as u32;
+        if every > 0 && self.turn / every > (self.turn - turns as u32) / every {
+            self.write_snapshot()?;
+        }
+        if let Some(period) = period {
+            self.events.send(Event::Stabilised { completed_turns: self.turn, period })?;
+            if self.params.stop_on_stable {
+                return Ok(Control::Quit);
+            }
+        }
+        Ok(Control::Continue)
+    }
+
+    /// Remember how to undo a step that took `turns` turns and changed `cells`.
+    /// Their earlier states are not kept by the engine, but follow from the states they changed to.
+    fn record(&mut self, turns: u64, cells: &[CellCoord]) {
+        if !self.history.is_enabled() {
+            return;
+        }
+        let cells = cells.iter()
+            .map(|&cell| {
+                let state = self.engine.state(cell);
+                (cell.offset(self.origin), state.get() ^ self.params.rule.previous_state(state).get())
+            })
+            .collect();
+        self.history.record(Delta { turns, cells });
+    }
+
+    /// Undo the most recent step still in the history, and report the cells it changed back
+    /// as of the turn before it. Does nothing once the history runs out.
+    fn step_back(&mut self) -> Result<()> {
+        let Some(delta) = self.history.pop() else {
+            return Ok(());
+        };
+        let cells = delta.cells.into_iter()
+            .map(|(cell, xor)| {
+                let cell = CellCoord::new((cell.x - self.origin.x) as usize, (cell.y - self.origin.y) as usize);
+                let before = self.engine.state(cell);
+                let state = CellState::new(before.get() ^ xor);
+                self.stability.change(cell.offset(self.origin), before, state);
+                (cell, state)
+            })
+            .collect::<Vec<_>>();
+        self.engine.set(&cells)?;
+        self.turn -= delta.turns as u32;
+        self.stability.rewind(self.turn);
+        self.report_changes(cells.into_iter().map(|(cell, _)| cell).collect())?;
+        self.events.send(Event::TurnComplete { completed_turns: self.turn })?;
+        Ok(())
+    }
+
+    /// The edges of an unbounded world that any of `cells` is alive within reach of.
+    /// Only cells that changed need checking: any other alive cell was already moved away
+    /// from the edges by the growth that followed its own change.
+    fn edges_reached(&self, cells: &[CellCoord]) -> Edges {
+        let mut edges = Edges::default();
+        if !self.params.unbounded {
+            return edges;
+        }
+        let reach = self.params.rule.reach();
+        for &cell in cells.iter().filter(|&&cell| self.engine.state(cell).is_alive()) {
+            edges.north |= cell.y < reach;
+            edges.east |= cell.x + reach >= self.width;
+            edges.south |= cell.y + reach >= self.height;
+            edges.west |= cell.x < reach;
+        }
+        edges
+    }
+
+    /// Pad the world with a dead tile on every side in `edges`, moving the origin
+    /// so that world coordinates of existing cells stay the same.
+    fn grow(&mut self, edges: Edges) -> Result<()> {
+        let tile = |grow: bool| if grow { TILE } else { 0 };
+        let (north, east, south, west) = (tile(edges.north), tile(edges.east), tile(edges.south), tile(edges.west));
+        if north + east + south + west == 0 {
+            return Ok(());
+        }
+        let (width, height) = (west + self.width + east, north + self.height + south);
+        let mut world = vec![vec![CellState::DEAD; width]; height];
+        for (row, old) in world[north..].iter_mut().zip(self.engine.world()) {
+            row[west..west + old.len()].copy_from_slice(&old);
+        }
+        self.engine.load(&world)?;
+        self.origin = CellCoord::new(self.origin.x - west as i64, self.origin.y - north as i64);
+        (self.width, self.height) = (width, height);
+        Ok(())
+    }
+}
+
+/// The top left corner, width and height of the smallest box holding every cell that is not dead.
+/// An empty world has an empty box at the top left.
+fn bounding_box(world: &[Vec<CellState>]) -> (CellCoord, usize, usize) {
+    let cells = world.iter().enumerate()
+        .flat_map(|(y, row)|
+            row.iter().enumerate()
+                .filter(|(_, state)| !state.is_dead())
+                .map(move |(x, _)| (x, y)));
+    let bounds = cells.fold(None, |bounds: Option<(usize, usize, usize, usize)>, (x, y)| Some(match bounds {
+        None => (x, y, x, y),
+        Some((left, top, right, bottom)) => (left.min(x), top.min(y), right.max(x), bottom.max(y)),
+    }));
+    match bounds {
+        Some((left, top, right, bottom)) => (CellCoord::new(left, top), right - left + 1, bottom - top + 1),
+        None => (CellCoord::new(0, 0), 0, 0),
+    }
+}
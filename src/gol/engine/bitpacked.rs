@@ -0,0 +1,106 @@
+use crate::gol::engine::{strip_bounds, Engine};
+use crate::gol::rule::Rule;
+use crate::gol::topology::Topology;
+use crate::util::bitworld::BitWorld;
+use crate::util::cell::{CellCoord, CellState, CellValue};
+use anyhow::Result;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+/// BitPacked steps a `BitWorld` in horizontal strips on a worker pool,
+/// keeping the previous world around to diff against.
+pub struct BitPacked {
+    pool: ThreadPool,
+    threads: usize,
+    rule: Rule,
+    topology: Topology,
+    world: BitWorld,
+    previous: BitWorld,
+}
+
+impl BitPacked {
+    pub fn new(threads: usize, rule: Rule, topology: Topology) -> Result<Self> {
+        Ok(BitPacked {
+            pool: ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .thread_name(|i| format!("gol-worker-{}", i))
+                .build()?,
+            threads,
+            rule,
+            topology,
+            world: BitWorld::new(0, 0),
+            previous: BitWorld::new(0, 0),
+        })
+    }
+
+    fn step_once(&self) -> BitWorld {
+        let (height, threads) = (self.world.height(), self.threads);
+        let mut next = BitWorld::new(self.world.width(), height);
+        let words_per_row = next.words_per_row();
+        let (world, rule, topology) = (&self.world, &self.rule, self.topology);
+        self.pool.scope(|scope| {
+            let mut rest = next.rows_mut(0, height);
+            for i in 0..threads {
+                let (start_y, end_y) = strip_bounds(i, threads, height);
+                let (strip, tail) = rest.split_at_mut((end_y - start_y) * words_per_row);
+                rest = tail;
+                scope.spawn(move |_| world.step_rows(rule, topology, start_y, strip));
+            }
+        });
+        next
+    }
+}
+
+impl Engine for BitPacked {
+    fn load(&mut self, world: &[Vec<CellState>]) -> Result<()> {
+        let (width, height) = (world.first().map_or(0, |row| row.len()), world.len());
+        let cells = world.iter().flatten().map(|&state| CellValue::from(state)).collect::<Vec<_>>();
+        self.world = BitWorld::from_cells(width, height, &cells);
+        self.previous = self.world.clone();
+        Ok(())
+    }
+
+    fn step(&mut self, turns: u64) -> Result<()> {
+        if turns == 0 {
+            self.previous = self.world.clone();
+        }
+        for turn in 0..turns {
+            let next = self.step_once();
+            let before = std::mem::replace(&mut self.world, next);
+            if turn == 0 {
+                self.previous = before;
+            }
+        }
+        Ok(())
+    }
+
+    fn population(&self) -> u64 {
+        self.world.population() as u64
+    }
+
+    fn alive_cells(&self) -> Vec<CellCoord> {
+        self.world.alive_cells()
+    }
+
+    fn diff(&self) -> Vec<CellCoord> {
+        self.previous.diff(&self.world)
+    }
+
+    fn state(&self, cell: CellCoord) -> CellState {
+        self.world.get(cell.x, cell.y).into()
+    }
+
+    fn world(&self) -> Vec<Vec<CellState>> {
+        self.world.to_cells()
+            .chunks(self.world.width().max(1))
+            .map(|row| row.iter().map(|&cell| cell.into()).collect())
+            .collect()
+    }
+
+    fn set(&mut self, cells: &[(CellCoord, CellState)]) -> Result<()> {
+        for &(cell, state) in cells {
+            self.world.set(cell.x, cell.y, state.into());
+            self.previous.set(cell.x, cell.y, state.into());
+        }
+        Ok(())
+    }
+}
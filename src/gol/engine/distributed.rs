@@ -0,0 +1,302 @@
+use crate::gol::engine::{alive_cells, strip_bounds, Engine};
+use crate::gol::protocol::{self, Counted, Load, Reply, Request, Stepped};
+use crate::gol::rule::Rule;
+use crate::gol::topology::Topology;
+use crate::gol::worker;
+use crate::util::cell::{CellCoord, CellState};
+use anyhow::{anyhow, bail, Context, Result};
+use std::io::{BufReader, BufWriter, ErrorKind};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::Duration;
+
+/// How many rounds in a row the workers may fail without any of them being lost, as when the
+/// halo link between two of them breaks, before the broker gives up on the run.
+const MAX_FAILURES: usize = 3;
+
+/// Distributed is the broker side of the broker and worker mode. Each worker keeps a horizontal
+/// strip of the world between turns and exchanges only the cells around it with the workers
+/// holding them, so the broker sends nothing but the number of turns to take and gets back
+/// the population and the cells that changed. It keeps its own copy of the world from those.
+///
+/// A worker that is lost, or silent for longer than the timeout, is dropped. Its strip is given
+/// to the workers left by loading them with the broker's copy of the world, which is always
+/// that of the last turn every worker finished, and the turns that were under way are taken again.
+/// Workers that fail a turn without being lost, usually because a peer was, are loaded again the same way.
+///
+/// Without any worker addresses it starts `threads` workers of its own on 127.0.0.1,
+/// each serving just this engine until it is dropped.
+pub struct Distributed {
+    rule: Rule,
+    topology: Topology,
+    timeout: Duration,
+    workers: Vec<Connection>,
+    /// How many of the workers hold a strip, as there may be more workers than rows.
+    loaded: usize,
+    /// How many times the workers have been loaded.
+    epoch: usize,
+    /// How many rounds in a row the workers have failed without any of them being lost.
+    failures: usize,
+    world: Vec<Vec<CellState>>,
+    population: u64,
+    flipped: Vec<CellCoord>,
+    traffic: Traffic,
+}
+
+/// Traffic is how many bytes a distributed engine has moved since it was loaded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Traffic {
+    pub turns: u64,
+    /// Bytes sent and received by the broker, besides loading the world.
+    pub broker: u64,
+    /// Bytes of halo cells sent between the workers.
+    pub halo: u64,
+}
+
+/// Connection is a worker the broker steps strips on.
+struct Connection {
+    address: SocketAddr,
+    /// Where the worker exchanges halo cells with the other workers.
+    halo: SocketAddr,
+    reader: BufReader<Counted<TcpStream>>,
+    writer: BufWriter<Counted<TcpStream>>,
+    /// The cells of the strip the worker holds, which are all a reply can say changed.
+    cells: usize,
+}
+
+impl Connection {
+    fn connect(address: SocketAddr, timeout: Duration) -> Result<Self> {
+        let stream = TcpStream::connect_timeout(&address, timeout)
+            .with_context(|| format!("Cannot connect to the worker at {}", address))?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(timeout))?;
+        // A worker that stops reading would otherwise block sending it a strip once the socket buffers fill up.
+        stream.set_write_timeout(Some(timeout))?;
+        let mut reader = BufReader::new(Counted::new(stream.try_clone()?));
+        let halo = protocol::read_address(&mut reader)
+            .with_context(|| format!("The worker at {} did not say where it exchanges halo cells", address))?;
+        Ok(Connection { address, halo, reader, writer: BufWriter::new(Counted::new(stream)), cells: 0 })
+    }
+
+    fn bytes(&self) -> u64 {
+        self.reader.get_ref().bytes() + self.writer.get_ref().bytes()
+    }
+
+    fn send(&mut self, request: &Request) -> Result<()> {
+        protocol::write_request(&mut self.writer, request).map_err(|error| self.lost(error))
+    }
+
+    /// Wait for the worker to finish a request, skipping the heartbeats it sends until then.
+    fn receive(&mut self) -> Result<Reply> {
+        loop {
+            let reply = protocol::read_reply(&mut self.reader, self.cells).map_err(|error| self.lost(error))?;
+            match reply {
+                Reply::Heartbeat => continue,
+                reply => return Ok(reply),
+            }
+        }
+    }
+
+    /// Explain why talking to the worker failed, telling a silent worker from a lost one.
+    fn lost(&self, error: anyhow::Error) -> anyhow::Error {
+        match error.downcast_ref::<std::io::Error>().map(std::io::Error::kind) {
+            Some(ErrorKind::WouldBlock | ErrorKind::TimedOut) => anyhow!("The worker at {} stopped responding", self.address),
+            _ => error.context(format!("Lost the worker at {}", self.address)),
+        }
+    }
+}
+
+impl Distributed {
+    pub fn new(addresses: &[SocketAddr], threads: usize, timeout: Duration, rule: Rule, topology: Topology) -> Result<Self> {
+        let workers = if addresses.is_empty() {
+            (0..threads.max(1)).map(|_| start_local_worker(timeout)).collect::<Result<Vec<_>>>()?
+        } else {
+            addresses.iter().map(|&address| Connection::connect(address, timeout)).collect::<Result<Vec<_>>>()?
+        };
+        Ok(Distributed {
+            rule,
+            topology,
+            timeout,
+            workers,
+            loaded: 0,
+            epoch: 0,
+            failures: 0,
+            world: Vec::new(),
+            population: 0,
+            flipped: Vec::new(),
+            traffic: Traffic::default(),
+        })
+    }
+
+    /// The bytes moved since the world was last loaded.
+    pub fn traffic(&self) -> Traffic {
+        self.traffic
+    }
+
+    /// How many workers are left.
+    pub fn workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    fn bytes(&self) -> u64 {
+        self.workers.iter().map(Connection::bytes).sum()
+    }
+
+    /// Send a request to each of the first workers and wait for all of their replies.
+    /// Workers that are lost are dropped, and the replies are only returned if every worker stepped.
+    /// Otherwise the workers need loading again, unless they have failed too many times in a row.
+    fn round(&mut self, requests: Vec<Request>) -> Result<Option<Vec<Stepped>>> {
+        let mut lost = vec![false; requests.len()];
+        for ((worker, request), lost) in self.workers.iter_mut().zip(&requests).zip(&mut lost) {
+            if let Err(error) = worker.send(request) {
+                log::warn!(target: "Broker", "{:#}", error);
+                *lost = true;
+            }
+        }
+        let mut replies = Vec::new();
+        let mut failed = false;
+        for (worker, lost) in self.workers.iter_mut().zip(&mut lost).filter(|(_, lost)| !**lost) {
+            match worker.receive() {
+                Ok(Reply::Stepped(stepped)) => replies.push(stepped),
+                Ok(reply) => {
+                    log::warn!(target: "Broker", "The worker at {} failed: {:?}", worker.address, reply);
+                    failed = true;
+                },
+                Err(error) => {
+                    log::warn!(target: "Broker", "{:#}", error);
+                    *lost = true;
+                },
+            }
+        }
+        if !lost.contains(&true) {
+            if !failed {
+                return Ok(Some(replies));
+            }
+            self.failures += 1;
+            if self.failures > MAX_FAILURES {
+                bail!("The workers failed {} times in a row without any of them being lost", self.failures);
+            }
+            log::warn!(target: "Broker", "Giving the world to the workers again after they failed");
+            return Ok(None);
+        }
+        let mut lost = lost.into_iter();
+        self.workers.retain(|_| !lost.next().unwrap_or(false));
+        if self.workers.is_empty() {
+            bail!("Every worker has been lost");
+        }
+        log::warn!(target: "Broker", "Giving the world to the {} workers left", self.workers.len());
+        Ok(None)
+    }
+
+    /// Give the strips of the broker's copy of the world to the workers, until every one is loaded.
+    fn reload(&mut self) -> Result<()> {
+        loop {
+            let (width, height) = (self.world.first().map_or(0, Vec::len), self.world.len());
+            self.loaded = self.workers.len().min(height);
+            self.epoch += 1;
+            let strips = (0..self.loaded).map(|i| strip_bounds(i, self.loaded, height)).collect::<Vec<_>>();
+            let peers = self.workers.iter().take(self.loaded).map(|worker| worker.halo).collect::<Vec<_>>();
+            for (index, worker) in self.workers.iter_mut().enumerate() {
+                worker.cells = strips.get(index).map_or(0, |&(start, end)| (end - start) * width);
+            }
+            // Workers past the last strip are loaded too, so that they drop any strip they held.
+            let requests = (0..self.workers.len())
+                .map(|index| Request::Load(Box::new(Load {
+                    rule: self.rule,
+                    topology: self.topology,
+                    width,
+                    height,
+                    index,
+                    epoch: self.epoch,
+                    timeout: self.timeout,
+                    strips: strips.clone(),
+                    peers: peers.clone(),
+                    cells: strips.get(index).map_or(Vec::new(), |&(start, end)| self.world[start..end].concat()),
+                })))
+                .collect();
+            if let Some(replies) = self.round(requests)? {
+                self.population = replies.iter().map(|stepped| stepped.population).sum();
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl Engine for Distributed {
+    fn load(&mut self, world: &[Vec<CellState>]) -> Result<()> {
+        self.world = world.to_vec();
+        self.flipped.clear();
+        self.failures = 0;
+        self.reload()?;
+        self.traffic = Traffic::default();
+        Ok(())
+    }
+
+    fn step(&mut self, turns: u64) -> Result<()> {
+        let before = self.bytes();
+        let request = Request::Step { turns: u32::try_from(turns)? };
+        let replies = loop {
+            if let Some(replies) = self.round(vec![request.clone(); self.loaded])? {
+                break replies;
+            }
+            // The turns are taken again from the world as it was before them.
+            self.reload()?;
+        };
+        self.failures = 0;
+        self.population = 0;
+        self.flipped.clear();
+        for stepped in replies {
+            self.population += stepped.population;
+            self.traffic.halo += stepped.halo_bytes;
+            for (cell, state) in stepped.flips {
+                self.world[cell.y][cell.x] = state;
+                self.flipped.push(cell);
+            }
+        }
+        self.traffic.turns += turns;
+        // Dropped workers take their counts with them, so a recovery can leave fewer bytes than before.
+        self.traffic.broker += self.bytes().saturating_sub(before);
+        Ok(())
+    }
+
+    fn population(&self) -> u64 {
+        self.population
+    }
+
+    fn alive_cells(&self) -> Vec<CellCoord> {
+        alive_cells(&self.world)
+    }
+
+    fn diff(&self) -> Vec<CellCoord> {
+        self.flipped.clone()
+    }
+
+    fn state(&self, cell: CellCoord) -> CellState {
+        self.world[cell.y][cell.x]
+    }
+
+    fn world(&self) -> Vec<Vec<CellState>> {
+        self.world.clone()
+    }
+
+    fn shutdown(&mut self) -> Result<()> {
+        for worker in &mut self.workers {
+            worker.send(&Request::Shutdown)
+                .with_context(|| format!("Cannot shut down the worker at {}", worker.address))?;
+        }
+        Ok(())
+    }
+}
+
+/// Start a worker on a thread of this process that serves a single connection to it.
+fn start_local_worker(timeout: Duration) -> Result<Connection> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let address = listener.local_addr()?;
+    std::thread::Builder::new()
+        .name(format!("gol-worker-{}", address.port()))
+        .spawn(move || {
+            if let Err(error) = listener.accept().map_err(Into::into).and_then(|(stream, _)| worker::serve_connection(stream)) {
+                log::warn!(target: "Worker", "The worker at {} stopped: {:#}", address, error);
+            }
+        })?;
+    Connection::connect(address, timeout)
+}
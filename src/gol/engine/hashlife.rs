@@ -0,0 +1,368 @@
+use crate::gol::engine::Engine;
+use crate::gol::rule::Rule;
+use crate::util::cell::{CellCoord, CellState};
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+
+type NodeId = u32;
+
+/// Leaf nodes (level 0) are the two canonical single cells.
+const DEAD: NodeId = 0;
+const ALIVE: NodeId = 1;
+
+/// Rough per-entry overhead of the hash tables on top of the stored key and value.
+const TABLE_OVERHEAD: usize = 16;
+
+/// Node is a canonical quadtree square of side `2^level`.
+/// Children are ordered north-west, north-east, south-west, south-east.
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    level: u8,
+    children: [NodeId; 4],
+    population: u64,
+}
+
+/// HashLife advances a power-of-two torus with a memoised quadtree.
+///
+/// The torus is stored as a single tile of side `2^level`, which repeats in every
+/// direction to form an infinite periodic plane. A square of 4^m copies of the tile is
+/// a canonical node like any other, so its centre after `2^j` generations is served
+/// from the result cache and its north-west tile is the torus after those generations.
+///
+/// The node cache is garbage collected between jumps whenever its estimated size goes
+/// over `memory_cap` bytes: the result cache is dropped and only the nodes reachable
+/// from the current and previous tiles are kept. If those alone do not fit, stepping fails.
+pub struct HashLife {
+    width: usize,
+    height: usize,
+    level: u8,
+    tile: NodeId,
+    previous: NodeId,
+    rule: Rule,
+    memory_cap: usize,
+    nodes: Vec<Node>,
+    index: HashMap<[NodeId; 4], NodeId, BuildFxHasher>,
+    results: HashMap<(NodeId, u8), NodeId, BuildFxHasher>,
+    empty: Vec<NodeId>,
+}
+
+impl HashLife {
+    /// Create an empty engine for `rule` whose caches are collected above `memory_cap` bytes.
+    pub fn new(memory_cap: usize, rule: Rule) -> Self {
+        HashLife {
+            width: 1,
+            height: 1,
+            level: 0,
+            tile: DEAD,
+            previous: DEAD,
+            rule,
+            memory_cap,
+            nodes: vec![
+                Node { level: 0, children: [DEAD; 4], population: 0 },
+                Node { level: 0, children: [ALIVE; 4], population: 1 },
+            ],
+            index: HashMap::default(),
+            results: HashMap::default(),
+            empty: vec![DEAD],
+        }
+    }
+
+    /// Estimated size of the node and result caches in bytes.
+    pub fn memory_usage(&self) -> usize {
+        let node = std::mem::size_of::<Node>() + std::mem::size_of::<([NodeId; 4], NodeId)>();
+        let result = std::mem::size_of::<((NodeId, u8), NodeId)>();
+        self.nodes.len() * (node + TABLE_OVERHEAD) + self.results.len() * (result + TABLE_OVERHEAD)
+    }
+
+    /// Advance the tile by `2^j` generations.
+    fn jump(&mut self, j: u8) {
+        // The centre of a square of 4^m tiles starts on a tile boundary for m >= 2,
+        // and it must be at least 2 levels above the jump for the result to exist.
+        let m = 2.max((j + 2).saturating_sub(self.level));
+        let mut universe = self.tile;
+        for _ in 0..m {
+            universe = self.join([universe; 4]);
+        }
+        let mut result = self.next(universe, j);
+        while self.nodes[result as usize].level > self.level {
+            result = self.nodes[result as usize].children[0];
+        }
+        self.tile = result;
+    }
+
+    /// Drop the caches if they have grown past the memory cap, keeping the current and previous tiles.
+    fn collect_garbage(&mut self) -> Result<()> {
+        if self.memory_usage() <= self.memory_cap {
+            return Ok(());
+        }
+        let nodes = std::mem::take(&mut self.nodes);
+        self.nodes = nodes[..2].to_vec();
+        self.index.clear();
+        self.results.clear();
+        self.empty = vec![DEAD];
+        let mut remap = HashMap::<NodeId, NodeId, BuildFxHasher>::default();
+        self.tile = self.copy(&nodes, self.tile, &mut remap);
+        self.previous = self.copy(&nodes, self.previous, &mut remap);
+        if self.memory_usage() > self.memory_cap {
+            bail!(
+                "HashLife needs {} bytes for the current world, which is over the {} byte cap",
+                self.memory_usage(), self.memory_cap
+            );
+        }
+        Ok(())
+    }
+
+    fn copy(
+        &mut self,
+        from: &[Node],
+        id: NodeId,
+        remap: &mut HashMap<NodeId, NodeId, BuildFxHasher>,
+    ) -> NodeId {
+        if id == DEAD || id == ALIVE {
+            return id;
+        }
+        if let Some(&new_id) = remap.get(&id) {
+            return new_id;
+        }
+        let children = from[id as usize].children.map(|child| self.copy(from, child, remap));
+        let new_id = self.join(children);
+        remap.insert(id, new_id);
+        new_id
+    }
+
+    fn build(&mut self, world: &[Vec<CellState>], x: usize, y: usize, level: u8) -> NodeId {
+        if level == 0 {
+            // Worlds smaller than the tile repeat to fill it.
+            return if world[y % self.height][x % self.width].is_alive() { ALIVE } else { DEAD };
+        }
+        let half = 1 << (level - 1);
+        let children = [(x, y), (x + half, y), (x, y + half), (x + half, y + half)]
+            .map(|(x, y)| self.build(world, x, y, level - 1));
+        self.join(children)
+    }
+
+    fn collect(&self, id: NodeId, x: usize, y: usize, cells: &mut Vec<CellCoord>) {
+        let node = self.nodes[id as usize];
+        if node.population == 0 || x >= self.width || y >= self.height {
+            return;
+        }
+        if node.level == 0 {
+            cells.push(CellCoord::new(x, y));
+            return;
+        }
+        let half = 1 << (node.level - 1);
+        self.collect(node.children[0], x, y, cells);
+        self.collect(node.children[1], x + half, y, cells);
+        self.collect(node.children[2], x, y + half, cells);
+        self.collect(node.children[3], x + half, y + half, cells);
+    }
+
+    fn diff_nodes(&self, a: NodeId, b: NodeId, x: usize, y: usize, cells: &mut Vec<CellCoord>) {
+        if a == b || x >= self.width || y >= self.height {
+            return;
+        }
+        let (a, b) = (self.nodes[a as usize], self.nodes[b as usize]);
+        if a.level == 0 {
+            cells.push(CellCoord::new(x, y));
+            return;
+        }
+        let half = 1 << (a.level - 1);
+        self.diff_nodes(a.children[0], b.children[0], x, y, cells);
+        self.diff_nodes(a.children[1], b.children[1], x + half, y, cells);
+        self.diff_nodes(a.children[2], b.children[2], x, y + half, cells);
+        self.diff_nodes(a.children[3], b.children[3], x + half, y + half, cells);
+    }
+
+    /// The canonical node with the given children.
+    fn join(&mut self, children: [NodeId; 4]) -> NodeId {
+        if let Some(&id) = self.index.get(&children) {
+            return id;
+        }
+        let level = self.nodes[children[0] as usize].level + 1;
+        let population = children.iter().map(|&child| self.nodes[child as usize].population).sum();
+        let id = self.nodes.len() as NodeId;
+        self.nodes.push(Node { level, children, population });
+        self.index.insert(children, id);
+        id
+    }
+
+    /// The canonical empty node at `level`.
+    fn empty(&mut self, level: u8) -> NodeId {
+        while self.empty.len() <= level as usize {
+            let below = *self.empty.last().unwrap();
+            let id = self.join([below; 4]);
+            self.empty.push(id);
+        }
+        self.empty[level as usize]
+    }
+
+    fn child(&self, id: NodeId, i: usize) -> NodeId {
+        self.nodes[id as usize].children[i]
+    }
+
+    /// The centre of a node, one level down, without advancing time.
+    fn centre(&mut self, id: NodeId) -> NodeId {
+        let [nw, ne, sw, se] = self.nodes[id as usize].children;
+        self.join([self.child(nw, 3), self.child(ne, 2), self.child(sw, 1), self.child(se, 0)])
+    }
+
+    /// The centre of a level `L` node after `2^j` generations, where `j <= L - 2`.
+    fn next(&mut self, id: NodeId, j: u8) -> NodeId {
+        let node = self.nodes[id as usize];
+        // Empty space stays empty unless the rule gives birth with no neighbours.
+        if node.population == 0 && !self.rule.transition(0) {
+            return self.empty(node.level - 1);
+        }
+        if let Some(&result) = self.results.get(&(id, j)) {
+            return result;
+        }
+        let result = if node.level == 2 {
+            self.base(id)
+        } else {
+            let [a, b, c, d] = node.children;
+            let sub = [
+                a,
+                self.join([self.child(a, 1), self.child(b, 0), self.child(a, 3), self.child(b, 2)]),
+                b,
+                self.join([self.child(a, 2), self.child(a, 3), self.child(c, 0), self.child(c, 1)]),
+                self.join([self.child(a, 3), self.child(b, 2), self.child(c, 1), self.child(d, 0)]),
+                self.join([self.child(b, 2), self.child(b, 3), self.child(d, 0), self.child(d, 1)]),
+                c,
+                self.join([self.child(c, 1), self.child(d, 0), self.child(c, 3), self.child(d, 2)]),
+                d,
+            ];
+            // At full speed both halves of the jump advance time, otherwise only the second.
+            let full_speed = j + 2 == node.level;
+            let r = sub.map(|n| if full_speed { self.next(n, j - 1) } else { self.centre(n) });
+            let quadrants = [
+                self.join([r[0], r[1], r[3], r[4]]),
+                self.join([r[1], r[2], r[4], r[5]]),
+                self.join([r[3], r[4], r[6], r[7]]),
+                self.join([r[4], r[5], r[7], r[8]]),
+            ];
+            let step = if full_speed { j - 1 } else { j };
+            let quadrants = quadrants.map(|q| self.next(q, step));
+            self.join(quadrants)
+        };
+        self.results.insert((id, j), result);
+        result
+    }
+
+    /// One generation of the centre 2x2 of a 4x4 node.
+    fn base(&mut self, id: NodeId) -> NodeId {
+        let mut cells = [[false; 4]; 4];
+        for (i, &quadrant) in self.nodes[id as usize].children.iter().enumerate() {
+            for (k, &leaf) in self.nodes[quadrant as usize].children.iter().enumerate() {
+                cells[(i / 2) * 2 + k / 2][(i % 2) * 2 + k % 2] = leaf == ALIVE;
+            }
+        }
+        let rule = self.rule;
+        let next = |x: usize, y: usize| {
+            let neighbourhood = (0..9)
+                .filter(|i| cells[y + i / 3 - 1][x + i % 3 - 1])
+                .fold(0, |index, i| index | 1 << i);
+            if rule.transition(neighbourhood) { ALIVE } else { DEAD }
+        };
+        self.join([next(1, 1), next(2, 1), next(1, 2), next(2, 2)])
+    }
+}
+
+impl Engine for HashLife {
+    /// Build the quadtree for a row-major world. Width and height must be powers of two.
+    fn load(&mut self, world: &[Vec<CellState>]) -> Result<()> {
+        let height = world.len();
+        let width = world.first().map_or(0, |row| row.len());
+        if !width.is_power_of_two() || !height.is_power_of_two() {
+            bail!("HashLife requires a power-of-two width and height, got {}x{}", width, height);
+        }
+        *self = HashLife::new(self.memory_cap, self.rule);
+        self.width = width;
+        self.height = height;
+        self.level = width.max(height).trailing_zeros() as u8;
+        self.tile = self.build(world, 0, 0, self.level);
+        self.previous = self.tile;
+        Ok(())
+    }
+
+    /// Advance the torus by exactly `turns` generations,
+    /// taken as a sum of power-of-two jumps, largest first.
+    fn step(&mut self, turns: u64) -> Result<()> {
+        self.previous = self.tile;
+        for j in (0..u64::BITS as u8).rev().filter(|j| turns >> j & 1 == 1) {
+            self.jump(j);
+            self.collect_garbage()?;
+        }
+        Ok(())
+    }
+
+    fn population(&self) -> u64 {
+        let copies = (1_u64 << (2 * self.level)) / (self.width * self.height) as u64;
+        self.nodes[self.tile as usize].population / copies
+    }
+
+    fn alive_cells(&self) -> Vec<CellCoord> {
+        let mut cells = Vec::new();
+        self.collect(self.tile, 0, 0, &mut cells);
+        cells
+    }
+
+    fn diff(&self) -> Vec<CellCoord> {
+        let mut cells = Vec::new();
+        self.diff_nodes(self.previous, self.tile, 0, 0, &mut cells);
+        cells
+    }
+
+    fn state(&self, cell: CellCoord) -> CellState {
+        let mut id = self.tile;
+        for level in (0..self.level).rev() {
+            let quadrant = (cell.y >> level & 1) << 1 | cell.x >> level & 1;
+            id = self.nodes[id as usize].children[quadrant];
+        }
+        if id == ALIVE { CellState::ALIVE } else { CellState::DEAD }
+    }
+
+    fn world(&self) -> Vec<Vec<CellState>> {
+        let mut world = vec![vec![CellState::DEAD; self.width]; self.height];
+        self.alive_cells().iter().for_each(|cell| world[cell.y][cell.x] = CellState::ALIVE);
+        world
+    }
+
+    fn max_turns_per_step(&self) -> u64 {
+        u64::MAX
+    }
+}
+
+type BuildFxHasher = BuildHasherDefault<FxHasher>;
+
+/// A small multiplicative hasher for the integer keys of the node and result caches.
+#[derive(Default)]
+struct FxHasher {
+    hash: u64,
+}
+
+impl Hasher for FxHasher {
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        bytes.iter().for_each(|&byte| self.write_u64(byte as u64));
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ i).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
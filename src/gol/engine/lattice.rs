@@ -0,0 +1,40 @@
+use crate::gol::rule::Rule;
+use crate::gol::topology::Topology;
+use crate::util::cell::{CellCoord, CellState};
+
+/// Compute the next state of the rows starting at `start_y` into `strip` under a rule on hexagons
+/// or triangles, calling `flip` with every cell that changed state.
+/// Each cell counts the neighbours its lattice gives it at its raster position.
+pub fn step_strip(
+    rule: &Rule,
+    topology: Topology,
+    world: &[Vec<CellState>],
+    start_y: usize,
+    strip: &mut [Vec<CellState>],
+    mut flip: impl FnMut(CellCoord),
+) {
+    let (width, height) = (world[0].len(), world.len());
+    let (lattice, reach) = (rule.lattice(), rule.reach());
+    for (dy, row) in strip.iter_mut().enumerate() {
+        let y = start_y + dy;
+        let border_row = y == 0 || y + 1 == height;
+        for (x, cell) in row.iter_mut().enumerate() {
+            let offsets = lattice.offsets(x, y);
+            let neighbours = if border_row || x < reach || x + reach >= width {
+                // Border cells find their neighbours past the edge through the topology.
+                offsets.iter()
+                    .filter(|&&(dx, dy)| topology.map(x as isize + dx, y as isize + dy, width, height)
+                        .is_some_and(|(x, y)| world[y][x].is_alive()))
+                    .count()
+            } else {
+                offsets.iter()
+                    .filter(|&&(dx, dy)| world[y.wrapping_add_signed(dy)][x.wrapping_add_signed(dx)].is_alive())
+                    .count()
+            };
+            *cell = rule.next_state(world[y][x], neighbours as u16);
+            if *cell != world[y][x] {
+                flip(CellCoord::new(x, y));
+            }
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use crate::gol::rule::{Neighbourhood, Rule};
+use crate::gol::topology::Topology;
+use crate::util::cell::{CellCoord, CellState};
+
+/// Compute the next state of the rows starting at `start_y` into `strip` under a Larger than Life rule,
+/// calling `flip` with every cell that changed state.
+/// Alive cells are summed once into `sums`, a table covering the strip and its margins, so a Moore count
+/// takes four lookups and a von Neumann count two lookups per row, whatever the range.
+pub fn step_strip(
+    rule: &Rule,
+    topology: Topology,
+    world: &[Vec<CellState>],
+    start_y: usize,
+    strip: &mut [Vec<CellState>],
+    sums: &mut Vec<u32>,
+    mut flip: impl FnMut(CellCoord),
+) {
+    let range = rule.range() as usize;
+    let table = SummedArea::new(topology, world, start_y as isize - range as isize, strip.len() + 2 * range, range, sums);
+    for (dy, row) in strip.iter_mut().enumerate() {
+        let y = start_y + dy;
+        for (x, cell) in row.iter_mut().enumerate() {
+            let current = world[y][x];
+            // Table rows and columns are offset by `range`, so the cell itself is at (x + range, dy + range).
+            let mut neighbours = match rule.neighbourhood() {
+                Neighbourhood::Moore => table.sum(x, dy, 2 * range + 1, 2 * range + 1),
+                Neighbourhood::VonNeumann => (0..=2 * range)
+                    .map(|i| {
+                        let half = range.abs_diff(i);
+                        table.sum(x + half, dy + i, 2 * (range - half) + 1, 1)
+                    })
+                    .sum(),
+            };
+            if !rule.middle() && current.is_alive() {
+                neighbours -= 1;
+            }
+            *cell = rule.next_state(current, neighbours as u16);
+            if *cell != current {
+                flip(CellCoord::new(x, y));
+            }
+        }
+    }
+}
+
+/// The length of the table [`step_strip`] sums a `width` by `rows` strip into.
+pub fn sums_len(rule: &Rule, width: usize, rows: usize) -> usize {
+    let range = rule.range() as usize;
+    (width + 2 * range + 1) * (rows + 2 * range + 1)
+}
+
+/// SummedArea holds the number of alive cells above and to the left of every point of a window
+/// onto the world's topology, which starts `margin` cells left of the world and is `margin` cells wider on each side.
+struct SummedArea<'a> {
+    width: usize,
+    sums: &'a [u32],
+}
+
+impl<'a> SummedArea<'a> {
+    /// Sum the window into `sums`, which only allocates if it has too little capacity.
+    fn new(topology: Topology, world: &[Vec<CellState>], start_y: isize, rows: usize, margin: usize, sums: &'a mut Vec<u32>) -> Self {
+        let (world_width, world_height) = (world[0].len(), world.len());
+        let width = world_width + 2 * margin + 1;
+        sums.clear();
+        sums.resize(width * (rows + 1), 0);
+        for i in 0..rows {
+            let y = start_y + i as isize;
+            let mut row_sum = 0;
+            for j in 0..width - 1 {
+                let x = j as isize - margin as isize;
+                row_sum += topology.map(x, y, world_width, world_height)
+                    .is_some_and(|(x, y)| world[y][x].is_alive()) as u32;
+                sums[(i + 1) * width + j + 1] = sums[i * width + j + 1] + row_sum;
+            }
+        }
+        SummedArea { width, sums }
+    }
+
+    /// The number of alive cells in the `width` by `height` rectangle with its top left corner at `(x, y)`.
+    fn sum(&self, x: usize, y: usize, width: usize, height: usize) -> u32 {
+        let at = |x: usize, y: usize| self.sums[y * self.width + x];
+        at(x + width, y + height) + at(x, y) - at(x + width, y) - at(x, y + height)
+    }
+}
@@ -0,0 +1,253 @@
+use crate::gol::lattice::Lattice;
+use crate::gol::rule::Rule;
+use crate::gol::topology::Topology;
+use crate::gol::Params;
+use crate::util::cell::{CellCoord, CellState};
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use std::time::Duration;
+
+pub mod bitpacked;
+pub mod distributed;
+pub mod hashlife;
+pub mod lattice;
+pub mod ltl;
+pub mod naive;
+pub mod parallel;
+#[cfg(target_os = "linux")]
+pub mod shared;
+
+/// `Engine` is a backend that owns the world and knows how to advance it.
+/// The distributor drives every engine the same way, so the event and Io plumbing
+/// does not depend on which one is selected.
+pub trait Engine: Send {
+    /// Replace the world with a row-major one of `image_height` rows by `image_width` cells.
+    fn load(&mut self, world: &[Vec<CellState>]) -> Result<()>;
+
+    /// Advance the world by exactly `turns` generations.
+    fn step(&mut self, turns: u64) -> Result<()>;
+
+    /// Count the alive cells.
+    fn population(&self) -> u64;
+
+    /// Coordinates of every alive cell.
+    fn alive_cells(&self) -> Vec<CellCoord>;
+
+    /// Coordinates of every cell that changed state during the last `step`.
+    fn diff(&self) -> Vec<CellCoord>;
+
+    /// The current state of a single cell.
+    fn state(&self, cell: CellCoord) -> CellState;
+
+    /// Unpack the world into row-major rows.
+    fn world(&self) -> Vec<Vec<CellState>>;
+
+    /// Set each of `cells` to the state given with it, leaving the rest of the world as it is.
+    /// Engines that hold the world cell by cell override this to set them in place rather than reloading it all.
+    fn set(&mut self, cells: &[(CellCoord, CellState)]) -> Result<()> {
+        let mut world = self.world();
+        for &(cell, state) in cells {
+            world[cell.y][cell.x] = state;
+        }
+        self.load(&world)
+    }
+
+    /// The most turns this engine should be asked to take in a single `step`.
+    /// Per-cell engines report every turn, so they keep the default of one.
+    fn max_turns_per_step(&self) -> u64 {
+        1
+    }
+
+    /// Stop anything the engine runs outside this process, as when `K` is pressed.
+    /// Engines that run in-process have nothing to stop.
+    fn shutdown(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// `EngineKind` selects the backend the distributor uses to advance the world.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum EngineKind {
+    /// A single thread stepping one byte per cell.
+    Naive,
+    /// Horizontal strips stepped on `threads` workers.
+    #[default]
+    Parallel,
+    /// 64 cells per word, with horizontal strips stepped on `threads` workers.
+    #[value(name = "bitpacked")]
+    BitPacked,
+    /// Memoised quadtree jumping many turns at a time. Needs a power-of-two world.
+    #[value(name = "hashlife")]
+    HashLife,
+    /// Horizontal strips stepped by worker processes over TCP, see `--workers`.
+    #[value(name = "distributed")]
+    Distributed,
+    /// Horizontal strips stepped by `threads` child processes through POSIX shared memory. Linux only.
+    #[value(name = "shared-memory")]
+    SharedMemory,
+}
+
+/// `Balance` selects how the strip engines cut the world between their workers.
+/// The world and the events never depend on where the strips are cut.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Balance {
+    /// Strips of equal height, cut once.
+    #[default]
+    Off,
+    /// Recut the strips every interval so that each took about as long to step as the others.
+    Time,
+    /// Recut the strips every interval by the cells each one stepped and changed rather than the time
+    /// it took, so the same run is cut the same way on any machine and under any load.
+    Work,
+}
+
+impl EngineKind {
+    /// Whether this engine can run `rule`. The byte engines run every rule,
+    /// the others only two-state rules over the 8 cells around each square cell.
+    pub fn supports(self, rule: &Rule) -> bool {
+        matches!(
+            self,
+            EngineKind::Naive | EngineKind::Parallel | EngineKind::Distributed | EngineKind::SharedMemory
+        ) || rule.is_life_like()
+    }
+
+    /// Whether this engine can join the edges of the world as `topology` does.
+    /// HashLife tiles the world to wrap it, so it only supports a torus.
+    pub fn supports_topology(self, topology: Topology) -> bool {
+        self != EngineKind::HashLife || topology.is_torus()
+    }
+
+    /// Create an empty engine of this kind for the given parameters.
+    pub fn build(self, params: &Params) -> Result<Box<dyn Engine>> {
+        if !self.supports(&params.rule) {
+            bail!("The {:?} engine does not support the rule {}", self, params.rule);
+        }
+        if !self.supports_topology(params.topology) {
+            bail!("The {:?} engine does not support the {} topology", self, params.topology);
+        }
+        let lattice = params.rule.lattice();
+        if !lattice.supports(params.topology, params.image_width, params.image_height) {
+            bail!(
+                "A {}x{} {} lattice cannot use the {} topology",
+                params.image_width, params.image_height, lattice, params.topology
+            );
+        }
+        let (rule, topology) = (params.rule, params.topology);
+        Ok(match self {
+            EngineKind::Naive => Box::new(naive::Naive::new(rule, topology)),
+            EngineKind::Parallel => {
+                if params.balance != Balance::Off && params.balance_interval == 0 {
+                    bail!("Strips cannot be balanced every 0 turns");
+                }
+                Box::new(parallel::Parallel::new(params.threads, rule, topology)?
+                    .balance(params.balance, params.balance_interval))
+            },
+            EngineKind::BitPacked => Box::new(bitpacked::BitPacked::new(params.threads, rule, topology)?),
+            EngineKind::HashLife =>
+                Box::new(hashlife::HashLife::new(params.hashlife_memory << 20, rule)),
+            EngineKind::Distributed =>
+                Box::new(distributed::Distributed::new(
+                    &params.workers,
+                    params.threads,
+                    Duration::from_millis(params.worker_timeout),
+                    rule,
+                    topology,
+                )?),
+            #[cfg(target_os = "linux")]
+            EngineKind::SharedMemory => Box::new(shared::SharedMemory::new(params.threads, rule, topology)?),
+            #[cfg(not(target_os = "linux"))]
+            EngineKind::SharedMemory => bail!("The SharedMemory engine needs Linux"),
+        })
+    }
+}
+
+/// Coordinates of every alive cell of a row-major world.
+pub(crate) fn alive_cells(world: &[Vec<CellState>]) -> Vec<CellCoord> {
+    world.iter().enumerate()
+        .flat_map(|(y, row)|
+            row.iter().enumerate()
+                .filter(|(_, cell)| cell.is_alive())
+                .map(move |(x, _)| CellCoord::new(x, y)))
+        .collect()
+}
+
+/// Coordinates of every cell that differs between two row-major worlds of the same size.
+pub(crate) fn diff_cells(before: &[Vec<CellState>], after: &[Vec<CellState>]) -> Vec<CellCoord> {
+    before.iter().zip(after).enumerate()
+        .flat_map(|(y, (before, after))|
+            before.iter().zip(after).enumerate()
+                .filter(|(_, (before, after))| before != after)
+                .map(move |(x, _)| CellCoord::new(x, y)))
+        .collect()
+}
+
+/// Rows `[start, end)` of the `i`th of `threads` strips. Strip heights differ by at most one row.
+pub(crate) fn strip_bounds(i: usize, threads: usize, height: usize) -> (usize, usize) {
+    (i * height / threads, (i + 1) * height / threads)
+}
+
+/// Cut `costs`, the cost of each row, into `threads` strips of about the same cost, as with
+/// [`strip_bounds`]. Every strip keeps at least one row while there are enough to go round.
+pub(crate) fn balanced_strips(costs: &[f64], threads: usize) -> Vec<(usize, usize)> {
+    let height = costs.len();
+    let total = costs.iter().sum::<f64>();
+    if height < threads || total <= 0.0 {
+        return (0..threads).map(|i| strip_bounds(i, threads, height)).collect();
+    }
+    let mut ends = Vec::with_capacity(threads);
+    let (mut end, mut cost) = (0, 0.0);
+    for i in 1..threads {
+        let target = total * i as f64 / threads as f64;
+        while end < height && cost + costs[end] / 2.0 < target {
+            cost += costs[end];
+            end += 1;
+        }
+        ends.push(end);
+    }
+    // Leave a row for each strip and for every strip after it.
+    let mut start = 0;
+    for (i, end) in ends.iter_mut().enumerate() {
+        *end = (*end).clamp(start + 1, height - (threads - 1 - i));
+        start = *end;
+    }
+    ends.push(height);
+    std::iter::once(0).chain(ends.iter().copied()).zip(ends.iter().copied()).collect()
+}
+
+/// The padding around the rows `[start, end)` of the world, as the columns before them,
+/// the rows before them and the size of the padded strip. There are as many cells of padding
+/// as the rule can reach on each side, with the padding before the strip rounded up to an even
+/// number of rows and columns for hexagons and triangles, which take their shape from the parity
+/// of their row and column.
+pub(crate) fn padding(rule: &Rule, width: usize, start: usize, end: usize) -> (usize, usize, usize, usize) {
+    let reach = rule.reach();
+    let (left, top) = if rule.lattice() == Lattice::Square {
+        (reach, reach)
+    } else {
+        (reach + reach % 2, reach + (start + reach) % 2)
+    };
+    (left, top, left + width + reach, top + (end - start) + reach)
+}
+
+/// Each cell of the padding around the rows `[start, end)`, by index into the padded strip in
+/// row-major order, with the cell of the world it is seen through the topology as.
+pub(crate) fn padding_cells(
+    rule: &Rule,
+    topology: Topology,
+    width: usize,
+    height: usize,
+    start: usize,
+    end: usize,
+) -> Vec<(usize, Option<(usize, usize)>)> {
+    let (left, top, padded_width, padded_height) = padding(rule, width, start, end);
+    let inside = |px: usize, py: usize| (top..top + end - start).contains(&py) && (left..left + width).contains(&px);
+    (0..padded_height)
+        .flat_map(|py| (0..padded_width).map(move |px| (px, py)))
+        .filter(|&(px, py)| !inside(px, py))
+        .map(|(px, py)| {
+            let x = px as isize - left as isize;
+            let y = (start + py) as isize - top as isize;
+            (py * padded_width + px, topology.map(x, y, width, height))
+        })
+        .collect()
+}
@@ -0,0 +1,154 @@
+use crate::gol::engine::{alive_cells, diff_cells, lattice, ltl, Engine};
+use crate::gol::lattice::Lattice;
+use crate::gol::rule::Rule;
+use crate::gol::topology::Topology;
+use crate::util::cell::{CellCoord, CellState};
+use anyhow::Result;
+
+/// Naive steps the whole byte world on the calling thread.
+pub struct Naive {
+    rule: Rule,
+    topology: Topology,
+    world: Vec<Vec<CellState>>,
+    flipped: Vec<CellCoord>,
+}
+
+impl Naive {
+    pub fn new(rule: Rule, topology: Topology) -> Self {
+        Naive { rule, topology, world: Vec::new(), flipped: Vec::new() }
+    }
+}
+
+impl Engine for Naive {
+    fn load(&mut self, world: &[Vec<CellState>]) -> Result<()> {
+        self.world = world.to_vec();
+        self.flipped.clear();
+        Ok(())
+    }
+
+    fn step(&mut self, turns: u64) -> Result<()> {
+        let before = (turns > 1).then(|| self.world.clone());
+        let mut next = self.world.clone();
+        for _ in 0..turns {
+            self.flipped = calculate_next_strip(&self.rule, self.topology, &self.world, 0, &mut next);
+            std::mem::swap(&mut self.world, &mut next);
+        }
+        if let Some(before) = before {
+            self.flipped = diff_cells(&before, &self.world);
+        }
+        Ok(())
+    }
+
+    fn population(&self) -> u64 {
+        self.world.iter().flatten().filter(|cell| cell.is_alive()).count() as u64
+    }
+
+    fn alive_cells(&self) -> Vec<CellCoord> {
+        alive_cells(&self.world)
+    }
+
+    fn diff(&self) -> Vec<CellCoord> {
+        self.flipped.clone()
+    }
+
+    fn state(&self, cell: CellCoord) -> CellState {
+        self.world[cell.y][cell.x]
+    }
+
+    fn world(&self) -> Vec<Vec<CellState>> {
+        self.world.clone()
+    }
+
+    fn set(&mut self, cells: &[(CellCoord, CellState)]) -> Result<()> {
+        for &(cell, state) in cells {
+            self.world[cell.y][cell.x] = state;
+        }
+        self.flipped.clear();
+        Ok(())
+    }
+}
+
+/// Offsets of the 8 neighbours of a cell, in row-major order.
+const NEIGHBOURS: [(isize, isize); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+
+/// Compute the next state of the rows starting at `start_y` into `strip`,
+/// returning the coordinates of every cell that changed state.
+pub fn calculate_next_strip(
+    rule: &Rule,
+    topology: Topology,
+    world: &[Vec<CellState>],
+    start_y: usize,
+    strip: &mut [Vec<CellState>],
+) -> Vec<CellCoord> {
+    let mut flipped = Vec::new();
+    step_strip(rule, topology, world, start_y, strip, &mut Vec::new(), |cell| flipped.push(cell));
+    flipped
+}
+
+/// The length of the table [`step_strip`] sums a `width` by `rows` strip into under `rule`,
+/// which is only needed by Larger than Life rules.
+pub fn sums_len(rule: &Rule, width: usize, rows: usize) -> usize {
+    if rule.lattice() == Lattice::Square && rule.is_larger_than_life() {
+        ltl::sums_len(rule, width, rows)
+    } else {
+        0
+    }
+}
+
+/// Compute the next state of the rows starting at `start_y` into `strip`, calling `flip` with
+/// every cell that changed state. Allocates nothing once `sums` holds [`sums_len`] items,
+/// so it can step a strip where allocating is not allowed, such as a forked child.
+pub fn step_strip(
+    rule: &Rule,
+    topology: Topology,
+    world: &[Vec<CellState>],
+    start_y: usize,
+    strip: &mut [Vec<CellState>],
+    sums: &mut Vec<u32>,
+    mut flip: impl FnMut(CellCoord),
+) {
+    if rule.lattice() != Lattice::Square {
+        return lattice::step_strip(rule, topology, world, start_y, strip, flip);
+    }
+    if rule.is_larger_than_life() {
+        return ltl::step_strip(rule, topology, world, start_y, strip, sums, flip);
+    }
+    let height = world.len();
+    let width = world[0].len();
+    for (dy, row) in strip.iter_mut().enumerate() {
+        let y = start_y + dy;
+        let above = &world[(y + height - 1) % height];
+        let current = &world[y];
+        let below = &world[(y + 1) % height];
+        let border_row = y == 0 || y + 1 == height;
+        for (x, cell) in row.iter_mut().enumerate() {
+            let neighbours = if topology.is_torus() || !(border_row || x == 0 || x + 1 == width) {
+                let left = (x + width - 1) % width;
+                let right = (x + 1) % width;
+                [
+                    above[left], above[x], above[right],
+                    current[left], current[right],
+                    below[left], below[x], below[right],
+                ]
+            } else {
+                // Border cells find their neighbours past the edge through the topology.
+                NEIGHBOURS.map(|(dx, dy)|
+                    topology.map(x as isize + dx, y as isize + dy, width, height)
+                        .map_or(CellState::DEAD, |(x, y)| world[y][x]))
+            };
+            *cell = if rule.is_totalistic() {
+                let alive = neighbours.iter().filter(|cell| cell.is_alive()).count();
+                rule.next_state(current[x], alive as u16)
+            } else {
+                // Skip bit 4 of the neighbourhood index, which is the cell itself.
+                let neighbourhood = neighbours.iter().enumerate()
+                    .filter(|(_, cell)| cell.is_alive())
+                    .fold(0, |index, (i, _)| index | 1 << (i + (i >= 4) as usize));
+                rule.next_state_isotropic(current[x], neighbourhood)
+            };
+            if *cell != current[x] {
+                flip(CellCoord::new(x, y));
+            }
+        }
+    }
+}
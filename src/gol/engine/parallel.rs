@@ -0,0 +1,173 @@
+use crate::gol::engine::{alive_cells, balanced_strips, diff_cells, naive::calculate_next_strip, strip_bounds, Balance, Engine};
+use crate::gol::rule::Rule;
+use crate::gol::topology::Topology;
+use crate::util::cell::{CellCoord, CellState};
+use anyhow::Result;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::time::{Duration, Instant};
+
+/// What recording a changed cell costs next to stepping one, when balancing by [`Balance::Work`].
+const FLIP_WORK: f64 = 8.0;
+
+/// Parallel splits the byte world into horizontal strips and steps them on a worker pool.
+/// Unless balancing is off, each strip is timed or its work counted every turn, and the strips
+/// are recut every `interval` turns so that the rows costing the most are shared between more workers.
+pub struct Parallel {
+    pool: ThreadPool,
+    threads: usize,
+    rule: Rule,
+    topology: Topology,
+    world: Vec<Vec<CellState>>,
+    flipped: Vec<CellCoord>,
+    balance: Balance,
+    interval: u64,
+    /// The rows `[start, end)` of each strip.
+    strips: Vec<(usize, usize)>,
+    /// What each row has cost to step since the strips were last cut.
+    costs: Vec<f64>,
+    /// Turns stepped since the strips were last cut.
+    measured: u64,
+}
+
+impl Parallel {
+    pub fn new(threads: usize, rule: Rule, topology: Topology) -> Result<Self> {
+        Ok(Parallel {
+            pool: ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .thread_name(|i| format!("gol-worker-{}", i))
+                .build()?,
+            threads,
+            rule,
+            topology,
+            world: Vec::new(),
+            flipped: Vec::new(),
+            balance: Balance::Off,
+            interval: 1,
+            strips: Vec::new(),
+            costs: Vec::new(),
+            measured: 0,
+        })
+    }
+
+    /// Recut the strips by `balance` every `interval` turns.
+    pub fn balance(mut self, balance: Balance, interval: u64) -> Self {
+        self.balance = balance;
+        self.interval = interval.max(1);
+        self
+    }
+
+    /// The rows `[start, end)` of each strip, as they will be stepped next turn.
+    pub fn strips(&self) -> &[(usize, usize)] {
+        &self.strips
+    }
+
+    /// Advance the world by one turn, splitting it into horizontal strips across the worker pool.
+    /// Returns the coordinates of every cell that changed state.
+    fn step_once(&mut self) -> Vec<CellCoord> {
+        let height = self.world.len();
+        let mut next = vec![vec![CellState::DEAD; self.world[0].len()]; height];
+        let mut stepped = vec![(Vec::new(), Duration::ZERO); self.strips.len()];
+
+        let (world, rule, topology) = (&self.world, &self.rule, self.topology);
+        self.pool.scope(|scope| {
+            let mut rest = next.as_mut_slice();
+            for (&(start_y, end_y), stepped) in self.strips.iter().zip(stepped.iter_mut()) {
+                let (strip, tail) = rest.split_at_mut(end_y - start_y);
+                rest = tail;
+                scope.spawn(move |_| {
+                    let start = Instant::now();
+                    let flipped = calculate_next_strip(rule, topology, world, start_y, strip);
+                    *stepped = (flipped, start.elapsed());
+                });
+            }
+        });
+
+        self.world = next;
+        if self.balance != Balance::Off {
+            self.measure(&stepped);
+        }
+        stepped.into_iter().flat_map(|(flipped, _)| flipped).collect()
+    }
+
+    /// Add what each strip cost to step this turn to its rows, and recut the strips once
+    /// `interval` turns have been measured.
+    fn measure(&mut self, stepped: &[(Vec<CellCoord>, Duration)]) {
+        let width = self.world[0].len() as f64;
+        for (&(start, end), (flipped, elapsed)) in self.strips.iter().zip(stepped) {
+            match self.balance {
+                Balance::Time => for cost in &mut self.costs[start..end] {
+                    *cost += elapsed.as_secs_f64() / (end - start) as f64;
+                },
+                Balance::Work => {
+                    for cost in &mut self.costs[start..end] {
+                        *cost += width;
+                    }
+                    for cell in flipped {
+                        self.costs[cell.y] += FLIP_WORK;
+                    }
+                },
+                Balance::Off => (),
+            }
+        }
+        self.measured += 1;
+        if self.measured == self.interval {
+            self.strips = balanced_strips(&self.costs, self.threads);
+            self.costs.fill(0.0);
+            self.measured = 0;
+        }
+    }
+}
+
+impl Engine for Parallel {
+    fn load(&mut self, world: &[Vec<CellState>]) -> Result<()> {
+        if world.len() != self.costs.len() || self.strips.is_empty() {
+            self.strips = (0..self.threads).map(|i| strip_bounds(i, self.threads, world.len())).collect();
+            self.costs = vec![0.0; world.len()];
+            self.measured = 0;
+        }
+        self.world = world.to_vec();
+        self.flipped.clear();
+        Ok(())
+    }
+
+    fn step(&mut self, turns: u64) -> Result<()> {
+        if turns == 1 {
+            self.flipped = self.step_once();
+            return Ok(());
+        }
+        let before = self.world.clone();
+        for _ in 0..turns {
+            self.step_once();
+        }
+        self.flipped = diff_cells(&before, &self.world);
+        Ok(())
+    }
+
+    fn population(&self) -> u64 {
+        self.world.iter().flatten().filter(|cell| cell.is_alive()).count() as u64
+    }
+
+    fn alive_cells(&self) -> Vec<CellCoord> {
+        alive_cells(&self.world)
+    }
+
+    fn diff(&self) -> Vec<CellCoord> {
+        self.flipped.clone()
+    }
+
+    fn state(&self, cell: CellCoord) -> CellState {
+        self.world[cell.y][cell.x]
+    }
+
+    fn world(&self) -> Vec<Vec<CellState>> {
+        self.world.clone()
+    }
+
+    fn set(&mut self, cells: &[(CellCoord, CellState)]) -> Result<()> {
+        for &(cell, state) in cells {
+            self.world[cell.y][cell.x] = state;
+        }
+        self.flipped.clear();
+        Ok(())
+    }
+}
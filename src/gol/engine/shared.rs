@@ -0,0 +1,415 @@
+use crate::gol::engine::{alive_cells, naive, padding, padding_cells, strip_bounds, Engine};
+use crate::gol::rule::Rule;
+use crate::gol::topology::Topology;
+use crate::util::cell::{CellCoord, CellState};
+use anyhow::{bail, Result};
+use std::ffi::CString;
+use std::io;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// The bytes at the start of the shared memory set aside for the [`Header`].
+const HEADER: usize = 64;
+
+/// How long to wait on the child processes before checking whether any of them have exited.
+const POLL: Duration = Duration::from_millis(100);
+
+/// Numbers the shared memory objects of this process, so that each has a name of its own.
+static OBJECTS: AtomicUsize = AtomicUsize::new(0);
+
+/// SharedMemory steps horizontal strips of the world on child processes rather than threads.
+/// The world is double buffered in POSIX shared memory: each generation the child processes
+/// read one buffer and write their strips of the next into the other, which then becomes
+/// the world. The parent starts a generation by bumping a counter the children wait on with
+/// a futex, and each child counts itself done on a second one the parent waits on.
+///
+/// The children are forked from the parent with their strips and scratch rows already allocated,
+/// take nothing from the parent but the shared memory, and are killed when the world changes size
+/// or the engine is dropped. They are also killed if the thread that forked them exits, so the engine
+/// should be loaded on the thread that goes on to step it. Linux only.
+pub struct SharedMemory {
+    processes: usize,
+    rule: Rule,
+    topology: Topology,
+    width: usize,
+    height: usize,
+    memory: Option<Memory>,
+    children: Vec<libc::pid_t>,
+    /// Generations stepped since the world was loaded, whose parity is the buffer holding the world.
+    generation: u32,
+    flipped: Vec<CellCoord>,
+}
+
+impl SharedMemory {
+    pub fn new(processes: usize, rule: Rule, topology: Topology) -> Result<Self> {
+        if processes == 0 {
+            bail!("The shared memory engine needs at least one process");
+        }
+        Ok(SharedMemory {
+            processes,
+            rule,
+            topology,
+            width: 0,
+            height: 0,
+            memory: None,
+            children: Vec::new(),
+            generation: 0,
+            flipped: Vec::new(),
+        })
+    }
+
+    /// Process ids of the child processes stepping the world.
+    pub fn children(&self) -> &[libc::pid_t] {
+        &self.children
+    }
+
+    /// Map shared memory for a `width` by `height` world and fork a child process for each strip.
+    fn start(&mut self, width: usize, height: usize) -> Result<()> {
+        self.stop();
+        let memory = Memory::new(HEADER + 2 * width * height)?;
+        let strips = (0..self.processes)
+            .map(|i| strip_bounds(i, self.processes, height))
+            .filter(|(start, end)| start < end)
+            .map(|(start, end)| Strip::new(&self.rule, self.topology, width, height, start, end))
+            .collect::<Vec<_>>();
+        self.width = width;
+        self.height = height;
+        self.generation = 0;
+        let parent = unsafe { libc::getpid() };
+        for strip in strips {
+            match unsafe { libc::fork() } {
+                -1 => {
+                    self.stop();
+                    return Err(io::Error::last_os_error().into());
+                },
+                0 => {
+                    // Only the forking thread exists in the child, and another thread of the parent could
+                    // have held the allocator's or any other lock, so from here on the child only makes
+                    // system calls and steps its strip in the buffers allocated before forking.
+                    let _guard = Exit;
+                    unsafe {
+                        libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL);
+                        if libc::getppid() != parent {
+                            libc::_exit(0);
+                        }
+                    }
+                    strip.serve(&self.rule, &memory, width, height)
+                },
+                pid => self.children.push(pid),
+            }
+        }
+        self.memory = Some(memory);
+        Ok(())
+    }
+
+    /// Kill and reap the child processes, and unmap the shared memory.
+    /// The children hold nothing but the shared memory, so there is nothing to wait on them for.
+    fn stop(&mut self) {
+        for pid in self.children.drain(..) {
+            unsafe {
+                libc::kill(pid, libc::SIGKILL);
+                libc::waitpid(pid, std::ptr::null_mut(), 0);
+            }
+        }
+        self.memory = None;
+    }
+
+    /// The first child process found to have exited, if any.
+    fn exited(&self) -> Option<libc::pid_t> {
+        self.children.iter()
+            .copied()
+            .find(|&pid| unsafe { libc::waitpid(pid, std::ptr::null_mut(), libc::WNOHANG) } != 0)
+    }
+
+    /// Have every child process step its strip one generation and wait for them all to finish.
+    fn step_once(&mut self) -> Result<()> {
+        let Some(memory) = &self.memory else {
+            return Ok(());
+        };
+        let header = memory.header();
+        header.done.store(0, Ordering::Relaxed);
+        header.generation.store(self.generation.wrapping_add(1), Ordering::Release);
+        wake(&header.generation, i32::MAX);
+        loop {
+            let done = header.done.load(Ordering::Acquire);
+            if done as usize == self.children.len() {
+                break;
+            }
+            if !wait(&header.done, done, Some(POLL)) {
+                if let Some(pid) = self.exited() {
+                    bail!("The stepping process {} exited", pid);
+                }
+            }
+        }
+        self.generation = self.generation.wrapping_add(1);
+        Ok(())
+    }
+
+    /// The cells of the world after `generation`, in row-major order.
+    fn cells(&self, generation: u32) -> &[CellState] {
+        match &self.memory {
+            Some(memory) => unsafe {
+                std::slice::from_raw_parts(memory.buffer(generation, self.width * self.height), self.width * self.height)
+            },
+            None => &[],
+        }
+    }
+}
+
+impl Engine for SharedMemory {
+    fn load(&mut self, world: &[Vec<CellState>]) -> Result<()> {
+        let (width, height) = (world.first().map_or(0, Vec::len), world.len());
+        if self.memory.is_none() || (width, height) != (self.width, self.height) {
+            self.start(width, height)?;
+        }
+        if let Some(memory) = &self.memory {
+            // The children only touch the buffers between bumping the generation and counting themselves done.
+            let cells = unsafe { std::slice::from_raw_parts_mut(memory.buffer(self.generation, width * height), width * height) };
+            for (row, cells) in world.iter().zip(cells.chunks_mut(width.max(1))) {
+                cells.copy_from_slice(row);
+            }
+        }
+        self.flipped.clear();
+        Ok(())
+    }
+
+    fn step(&mut self, turns: u64) -> Result<()> {
+        let before = (turns != 1).then(|| self.cells(self.generation).to_vec());
+        for _ in 0..turns {
+            self.step_once()?;
+        }
+        let before = before.as_deref().unwrap_or_else(|| self.cells(self.generation.wrapping_sub(1)));
+        let width = self.width.max(1);
+        self.flipped = before.chunks(width).zip(self.cells(self.generation).chunks(width)).enumerate()
+            .filter(|(_, (before, after))| before != after)
+            .flat_map(|(y, (before, after))|
+                before.iter().zip(after).enumerate()
+                    .filter(|(_, (before, after))| before != after)
+                    .map(move |(x, _)| CellCoord::new(x, y)))
+            .collect();
+        Ok(())
+    }
+
+    fn population(&self) -> u64 {
+        self.cells(self.generation).iter().filter(|cell| cell.is_alive()).count() as u64
+    }
+
+    fn alive_cells(&self) -> Vec<CellCoord> {
+        alive_cells(&self.world())
+    }
+
+    fn diff(&self) -> Vec<CellCoord> {
+        self.flipped.clone()
+    }
+
+    fn state(&self, cell: CellCoord) -> CellState {
+        self.cells(self.generation)[cell.y * self.width + cell.x]
+    }
+
+    fn world(&self) -> Vec<Vec<CellState>> {
+        self.cells(self.generation).chunks(self.width.max(1)).map(<[CellState]>::to_vec).collect()
+    }
+
+    fn set(&mut self, cells: &[(CellCoord, CellState)]) -> Result<()> {
+        if let Some(memory) = &self.memory {
+            // As in `load`, the children are not touching the buffers between generations.
+            let world = memory.buffer(self.generation, self.width * self.height);
+            for &(cell, state) in cells {
+                unsafe { *world.add(cell.y * self.width + cell.x) = state };
+            }
+        }
+        self.flipped.clear();
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> Result<()> {
+        self.stop();
+        Ok(())
+    }
+}
+
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// The counters at the start of the shared memory that the processes coordinate through.
+#[repr(C)]
+struct Header {
+    /// Bumped by the parent to start a generation. The children wait on it.
+    generation: AtomicU32,
+    /// Counted up by the children as they finish a generation. The parent waits on it.
+    done: AtomicU32,
+}
+
+/// Memory is a shared mapping of the [`Header`] followed by the two buffers of the world.
+/// Its name is unlinked as soon as it is mapped, so it goes away with the last process mapping it.
+struct Memory {
+    address: *mut u8,
+    length: usize,
+}
+
+// The mapping is only read and written through the header's atomics, or between generations.
+unsafe impl Send for Memory {}
+
+impl Memory {
+    fn new(length: usize) -> Result<Self> {
+        let name = CString::new(format!(
+            "/gol-rs-{}-{}",
+            std::process::id(),
+            OBJECTS.fetch_add(1, Ordering::Relaxed)
+        ))?;
+        unsafe {
+            let fd = libc::shm_open(name.as_ptr(), libc::O_CREAT | libc::O_EXCL | libc::O_RDWR, 0o600);
+            if fd == -1 {
+                return Err(io::Error::last_os_error().into());
+            }
+            libc::shm_unlink(name.as_ptr());
+            if libc::ftruncate(fd, length as libc::off_t) == -1 {
+                let error = io::Error::last_os_error();
+                libc::close(fd);
+                return Err(error.into());
+            }
+            let address = libc::mmap(
+                std::ptr::null_mut(),
+                length,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd,
+                0,
+            );
+            libc::close(fd);
+            if address == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error().into());
+            }
+            Ok(Memory { address: address.cast(), length })
+        }
+    }
+
+    fn header(&self) -> &Header {
+        unsafe { &*self.address.cast::<Header>() }
+    }
+
+    /// The buffer of `cells` cells holding the world after `generation`.
+    fn buffer(&self, generation: u32, cells: usize) -> *mut CellState {
+        unsafe { self.address.add(HEADER + (generation % 2) as usize * cells).cast() }
+    }
+}
+
+impl Drop for Memory {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.address.cast(), self.length);
+        }
+    }
+}
+
+/// Exit ends a child process without returning to the code it was forked from,
+/// even if it is dropped by a panic unwinding out of the child.
+struct Exit;
+
+impl Drop for Exit {
+    fn drop(&mut self) {
+        unsafe { libc::_exit(1) }
+    }
+}
+
+/// Strip is the rows `[start, end)` a child process steps, with the padding around them,
+/// the rows it steps them into and the table Larger than Life rules sum them into,
+/// all allocated before forking.
+struct Strip {
+    start: usize,
+    end: usize,
+    left: usize,
+    top: usize,
+    /// Each cell of the padded strip outside the strip itself, by index into the padded strip,
+    /// with the cell of the world it is seen as.
+    fills: Vec<(usize, Option<(usize, usize)>)>,
+    padded: Vec<Vec<CellState>>,
+    next: Vec<Vec<CellState>>,
+    sums: Vec<u32>,
+}
+
+impl Strip {
+    fn new(rule: &Rule, topology: Topology, width: usize, height: usize, start: usize, end: usize) -> Self {
+        let (left, top, padded_width, padded_height) = padding(rule, width, start, end);
+        Strip {
+            start,
+            end,
+            left,
+            top,
+            fills: padding_cells(rule, topology, width, height, start, end),
+            padded: vec![vec![CellState::DEAD; padded_width]; padded_height],
+            next: vec![vec![CellState::DEAD; padded_width]; end - start],
+            sums: vec![0; naive::sums_len(rule, padded_width, end - start)],
+        }
+    }
+
+    /// Step the strip every generation the parent starts. Never returns,
+    /// as the parent kills the child rather than asking it to stop.
+    fn serve(mut self, rule: &Rule, memory: &Memory, width: usize, height: usize) -> ! {
+        let header = memory.header();
+        let mut generation = 0;
+        loop {
+            while header.generation.load(Ordering::Acquire) == generation {
+                wait(&header.generation, generation, None);
+            }
+            generation = header.generation.load(Ordering::Acquire);
+            let cells = width * height;
+            // Every child reads the whole of the last world, but writes only its own rows of the next.
+            let world = unsafe { std::slice::from_raw_parts(memory.buffer(generation.wrapping_sub(1), cells), cells) };
+            let next = unsafe {
+                std::slice::from_raw_parts_mut(
+                    memory.buffer(generation, cells).add(self.start * width),
+                    (self.end - self.start) * width,
+                )
+            };
+            self.step(rule, width, world, next);
+            header.done.fetch_add(1, Ordering::Release);
+            wake(&header.done, 1);
+        }
+    }
+
+    /// Step the strip of `world` one generation into `next`, without allocating.
+    fn step(&mut self, rule: &Rule, width: usize, world: &[CellState], next: &mut [CellState]) {
+        let padded_width = self.padded[0].len();
+        for (y, padded) in (self.start..self.end).zip(&mut self.padded[self.top..]) {
+            padded[self.left..self.left + width].copy_from_slice(&world[y * width..(y + 1) * width]);
+        }
+        for &(index, cell) in &self.fills {
+            self.padded[index / padded_width][index % padded_width] =
+                cell.map_or(CellState::DEAD, |(x, y)| world[y * width + x]);
+        }
+        naive::step_strip(rule, Topology::Plane, &self.padded, self.top, &mut self.next, &mut self.sums, |_| ());
+        for (row, next) in next.chunks_mut(width).zip(&self.next) {
+            row.copy_from_slice(&next[self.left..self.left + width]);
+        }
+    }
+}
+
+/// Sleep until `word` is woken, unless it no longer holds `value`, or until `timeout` passes.
+/// Returns false if the timeout passed. The futex is not private, as it is shared between processes.
+fn wait(word: &AtomicU32, value: u32, timeout: Option<Duration>) -> bool {
+    let timeout = timeout.map(|timeout| libc::timespec {
+        tv_sec: timeout.as_secs() as libc::time_t,
+        tv_nsec: timeout.subsec_nanos() as libc::c_long,
+    });
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_futex,
+            word.as_ptr(),
+            libc::FUTEX_WAIT,
+            value,
+            timeout.as_ref().map_or(std::ptr::null(), |timeout| timeout as *const libc::timespec),
+        )
+    };
+    result == 0 || io::Error::last_os_error().raw_os_error() != Some(libc::ETIMEDOUT)
+}
+
+/// Wake up to `count` processes waiting on `word`.
+fn wake(word: &AtomicU32, count: i32) {
+    unsafe {
+        libc::syscall(libc::SYS_futex, word.as_ptr(), libc::FUTEX_WAKE, count);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::util::cell::CellCoord;
+use crate::util::cell::{CellCoord, CellState};
 use std::fmt::Display;
 
 /// State represents a change in the state of execution.
@@ -17,6 +17,8 @@ impl Display for State {
 }
 
 /// `Event` represents any Game of Life event that needs to be communicated to the user.
+/// Cells are given in world coordinates, which only differ from image coordinates
+/// once an unbounded world has grown to the west or north and they go negative.
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub enum Event {
@@ -35,24 +37,34 @@ pub enum Event {
     /// `CellFlipped` is an Event notifying the GUI about a change of state of a single cell.
     /// This event should be sent every time a cell changes state.
     /// Make sure to send this event for all cells that are alive when the image is loaded in.
-    CellFlipped { completed_turns: u32, cell: CellCoord },
+    CellFlipped { completed_turns: u32, cell: CellCoord<i64> },
 
     /// `CellsFlipped` is an Event notifying the GUI about a change of state of many cells.
     /// You can collect many flipped cells and send `CellsFlipped` at a time instead of sending `CellFlipped` for every flipped cell.
     /// You can send many times of `CellsFlipped` event in a turn, i.e., each worker could send `CellsFlipped`.
     /// **Please be careful not to send `CellFlipped` and `CellsFlipped` at the same time, as they may conflict.**
     /// Choose one of them.
-    CellsFlipped { completed_turns: u32, cells: Vec<CellCoord> },
+    CellsFlipped { completed_turns: u32, cells: Vec<CellCoord<i64>> },
+
+    /// `CellsChanged` is an Event notifying the GUI about the new state of many cells under a Generations rule.
+    /// It is sent instead of `CellsFlipped` when the rule has refractory states,
+    /// since a cell may change between several states rather than just flip.
+    CellsChanged { completed_turns: u32, cells: Vec<(CellCoord<i64>, CellState)> },
 
     /// `TurnComplete` is an Event notifying the GUI about turn completion.
     /// SDL will render a frame when this event is sent.
     /// All `CellFlipped` or `CellsFlipped` events must be sent *before* `TurnComplete`.
     TurnComplete { completed_turns: u32 },
 
+    /// `Stabilised` is an Event notifying the user that the world has started repeating itself
+    /// every `period` turns, where a period of 1 is a still life or an empty world.
+    /// This Event is sent once, after the `TurnComplete` of the first turn found to repeat.
+    Stabilised { completed_turns: u32, period: u32 },
+
     /// `FinalTurnComplete` is an Event notifying the testing framework about the new world state after execution finished.
     /// The data included with this Event is used directly by the tests.
     /// SDL closes the window when this Event is sent.
-    FinalTurnComplete { completed_turns: u32, alive: Vec<CellCoord> },
+    FinalTurnComplete { completed_turns: u32, alive: Vec<CellCoord<i64>> },
 }
 
 impl Display for Event {
@@ -66,6 +78,8 @@ impl Display for Event {
                 write!(f, "Complete Turns {:<8} Final Turn Complete", completed_turns),
             Event::StateChange { completed_turns, new_state } =>
                 write!(f, "Complete Turns {:<8} {}", completed_turns, new_state),
+            Event::Stabilised { completed_turns, period } =>
+                write!(f, "Complete Turns {:<8} Stabilised with period {}", completed_turns, period),
             _ => Ok(()),
         }
     }
@@ -79,8 +93,10 @@ impl Event {
             | Event::StateChange { completed_turns, .. }
             | Event::CellFlipped { completed_turns, .. }
             | Event::TurnComplete { completed_turns, .. }
+            | Event::Stabilised { completed_turns, .. }
             | Event::FinalTurnComplete { completed_turns, .. }
-            | Event::CellsFlipped { completed_turns, .. } => *completed_turns,
+            | Event::CellsFlipped { completed_turns, .. }
+            | Event::CellsChanged { completed_turns, .. } => *completed_turns,
         }
     }
 }
@@ -0,0 +1,65 @@
+use crate::util::cell::CellCoord;
+use std::collections::VecDeque;
+
+/// Delta is the change one step made to the world, as the XOR of each changed cell's state index
+/// before and after it. XORing the same values into the world again undoes the step.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Delta {
+    /// How many turns the step took. The distributor steps one turn at a time while it keeps a history.
+    pub turns: u64,
+    /// The changed cells in world coordinates, so they stay put while an unbounded world grows.
+    pub cells: Vec<(CellCoord<i64>, u8)>,
+}
+
+/// History is a ring buffer of the last `capacity` steps, oldest first.
+/// ## Examples
+/// ``` ignore
+/// let mut history = History::new(2);
+/// history.record(Delta { turns: 1, cells: vec![] });
+/// history.record(Delta { turns: 1, cells: vec![] });
+/// history.record(Delta { turns: 4, cells: vec![] }); // forgets the first step
+/// assert_eq!(history.len(), 2);
+/// assert_eq!(history.pop().map(|delta| delta.turns), Some(4));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    capacity: usize,
+    deltas: VecDeque<Delta>,
+}
+
+impl History {
+    /// Create an empty history of at most `capacity` steps. A capacity of 0 records nothing.
+    pub fn new(capacity: usize) -> Self {
+        History { capacity, deltas: VecDeque::with_capacity(capacity) }
+    }
+
+    /// Whether any steps are recorded at all.
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Record a step, forgetting the oldest one once the history is full.
+    pub fn record(&mut self, delta: Delta) {
+        if !self.is_enabled() {
+            return;
+        }
+        if self.deltas.len() == self.capacity {
+            self.deltas.pop_front();
+        }
+        self.deltas.push_back(delta);
+    }
+
+    /// Take the most recent step, if there is one left to undo.
+    pub fn pop(&mut self) -> Option<Delta> {
+        self.deltas.pop_back()
+    }
+
+    /// The number of steps that can be undone.
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+}
@@ -0,0 +1,143 @@
+use crate::gol::io::pattern::Pattern;
+use crate::gol::rule::Rule;
+use crate::util::cell::{CellCoord, CellState};
+use anyhow::{bail, Context, Result};
+
+/// The widest block of rows written in Life 1.05, keeping lines within 80 characters.
+const BLOCK_WIDTH: usize = 80;
+
+/// Parse a pattern in Life 1.05, for example
+/// ``` text
+/// #Life 1.05
+/// #D The smallest spaceship.
+/// #N
+/// #P -1 -1
+/// .*.
+/// ..*
+/// ***
+/// ```
+/// `#D` lines are comments, `#N` is Conway's Life and `#R` gives another rule in S/B notation.
+/// Each `#P x y` starts a block of rows with its top left cell at `x y`, with `.` for dead and `*`
+/// for alive cells. The pattern is the bounding box of its alive cells, with its origin at their top left.
+pub fn parse_105(text: &str) -> Result<Pattern> {
+    let mut alive = Vec::new();
+    let (mut name, mut comments, mut rule) = (None, Vec::new(), None);
+    let mut block = CellCoord::new(0_i64, 0_i64);
+    let mut y = 0;
+    for (number, line) in text.lines().enumerate() {
+        let context = || format!("The Life 1.05 pattern is invalid on line {}", number + 1);
+        let line = line.trim();
+        match line.get(..2) {
+            _ if line.is_empty() || line.starts_with("#Life") => (),
+            Some("#D" | "#C") => match line[2..].trim().strip_prefix("Name:") {
+                Some(text) => name = Some(text.trim().to_string()),
+                None => comments.push(line[2..].trim().to_string()),
+            },
+            Some("#N") => rule = Some(Rule::CONWAY),
+            Some("#R") => rule = Some(line[2..].parse::<Rule>().with_context(context)?),
+            Some("#P") => {
+                block = read_coordinate(&line[2..]).with_context(context)?;
+                y = 0;
+            },
+            Some(_) if line.starts_with('#') => (),
+            _ => {
+                for (x, byte) in line.bytes().enumerate() {
+                    match byte {
+                        b'.' => (),
+                        b'*' | b'O' => alive.push(CellCoord::new(block.x + x as i64, block.y + y)),
+                        byte => bail!("{}: unexpected {:?}", context(), byte as char),
+                    }
+                }
+                y += 1;
+            },
+        }
+    }
+    Ok(Pattern { name, comments, rule, ..Pattern::from_alive_cells(&alive)? })
+}
+
+/// Write a pattern in Life 1.05, as described in [`parse_105`], in blocks of at most 80 columns
+/// from the first to the last row with alive cells in them.
+pub fn write_105(pattern: &Pattern) -> String {
+    let mut text = String::from("#Life 1.05\n");
+    write_descriptions(&mut text, pattern);
+    match pattern.rule {
+        Some(rule) if rule != Rule::CONWAY => text.push_str(&format!("#R {}\n", rule)),
+        _ => text.push_str("#N\n"),
+    }
+    let origin = pattern.origin.unwrap_or(CellCoord::new(0, 0));
+    let rows = pattern.cells.chunks(pattern.width.max(1)).collect::<Vec<_>>();
+    for left in (0..pattern.width).step_by(BLOCK_WIDTH) {
+        let right = (left + BLOCK_WIDTH).min(pattern.width);
+        let occupied = |row: &&[CellState]| row[left..right].iter().any(CellState::is_alive);
+        let (Some(top), Some(bottom)) = (rows.iter().position(occupied), rows.iter().rposition(occupied)) else {
+            continue;
+        };
+        text.push_str(&format!("#P {} {}\n", origin.x + left as i64, origin.y + top as i64));
+        for row in &rows[top..=bottom] {
+            let cells = &row[left..right];
+            let length = cells.iter().rposition(|cell| cell.is_alive()).map_or(1, |last| last + 1);
+            text.extend(cells[..length].iter().map(|cell| if cell.is_alive() { '*' } else { '.' }));
+            text.push('\n');
+        }
+    }
+    text
+}
+
+/// Parse a pattern in Life 1.06, for example
+/// ``` text
+/// #Life 1.06
+/// 0 -1
+/// 1 0
+/// -1 1
+/// 0 1
+/// 1 1
+/// ```
+/// Each line after the `#Life 1.06` one is the `x y` coordinate of an alive cell. `#D` lines,
+/// which many programs write although the format has no comments, are read as comments.
+/// The pattern is the bounding box of its alive cells, with its origin at their top left.
+pub fn parse_106(text: &str) -> Result<Pattern> {
+    let mut alive = Vec::new();
+    let (mut name, mut comments) = (None, Vec::new());
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if let Some(description) = line.strip_prefix("#D") {
+            match description.trim().strip_prefix("Name:") {
+                Some(text) => name = Some(text.trim().to_string()),
+                None => comments.push(description.trim().to_string()),
+            }
+        } else if !line.is_empty() && !line.starts_with('#') {
+            alive.push(read_coordinate(line)
+                .with_context(|| format!("The Life 1.06 pattern is invalid on line {}", number + 1))?);
+        }
+    }
+    Ok(Pattern { name, comments, ..Pattern::from_alive_cells(&alive)? })
+}
+
+/// Write a pattern in Life 1.06, as described in [`parse_106`].
+pub fn write_106(pattern: &Pattern) -> String {
+    let mut text = String::from("#Life 1.06\n");
+    write_descriptions(&mut text, pattern);
+    for cell in pattern.alive_world_cells() {
+        text.push_str(&format!("{} {}\n", cell.x, cell.y));
+    }
+    text
+}
+
+/// Read a coordinate such as `-1 4`.
+fn read_coordinate(text: &str) -> Result<CellCoord<i64>> {
+    let mut numbers = text.split_whitespace().map(str::parse::<i64>);
+    match (numbers.next(), numbers.next(), numbers.next()) {
+        (Some(x), Some(y), None) => Ok(CellCoord::new(x?, y?)),
+        _ => bail!("{} is not an x y coordinate", text.trim()),
+    }
+}
+
+/// Write the name and comments as `#D` lines.
+fn write_descriptions(text: &mut String, pattern: &Pattern) {
+    if let Some(name) = &pattern.name {
+        text.push_str(&format!("#D Name: {}\n", name));
+    }
+    for comment in &pattern.comments {
+        text.push_str(&format!("#D {}\n", comment));
+    }
+}
@@ -0,0 +1,462 @@
+use crate::gol::io::pattern::{dead_cells, Pattern};
+use crate::gol::rule::Rule;
+use crate::util::cell::{CellCoord, CellState};
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+
+type NodeId = u32;
+
+/// The first line of every macrocell file, followed by the program that wrote it.
+const MAGIC: &str = "[M2]";
+
+/// The level of the 8x8 leaves of two state macrocell files.
+const LEAF_LEVEL: u8 = 3;
+
+/// The highest level a quadtree can have, keeping every world coordinate within an `i64`.
+const MAX_LEVEL: u8 = 62;
+
+/// Node is a canonical square of side `2^level`. Children are ordered north-west, north-east,
+/// south-west, south-east, and a level 0 node is a single cell whose state is its first child.
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    level: u8,
+    children: [NodeId; 4],
+    population: u64,
+    /// The left, top, right and bottom of the cells that are not dead, inclusive and counted from
+    /// the top left of the node, or none if every cell is dead.
+    bounds: Option<[u64; 4]>,
+}
+
+/// `Quadtree` is a pattern as a canonical quadtree, in which equal squares are the same node,
+/// so a huge but regular pattern takes little memory however many cells it covers.
+///
+/// As in Golly, the root is centred on the world origin, covering the cells from `-2^(level - 1)`
+/// to `2^(level - 1) - 1` in each direction.
+#[derive(Debug, Clone)]
+pub struct Quadtree {
+    nodes: Vec<Node>,
+    index: HashMap<(u8, [NodeId; 4]), NodeId>,
+    empty: Vec<NodeId>,
+    root: NodeId,
+    /// The rule the pattern is for, if the file names one.
+    pub rule: Option<Rule>,
+    pub name: Option<String>,
+    pub comments: Vec<String>,
+}
+
+impl Default for Quadtree {
+    fn default() -> Self {
+        let mut tree = Quadtree {
+            nodes: Vec::new(),
+            index: HashMap::new(),
+            empty: Vec::new(),
+            root: 0,
+            rule: None,
+            name: None,
+            comments: Vec::new(),
+        };
+        tree.root = tree.empty(LEAF_LEVEL);
+        tree
+    }
+}
+
+impl Quadtree {
+    /// Build the quadtree of a pattern, putting its top left cell at its origin, or at the world
+    /// origin if it has none.
+    pub fn from_pattern(pattern: &Pattern) -> Result<Self> {
+        let mut tree = Quadtree {
+            rule: pattern.rule,
+            name: pattern.name.clone(),
+            comments: pattern.comments.clone(),
+            ..Quadtree::default()
+        };
+        let origin = pattern.origin.unwrap_or(CellCoord::new(0, 0));
+        let far = CellCoord::new(
+            origin.x.checked_add_unsigned(pattern.width as u64).context("The pattern is too large")?,
+            origin.y.checked_add_unsigned(pattern.height as u64).context("The pattern is too large")?,
+        );
+        // The smallest root whose cells from -half to half - 1 hold the pattern.
+        let reach = [origin.x, origin.y].map(|start| start.unsigned_abs())
+            .into_iter()
+            .chain([far.x, far.y].map(|end| end.unsigned_abs()))
+            .max()
+            .unwrap_or(0);
+        let level = (LEAF_LEVEL..=MAX_LEVEL)
+            .find(|&level| 1_u64 << (level - 1) >= reach)
+            .context("The pattern is too large")?;
+        let half = 1_i64 << (level - 1);
+        let state = |x: i64, y: i64| {
+            let (x, y) = (x - origin.x, y - origin.y);
+            pattern.cells[y as usize * pattern.width + x as usize]
+        };
+        let inside = |x: i64, y: i64, size: i64| x < far.x && y < far.y && x + size > origin.x && y + size > origin.y;
+        tree.root = tree.build(level, -half, -half, &state, &inside);
+        Ok(tree)
+    }
+
+    /// The level of the root, which covers `2^level` by `2^level` cells.
+    pub fn level(&self) -> u8 {
+        self.nodes[self.root as usize].level
+    }
+
+    /// The number of alive cells.
+    pub fn population(&self) -> u64 {
+        self.nodes[self.root as usize].population
+    }
+
+    /// The number of distinct nodes stored, far fewer than the cells they cover for regular patterns.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The world coordinate of the top left cell that is not dead, with the width and height of the
+    /// box holding every such cell, or none if every cell is dead.
+    pub fn bounds(&self) -> Option<(CellCoord<i64>, u64, u64)> {
+        let [left, top, right, bottom] = self.nodes[self.root as usize].bounds?;
+        let corner = self.corner();
+        Some((
+            CellCoord::new(corner.checked_add_unsigned(left)?, corner.checked_add_unsigned(top)?),
+            right - left + 1,
+            bottom - top + 1,
+        ))
+    }
+
+    /// The state of the cell at a world coordinate.
+    pub fn state(&self, cell: CellCoord<i64>) -> CellState {
+        let corner = self.corner();
+        let (Some(x), Some(y)) = (cell.x.checked_sub(corner), cell.y.checked_sub(corner)) else {
+            return CellState::DEAD;
+        };
+        let (x, y) = (x as u64, y as u64);
+        if x >> self.level() != 0 || y >> self.level() != 0 {
+            return CellState::DEAD;
+        }
+        let mut id = self.root;
+        for level in (0..self.level()).rev() {
+            let quadrant = ((y >> level & 1) << 1 | x >> level & 1) as usize;
+            id = self.nodes[id as usize].children[quadrant];
+        }
+        CellState::new(self.nodes[id as usize].children[0] as u8)
+    }
+
+    /// Expand the box holding every cell that is not dead into a flat pattern, with its origin at
+    /// the top left of the box, as long as the box fits in `width` by `height` cells.
+    pub fn to_pattern(&self, width: usize, height: usize) -> Result<Pattern> {
+        let described = Pattern {
+            rule: self.rule,
+            name: self.name.clone(),
+            comments: self.comments.clone(),
+            ..Pattern::default()
+        };
+        let Some((origin, box_width, box_height)) = self.bounds() else {
+            return Ok(described);
+        };
+        if box_width > width as u64 || box_height > height as u64 {
+            bail!("The {}x{} macrocell pattern does not fit in {}x{}", box_width, box_height, width, height);
+        }
+        let (box_width, box_height) = (box_width as usize, box_height as usize);
+        let mut cells = dead_cells(box_width, box_height).context("The macrocell pattern is too large")?;
+        let corner = self.corner();
+        let at = CellCoord::new(origin.x.abs_diff(corner), origin.y.abs_diff(corner));
+        self.expand(self.root, 0, 0, at, box_width, &mut cells);
+        Ok(Pattern { width: box_width, height: box_height, cells, origin: Some(origin), ..described })
+    }
+
+    /// The world coordinate of the top left cell of the root.
+    fn corner(&self) -> i64 {
+        match self.level() {
+            0 => 0,
+            level => -(1_i64 << (level - 1)),
+        }
+    }
+
+    /// Write the cells of a node at `x, y` from the top left of the root into `cells`,
+    /// a row-major box whose top left is at `at` from the top left of the root.
+    fn expand(&self, id: NodeId, x: u64, y: u64, at: CellCoord<u64>, width: usize, cells: &mut [CellState]) {
+        let node = self.nodes[id as usize];
+        if node.bounds.is_none() {
+            return;
+        }
+        if node.level == 0 {
+            cells[(y - at.y) as usize * width + (x - at.x) as usize] = CellState::new(node.children[0] as u8);
+            return;
+        }
+        let half = 1_u64 << (node.level - 1);
+        for (i, (dx, dy)) in [(0, 0), (half, 0), (0, half), (half, half)].into_iter().enumerate() {
+            self.expand(node.children[i], x + dx, y + dy, at, width, cells);
+        }
+    }
+
+    /// Build the node at `level` with its top left cell at `x, y`, leaving squares that are not
+    /// `inside` the pattern empty.
+    fn build(
+        &mut self,
+        level: u8,
+        x: i64,
+        y: i64,
+        state: &impl Fn(i64, i64) -> CellState,
+        inside: &impl Fn(i64, i64, i64) -> bool,
+    ) -> NodeId {
+        if !inside(x, y, 1 << level) {
+            return self.empty(level);
+        }
+        if level == 0 {
+            return self.leaf(state(x, y));
+        }
+        let half = 1 << (level - 1);
+        let children = [(x, y), (x + half, y), (x, y + half), (x + half, y + half)]
+            .map(|(x, y)| self.build(level - 1, x, y, state, inside));
+        self.join(children)
+    }
+
+    /// The canonical single cell in `state`.
+    fn leaf(&mut self, state: CellState) -> NodeId {
+        let children = [state.get() as NodeId, 0, 0, 0];
+        if let Some(&id) = self.index.get(&(0, children)) {
+            return id;
+        }
+        let id = self.nodes.len() as NodeId;
+        let bounds = (!state.is_dead()).then_some([0; 4]);
+        self.nodes.push(Node { level: 0, children, population: state.is_alive() as u64, bounds });
+        self.index.insert((0, children), id);
+        id
+    }
+
+    /// The canonical node with the given children, which must all have the same level.
+    fn join(&mut self, children: [NodeId; 4]) -> NodeId {
+        let level = self.nodes[children[0] as usize].level + 1;
+        if let Some(&id) = self.index.get(&(level, children)) {
+            return id;
+        }
+        let half = 1_u64 << (level - 1);
+        let bounds = children.iter().zip([(0, 0), (half, 0), (0, half), (half, half)])
+            .filter_map(|(&child, (dx, dy))| self.nodes[child as usize].bounds
+                .map(|[left, top, right, bottom]| [left + dx, top + dy, right + dx, bottom + dy]))
+            .reduce(|a, b| [a[0].min(b[0]), a[1].min(b[1]), a[2].max(b[2]), a[3].max(b[3])]);
+        let population = children.iter().map(|&child| self.nodes[child as usize].population).sum();
+        let id = self.nodes.len() as NodeId;
+        self.nodes.push(Node { level, children, population, bounds });
+        self.index.insert((level, children), id);
+        id
+    }
+
+    /// The canonical empty node at `level`.
+    fn empty(&mut self, level: u8) -> NodeId {
+        if self.empty.is_empty() {
+            let dead = self.leaf(CellState::DEAD);
+            self.empty.push(dead);
+        }
+        while self.empty.len() <= level as usize {
+            let below = *self.empty.last().unwrap();
+            let id = self.join([below; 4]);
+            self.empty.push(id);
+        }
+        self.empty[level as usize]
+    }
+
+    /// Put the root in the middle of a node one level up, keeping every cell where it is.
+    fn grow(&mut self) {
+        let level = self.level();
+        let empty = self.empty(level - 1);
+        let [nw, ne, sw, se] = self.nodes[self.root as usize].children;
+        let quadrants = [
+            [empty, empty, empty, nw],
+            [empty, empty, ne, empty],
+            [empty, sw, empty, empty],
+            [se, empty, empty, empty],
+        ];
+        let children = quadrants.map(|children| self.join(children));
+        self.root = self.join(children);
+    }
+
+    /// Whether any cell is in a state besides dead and alive.
+    fn has_states(&self) -> bool {
+        self.nodes.iter().any(|node| node.level == 0 && node.children[0] > 1)
+    }
+}
+
+/// Parse a pattern in Golly's macrocell format, for example
+/// ``` text
+/// [M2] (golly 4.2)
+/// #R B3/S23
+/// $$$$$$$.*$
+/// ..*$***$
+/// 4 1 0 2 0
+/// ```
+/// After the `[M2]` line, `#R` gives the rule and `#C` lines are comments. Every other line is a
+/// node, numbered from 1 in order, and the last is the root. A node of two state patterns is either
+/// an 8x8 leaf written as rows of `.` for dead and `*` for alive cells, each ended by `$`, or
+/// `level nw ne sw se` with the numbers of its four children one level down, 0 being empty. Patterns
+/// with more states have 2x2 leaves written as `1 nw ne sw se` with the state of each cell instead.
+pub fn parse(text: &str) -> Result<Quadtree> {
+    let mut lines = text.lines().enumerate();
+    match lines.next() {
+        Some((_, line)) if line.starts_with(MAGIC) => (),
+        _ => bail!("This is not a macrocell pattern, it does not start with {}", MAGIC),
+    }
+    let mut tree = Quadtree::default();
+    // The node each line number refers to, with 0 standing for the empty node of any level.
+    let mut numbered = vec![None];
+    for (number, line) in lines {
+        let context = || format!("The macrocell pattern is invalid on line {}", number + 1);
+        let line = line.trim();
+        if let Some(comment) = line.strip_prefix('#') {
+            let mut chars = comment.chars();
+            let (kind, text) = (chars.next(), chars.as_str());
+            match kind {
+                Some('R') => tree.rule = Some(text.trim().parse::<Rule>().with_context(context)?),
+                Some('C') => match text.trim().strip_prefix("Name:") {
+                    Some(name) => tree.name = Some(name.trim().to_string()),
+                    None => tree.comments.push(text.trim().to_string()),
+                },
+                // The generation and anything else are left out.
+                _ => (),
+            }
+            continue;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        let id = match line.as_bytes()[0] {
+            b'.' | b'*' | b'$' => read_leaf(&mut tree, line).with_context(context)?,
+            _ => read_node(&mut tree, &numbered, line).with_context(context)?,
+        };
+        numbered.push(Some(id));
+    }
+    if let Some(&Some(root)) = numbered.last() {
+        tree.root = root;
+    }
+    while tree.level() < LEAF_LEVEL {
+        tree.grow();
+    }
+    Ok(tree)
+}
+
+/// Read an 8x8 leaf such as `.*$..*$***$`.
+fn read_leaf(tree: &mut Quadtree, line: &str) -> Result<NodeId> {
+    let mut cells = [[CellState::DEAD; 8]; 8];
+    let (mut x, mut y) = (0, 0);
+    for byte in line.bytes() {
+        match byte {
+            b'.' | b'*' if x >= 8 || y >= 8 => bail!("The leaf {} is wider or taller than 8 cells", line),
+            b'.' => x += 1,
+            b'*' => {
+                cells[y][x] = CellState::ALIVE;
+                x += 1;
+            },
+            b'$' => (x, y) = (0, y + 1),
+            byte => bail!("The leaf {} has an unexpected {:?}", line, byte as char),
+        }
+    }
+    let state = |x: i64, y: i64| cells[y as usize][x as usize];
+    Ok(tree.build(LEAF_LEVEL, 0, 0, &state, &|_, _, _| true))
+}
+
+/// Read a node such as `4 1 0 2 0`, or a 2x2 leaf such as `1 0 2 1 1`.
+fn read_node(tree: &mut Quadtree, numbered: &[Option<NodeId>], line: &str) -> Result<NodeId> {
+    let numbers = line.split_whitespace().map(str::parse::<u64>).collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("{} is not a leaf or a list of numbers", line))?;
+    let [level, nw, ne, sw, se] = numbers[..] else {
+        bail!("{} should be a level and four children", line);
+    };
+    let children = [nw, ne, sw, se];
+    match level {
+        1 => {
+            let states = children.map(|state| u8::try_from(state).ok().map(CellState::new));
+            let leaves = states.iter().map(|&state| state.map(|state| tree.leaf(state)))
+                .collect::<Option<Vec<_>>>()
+                .with_context(|| format!("{} has a state above 255", line))?;
+            Ok(tree.join([leaves[0], leaves[1], leaves[2], leaves[3]]))
+        },
+        2..=62 => {
+            let level = level as u8;
+            let mut ids = [0; 4];
+            for (id, child) in ids.iter_mut().zip(children) {
+                *id = match numbered.get(child as usize) {
+                    Some(None) => tree.empty(level - 1),
+                    Some(&Some(id)) if tree.nodes[id as usize].level == level - 1 => id,
+                    Some(Some(_)) => bail!("The child {} of {} is not at level {}", child, line, level - 1),
+                    None => bail!("The child {} of {} comes after it", child, line),
+                };
+            }
+            Ok(tree.join(ids))
+        },
+        level => bail!("There are no nodes at level {}", level),
+    }
+}
+
+/// Write a pattern in Golly's macrocell format, as described in [`parse`], with two state leaves
+/// unless it has cells in other states.
+pub fn write(tree: &Quadtree) -> String {
+    let mut text = format!("{} (gol-rs)\n", MAGIC);
+    if let Some(rule) = tree.rule {
+        text.push_str(&format!("#R {}\n", rule));
+    }
+    if let Some(name) = &tree.name {
+        text.push_str(&format!("#C Name: {}\n", name));
+    }
+    for comment in &tree.comments {
+        text.push_str(&format!("#C {}\n", comment));
+    }
+    let leaf_level = if tree.has_states() || tree.rule.is_some_and(|rule| rule.is_generations()) { 1 } else { LEAF_LEVEL };
+    let mut numbers = HashMap::new();
+    write_node(tree, tree.root, leaf_level, &mut numbers, &mut text);
+    text
+}
+
+/// Write a node after its children, unless it is empty or already written, returning its number.
+fn write_node(
+    tree: &Quadtree,
+    id: NodeId,
+    leaf_level: u8,
+    numbers: &mut HashMap<NodeId, usize>,
+    text: &mut String,
+) -> usize {
+    let node = tree.nodes[id as usize];
+    if node.bounds.is_none() {
+        return 0;
+    }
+    if let Some(&number) = numbers.get(&id) {
+        return number;
+    }
+    match node.level {
+        1 if leaf_level == 1 => {
+            let [nw, ne, sw, se] = node.children.map(|child| tree.nodes[child as usize].children[0]);
+            text.push_str(&format!("1 {} {} {} {}\n", nw, ne, sw, se));
+        },
+        LEAF_LEVEL if leaf_level == LEAF_LEVEL => {
+            let mut cells = [[false; 8]; 8];
+            mark(tree, id, 0, 0, &mut cells);
+            let last = cells.iter().rposition(|row| row.contains(&true)).unwrap_or(0);
+            for row in &cells[..=last] {
+                let length = row.iter().rposition(|&alive| alive).map_or(0, |x| x + 1);
+                text.extend(row[..length].iter().map(|&alive| if alive { '*' } else { '.' }));
+                text.push('$');
+            }
+            text.push('\n');
+        },
+        level => {
+            let children = node.children.map(|child| write_node(tree, child, leaf_level, numbers, text));
+            text.push_str(&format!("{} {} {} {} {}\n", level, children[0], children[1], children[2], children[3]));
+        },
+    }
+    let number = numbers.len() + 1;
+    numbers.insert(id, number);
+    number
+}
+
+/// Mark the alive cells of a node within an 8x8 leaf.
+fn mark(tree: &Quadtree, id: NodeId, x: usize, y: usize, cells: &mut [[bool; 8]; 8]) {
+    let node = tree.nodes[id as usize];
+    if node.population == 0 {
+        return;
+    }
+    if node.level == 0 {
+        cells[y][x] = true;
+        return;
+    }
+    let half = 1 << (node.level - 1);
+    for (i, (dx, dy)) in [(0, 0), (half, 0), (0, half), (half, half)].into_iter().enumerate() {
+        mark(tree, node.children[i], x + dx, y + dy, cells);
+    }
+}
@@ -0,0 +1,309 @@
+use crate::gol::io::pattern::{Format, Pattern, SNIFF_LENGTH};
+use crate::gol::snapshot::{self, SnapshotHeader};
+use crate::gol::Params;
+use crate::util::cell::{CellCoord, CellState};
+use anyhow::{bail, Context, Result};
+use flume::{Receiver, Sender};
+use image::ImageReader;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::{fs::{create_dir_all, read, rename, write, File}, io::AsyncReadExt};
+
+pub mod life;
+pub mod macrocell;
+pub mod pattern;
+pub mod plaintext;
+pub mod rle;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum IoCommand {
+    IoCheckIdle,
+    IoInput,
+    /// Write the world to the output directory, as a pattern if the output name ends with
+    /// the extension of a pattern format and as a PGM image otherwise.
+    IoOutput,
+    /// Write the `width` by `height` bounding box of an unbounded world,
+    /// recording the world coordinate of its top left cell in a header comment.
+    IoOutputRegion { origin: CellCoord<i64>, width: usize, height: usize },
+    /// Write the `header.width` by `header.height` world to a snapshot in the output directory.
+    IoSnapshot(Box<SnapshotHeader>),
+    /// Read the snapshot at the path sent on the filename channel, sending its header
+    /// or why it could not be read on the snapshot channel, then its cells on the input channel.
+    IoResume,
+}
+
+/// `SizeError` describes why the input image does not settle the size of the world.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SizeError {
+    /// The header of the input image could not be read.
+    Unreadable { path: PathBuf, reason: String },
+    /// The input image is not the width and height given.
+    Mismatch { path: PathBuf, given: (usize, usize), found: (usize, usize) },
+}
+
+impl Display for SizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SizeError::Unreadable { path, reason } =>
+                write!(f, "Cannot read the size of the input image {}: {}", path.display(), reason),
+            SizeError::Mismatch { path, given, found } => write!(
+                f, "The input image {} is {}x{}, not the {}x{} given",
+                path.display(), found.0, found.1, given.0, given.1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SizeError {}
+
+/// Read the width and height from the header of an image, without decoding the rest of it,
+/// or the size of the bounding box of a pattern.
+pub fn read_size(path: &Path) -> Result<(usize, usize), SizeError> {
+    let unreadable = |reason: String| SizeError::Unreadable { path: path.to_owned(), reason };
+    if let Some(format) = Format::of_file(path).map_err(|error| unreadable(error.to_string()))? {
+        return std::fs::read_to_string(path)
+            .map_err(|error| unreadable(error.to_string()))
+            .and_then(|text| format.size(&text).map_err(|error| unreadable(format!("{:#}", error))));
+    }
+    let (width, height) = ImageReader::open(path)
+        .map_err(|error| unreadable(error.to_string()))?
+        .with_guessed_format()
+        .map_err(|error| unreadable(error.to_string()))?
+        .into_dimensions()
+        .map_err(|error| unreadable(error.to_string()))?;
+    Ok((width as usize, height as usize))
+}
+
+pub struct IoChannels {
+    pub command: Option<Receiver<IoCommand>>,
+    pub idle: Option<Sender<bool>>,
+    pub filename: Option<Receiver<String>>,
+    pub input: Option<Sender<CellState>>,
+    pub output: Option<Receiver<CellState>>,
+    pub snapshot: Option<Sender<Result<SnapshotHeader>>>,
+}
+
+struct IoState {
+    params: Params,
+    channels: IoChannels,
+}
+
+/// Carry out the distributor's commands until it hangs up. The first command that fails ends the Io,
+/// which closes the channels the distributor is waiting on, and its error is returned.
+pub async fn start_io(params: Params, channels: IoChannels) -> Result<()> {
+    let mut io = IoState { params, channels };
+    let command = io.channels.command
+        .take().context("The command channel is None")?;
+    let idle = io.channels.idle
+        .take().context("The idle channel is None")?;
+    loop {
+        match command.recv_async().await {
+            Ok(IoCommand::IoInput) => io.read_image().await?,
+            Ok(IoCommand::IoOutput) => {
+                let (width, height) = (io.params.image_width, io.params.image_height);
+                io.write_image(width, height, None).await?
+            },
+            Ok(IoCommand::IoOutputRegion { origin, width, height }) =>
+                io.write_image(width, height, Some(origin)).await?,
+            Ok(IoCommand::IoSnapshot(header)) => io.write_snapshot(*header).await?,
+            Ok(IoCommand::IoResume) => io.read_snapshot().await?,
+            Ok(IoCommand::IoCheckIdle) => idle.send_async(true).await?,
+            Err(_) => return Ok(()),
+        }
+    }
+}
+
+/// Fill in the `{w}`, `{h}`, `{turn}` and `{timestamp}` placeholders of an output name template.
+/// The timestamp is in seconds since the Unix epoch.
+pub fn output_name(template: &str, width: usize, height: usize, turn: u32) -> Result<String> {
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        name.push_str(&rest[..open]);
+        let close = rest[open..].find('}')
+            .with_context(|| format!("The output name {} has an unclosed {{", template))?;
+        match &rest[open + 1..open + close] {
+            "w" => name.push_str(&width.to_string()),
+            "h" => name.push_str(&height.to_string()),
+            "turn" => name.push_str(&turn.to_string()),
+            "timestamp" => name.push_str(&SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs().to_string()),
+            placeholder => bail!("The output name {} has an unknown placeholder {{{}}}", template, placeholder),
+        }
+        rest = &rest[open + close + 1..];
+    }
+    name.push_str(rest);
+    if name.is_empty() {
+        bail!("The output name cannot be empty");
+    }
+    Ok(name)
+}
+
+impl IoState {
+    /// Read the input image, or the input pattern placed on an empty board.
+    async fn read_image(&mut self) -> Result<()> {
+        let filename = self.channels.filename
+            .as_mut().context("The filename channel is None")?
+            .recv_async().await.context("The filename channel has been closed")?;
+        let path = self.params.input.clone()
+            .unwrap_or_else(|| PathBuf::from(format!("images/{}.pgm", filename)));
+        let mut buffer = Vec::new();
+        File::open(&path).await
+            .with_context(|| format!("Cannot open the input image {}", path.display()))?
+            .read_to_end(&mut buffer).await
+            .with_context(|| format!("Cannot read the input image {}", path.display()))?;
+        let head = &buffer[..buffer.len().min(SNIFF_LENGTH)];
+        let cells = match Format::detect(&path, head) {
+            Some(format) => self.place_pattern(&path, format, &buffer)?,
+            None => self.decode_pgm_image(path, &buffer)?,
+        };
+        let input_tx = self.channels.input.as_ref().context("The input channel is None")?;
+        for cell in cells {
+            input_tx.send_async(cell).await?;
+        }
+        Ok(())
+    }
+
+    fn decode_pgm_image(&self, path: PathBuf, buffer: &[u8]) -> Result<Vec<CellState>> {
+        let pgm = image::load_from_memory(buffer)
+            .with_context(|| format!("Cannot decode the input image {}", path.display()))?;
+        let given = (self.params.image_width, self.params.image_height);
+        let found = (pgm.width() as usize, pgm.height() as usize);
+        if given != found {
+            return Err(SizeError::Mismatch { path, given, found }.into());
+        }
+        let states = self.params.rule.states();
+        Ok(pgm.into_bytes().into_iter().map(|byte| CellState::from_grey(byte, states)).collect())
+    }
+
+    /// Parse a pattern and put it on the board where the parameters say. A pattern for another rule
+    /// is still run under the rule of the run, as long as it has no states that rule lacks.
+    /// A macrocell pattern is only expanded if it fits on the board.
+    fn place_pattern(&self, path: &Path, format: Format, buffer: &[u8]) -> Result<Vec<CellState>> {
+        let (width, height) = (self.params.image_width, self.params.image_height);
+        let pattern = std::str::from_utf8(buffer).map_err(anyhow::Error::from)
+            .and_then(|text| match format {
+                Format::Macrocell => macrocell::parse(text)?.to_pattern(width, height),
+                format => format.parse(text),
+            })
+            .with_context(|| format!("Cannot decode the input pattern {}", path.display()))?;
+        let rule = self.params.rule;
+        if let Some(state) = pattern.cells.iter().find(|cell| cell.get() >= rule.states()) {
+            bail!("The input pattern {} has a cell in state {} but the rule {} only has {} states",
+                path.display(), state.get(), rule, rule.states());
+        }
+        if let Some(other) = pattern.rule.filter(|&other| other != rule) {
+            log::warn!(target: "Main", "The input pattern {} is for {} but is run under {}", path.display(), other, rule);
+        }
+        pattern.place(self.params.place, width, height)
+            .with_context(|| format!("Cannot place the input pattern {}", path.display()))
+    }
+
+    /// Write the world to `{output directory}/{filename}`, in the pattern format its extension names,
+    /// or to `{output directory}/{filename}.pgm` if it names none. As with snapshots, it is written
+    /// to a temporary file first and renamed into place, so a failure never leaves a truncated image behind.
+    async fn write_image(&mut self, width: usize, height: usize, origin: Option<CellCoord<i64>>) -> Result<()> {
+        let filename = self.channels.filename
+            .as_mut().context("The filename channel is None")?
+            .recv_async().await.context("The filename channel has been closed")?;
+        let format = Format::from_path(Path::new(&filename));
+        let path = match format {
+            Some(_) => self.params.output_dir.join(&filename),
+            None => self.params.output_dir.join(format!("{}.pgm", filename)),
+        };
+        if let Some(directory) = path.parent() {
+            create_dir_all(directory).await
+                .with_context(|| format!("Cannot create the output directory {}", directory.display()))?;
+        }
+
+        let output_rx = self.channels.output
+            .as_mut().context("The output channel is None")?;
+        let mut cells = Vec::with_capacity(width * height);
+        for _ in 0..width * height {
+            cells.push(output_rx.recv_async().await.context("The output channel has been closed")?);
+        }
+
+        let bytes = match format {
+            Some(format) => {
+                let pattern = Pattern { rule: Some(self.params.rule), origin, ..Pattern::new(width, height, cells) };
+                format.write(&pattern)
+                    .with_context(|| format!("Cannot write the output pattern {}", path.display()))?
+                    .into_bytes()
+            },
+            None => encode_pgm_image(width, height, origin, &cells, self.params.rule.states()),
+        };
+        let mut partial = path.clone().into_os_string();
+        partial.push(".part");
+        write(&partial, bytes).await
+            .with_context(|| format!("Cannot create the output image {}", path.display()))?;
+        rename(&partial, &path).await?;
+        Ok(())
+    }
+
+    /// Write a snapshot to `{output directory}/{filename}.snap`. It is written to a temporary file first
+    /// and renamed into place, so a crash part way through never leaves a truncated snapshot behind.
+    async fn write_snapshot(&mut self, header: SnapshotHeader) -> Result<()> {
+        create_dir_all(&self.params.output_dir).await
+            .with_context(|| format!("Cannot create the output directory {}", self.params.output_dir.display()))?;
+        let filename = self.channels.filename
+            .as_mut().context("The filename channel is None")?
+            .recv_async().await.context("The filename channel has been closed")?;
+        let output_rx = self.channels.output
+            .as_mut().context("The output channel is None")?;
+        let mut cells = Vec::with_capacity(header.width * header.height);
+        for _ in 0..header.width * header.height {
+            cells.push(output_rx.recv_async().await.context("The output channel has been closed")?);
+        }
+        let bytes = snapshot::encode(&header, &cells)?;
+        let path = self.params.output_dir.join(format!("{}.snap", filename));
+        let partial = path.with_extension("snap.part");
+        write(&partial, bytes).await
+            .with_context(|| format!("Cannot write the snapshot {}", partial.display()))?;
+        rename(&partial, &path).await?;
+        Ok(())
+    }
+
+    /// Read the snapshot a run resumes from. A snapshot that cannot be read is reported
+    /// to the distributor rather than failing here, so that the run ends with the reason.
+    async fn read_snapshot(&mut self) -> Result<()> {
+        let path = self.channels.filename
+            .as_mut().context("The filename channel is None")?
+            .recv_async().await.context("The filename channel has been closed")?;
+        let snapshot_tx = self.channels.snapshot
+            .as_ref().context("The snapshot channel is None")?;
+        let decoded = read(&path).await
+            .with_context(|| format!("Cannot read the snapshot {}", path))
+            .and_then(|bytes| snapshot::decode(&bytes).with_context(|| format!("Cannot resume from {}", path)));
+        let (header, cells) = match decoded {
+            Ok(decoded) => decoded,
+            Err(error) => return Ok(snapshot_tx.send_async(Err(error)).await?),
+        };
+        snapshot_tx.send_async(Ok(header)).await?;
+        let input_tx = self.channels.input.as_ref().context("The input channel is None")?;
+        for cell in cells {
+            // The distributor stops listening once it finds the snapshot does not fit the run.
+            if input_tx.send_async(cell).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn encode_pgm_image(
+    width: usize,
+    height: usize,
+    origin: Option<CellCoord<i64>>,
+    cells: &[CellState],
+    states: u8,
+) -> Vec<u8> {
+    let mut bytes = b"P5\n".to_vec();
+    if let Some(origin) = origin {
+        bytes.extend(format!("# origin {} {}\n", origin.x, origin.y).as_bytes());
+    }
+    bytes.extend(format!("{} {}\n{}\n", width, height, 255).as_bytes());
+
+    // Each cell state is written as its evenly spaced grey level.
+    bytes.extend(cells.iter().map(|cell| cell.grey(states)));
+    bytes
+}
@@ -0,0 +1,270 @@
+use crate::gol::io::{life, macrocell::{self, Quadtree}, plaintext, rle};
+use crate::gol::rule::Rule;
+use crate::util::cell::{CellCoord, CellState};
+use anyhow::{bail, Context, Result};
+use std::fmt::Display;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The most of a file read to tell its format.
+pub const SNIFF_LENGTH: usize = 4096;
+
+/// The most cells a pattern is expanded to or placed on. A header or a couple of far apart
+/// coordinates can describe billions of cells in a few bytes, which are refused rather than allocated.
+pub const MAX_CELLS: usize = 1 << 30;
+
+/// `Format` is a pattern file format, chosen by the extension of the file or, when that does not
+/// settle it, by what the file starts with. Other files are read and written as PGM images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Golly's run length encoding, `.rle`.
+    Rle,
+    /// Rows of `.` and `O`, `.cells`.
+    Plaintext,
+    /// Blocks of `.` and `*` rows, each after the `#P` position of its top left cell, `.lif`.
+    Life105,
+    /// The `x y` coordinates of each alive cell, `.lif`.
+    Life106,
+    /// Golly's canonical quadtree of 8x8 leaves, `.mc`.
+    Macrocell,
+}
+
+impl Format {
+    /// The pattern format named by the extension of `path`, ignoring case. Both versions of Life
+    /// share `.lif` and `.life`, which are written as Life 1.06.
+    pub fn from_path(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "rle" => Some(Format::Rle),
+            "cells" => Some(Format::Plaintext),
+            "lif" | "life" => Some(Format::Life106),
+            "mc" => Some(Format::Macrocell),
+            _ => None,
+        }
+    }
+
+    /// The pattern format of a file at `path` that starts with `head`. A `.lif` or `.life` file
+    /// is told apart by its `#Life` line, and a file with any other extension than these or `.pgm`
+    /// is recognised by its first lines.
+    pub fn detect(path: &Path, head: &[u8]) -> Option<Format> {
+        match path.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("pgm") => None,
+            Some("lif" | "life") => Some(Format::sniff(head).unwrap_or(Format::Life106)),
+            _ => Format::from_path(path).or_else(|| Format::sniff(head)),
+        }
+    }
+
+    /// The pattern format of the file at `path`, reading no more of it than needed to tell.
+    pub fn of_file(path: &Path) -> std::io::Result<Option<Format>> {
+        let mut head = Vec::new();
+        File::open(path)?.take(SNIFF_LENGTH as u64).read_to_end(&mut head)?;
+        Ok(Format::detect(path, &head))
+    }
+
+    /// The pattern format a file starting with `head` looks to be in, if any.
+    pub fn sniff(head: &[u8]) -> Option<Format> {
+        let text = String::from_utf8_lossy(head);
+        if text.starts_with("#Life 1.05") {
+            return Some(Format::Life105);
+        }
+        if text.starts_with("#Life 1.06") {
+            return Some(Format::Life106);
+        }
+        if text.starts_with("[M2]") {
+            return Some(Format::Macrocell);
+        }
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            match line.as_bytes()[0] {
+                b'!' => return Some(Format::Plaintext),
+                b'#' => continue,
+                b'x' if line.contains('=') => return Some(Format::Rle),
+                _ if line.bytes().all(|byte| matches!(byte, b'.' | b'O' | b'*')) => return Some(Format::Plaintext),
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    /// Parse a pattern file of this format. A macrocell pattern is expanded whatever its size,
+    /// so [`macrocell::parse`] is the way to read one that may not fit in memory.
+    pub fn parse(&self, text: &str) -> Result<Pattern> {
+        match self {
+            Format::Rle => rle::parse(text),
+            Format::Plaintext => plaintext::parse(text),
+            Format::Life105 => life::parse_105(text),
+            Format::Life106 => life::parse_106(text),
+            Format::Macrocell => macrocell::parse(text)?.to_pattern(usize::MAX, usize::MAX),
+        }
+    }
+
+    /// The width and height of a pattern file of this format, without expanding a macrocell pattern.
+    pub fn size(&self, text: &str) -> Result<(usize, usize)> {
+        if *self == Format::Macrocell {
+            let (_, width, height) = macrocell::parse(text)?.bounds().unwrap_or((CellCoord::new(0, 0), 0, 0));
+            return Ok((
+                usize::try_from(width).context("The pattern is too wide")?,
+                usize::try_from(height).context("The pattern is too tall")?,
+            ));
+        }
+        let pattern = self.parse(text)?;
+        Ok((pattern.width, pattern.height))
+    }
+
+    /// Write a pattern in this format. Only RLE and macrocell can hold states besides dead and alive.
+    pub fn write(&self, pattern: &Pattern) -> Result<String> {
+        if !matches!(self, Format::Rle | Format::Macrocell) {
+            if let Some(cell) = pattern.cells.iter().find(|cell| cell.get() > 1) {
+                bail!("{} patterns only hold dead and alive cells, not cells in state {}", self, cell.get());
+            }
+        }
+        Ok(match self {
+            Format::Rle => rle::write(pattern),
+            Format::Plaintext => plaintext::write(pattern),
+            Format::Life105 => life::write_105(pattern),
+            Format::Life106 => life::write_106(pattern),
+            Format::Macrocell => macrocell::write(&Quadtree::from_pattern(pattern)?),
+        })
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Format::Rle => write!(f, "RLE"),
+            Format::Plaintext => write!(f, "Plaintext"),
+            Format::Life105 => write!(f, "Life 1.05"),
+            Format::Life106 => write!(f, "Life 1.06"),
+            Format::Macrocell => write!(f, "Macrocell"),
+        }
+    }
+}
+
+/// `Pattern` is a world as a pattern file holds it: the cells of its bounding box
+/// and what the file says about them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pattern {
+    pub width: usize,
+    pub height: usize,
+    /// The state of each of the `width` by `height` cells, row by row.
+    pub cells: Vec<CellState>,
+    /// The rule the pattern is for, if the file names one.
+    pub rule: Option<Rule>,
+    pub name: Option<String>,
+    pub comments: Vec<String>,
+    /// The world coordinate of the top left cell, if the file records one.
+    pub origin: Option<CellCoord<i64>>,
+}
+
+impl Pattern {
+    /// A pattern of the `width` by `height` `cells`, row by row, with nothing else said about them.
+    pub fn new(width: usize, height: usize, cells: Vec<CellState>) -> Self {
+        assert_eq!(cells.len(), width * height, "A {}x{} pattern needs {} cells", width, height, width * height);
+        Pattern { width, height, cells, ..Pattern::default() }
+    }
+
+    /// The coordinates of the alive cells, row by row.
+    pub fn alive_cells(&self) -> Vec<CellCoord> {
+        self.cells.iter().enumerate()
+            .filter(|(_, cell)| cell.is_alive())
+            .map(|(i, _)| CellCoord::new(i % self.width.max(1), i / self.width.max(1)))
+            .collect()
+    }
+
+    /// The smallest pattern holding these alive cells, with its origin at the top left of them.
+    pub fn from_alive_cells(alive: &[CellCoord<i64>]) -> Result<Self> {
+        let Some(&first) = alive.first() else {
+            return Ok(Pattern::default());
+        };
+        let (min, max) = alive.iter().fold((first, first), |(min, max), cell| (
+            CellCoord::new(min.x.min(cell.x), min.y.min(cell.y)),
+            CellCoord::new(max.x.max(cell.x), max.y.max(cell.y)),
+        ));
+        let size = |min: i64, max: i64| usize::try_from(max.abs_diff(min)).ok().and_then(|size| size.checked_add(1));
+        let (width, height) = size(min.x, max.x).zip(size(min.y, max.y)).context("The pattern is too large")?;
+        let mut cells = dead_cells(width, height).context("The pattern is too large")?;
+        for cell in alive {
+            cells[cell.y.abs_diff(min.y) as usize * width + cell.x.abs_diff(min.x) as usize] = CellState::ALIVE;
+        }
+        Ok(Pattern { origin: Some(min), ..Pattern::new(width, height, cells) })
+    }
+
+    /// The world coordinates of the alive cells, row by row, counting from the origin if there is one.
+    pub fn alive_world_cells(&self) -> Vec<CellCoord<i64>> {
+        let origin = self.origin.unwrap_or(CellCoord::new(0, 0));
+        self.alive_cells().into_iter().map(|cell| cell.offset(origin)).collect()
+    }
+
+    /// Put the pattern on an empty `width` by `height` board, returning the cells of the board row by row.
+    pub fn place(&self, placement: Placement, width: usize, height: usize) -> Result<Vec<CellState>> {
+        let at = match placement {
+            Placement::Centre if self.width <= width && self.height <= height =>
+                CellCoord::new((width - self.width) / 2, (height - self.height) / 2),
+            Placement::At(at) if at.x.checked_add(self.width).is_some_and(|right| right <= width)
+                && at.y.checked_add(self.height).is_some_and(|bottom| bottom <= height) => at,
+            _ => bail!(
+                "The {}x{} pattern does not fit on the {}x{} board at {}",
+                self.width, self.height, width, height, placement
+            ),
+        };
+        let mut board = dead_cells(width, height).context("The board is too large")?;
+        for (y, row) in self.cells.chunks(self.width.max(1)).enumerate() {
+            let start = (at.y + y) * width + at.x;
+            board[start..start + row.len()].copy_from_slice(row);
+        }
+        Ok(board)
+    }
+}
+
+/// The dead cells of a `width` by `height` pattern or board, as long as it has no more than [`MAX_CELLS`].
+pub fn dead_cells(width: usize, height: usize) -> Result<Vec<CellState>> {
+    match width.checked_mul(height) {
+        Some(cells) if cells <= MAX_CELLS => Ok(vec![CellState::DEAD; cells]),
+        _ => bail!("{}x{} cells are more than the {} a pattern can have", width, height, MAX_CELLS),
+    }
+}
+
+/// `Placement` is where a pattern read from a file is put on the board.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Placement {
+    /// In the middle of the board, half a cell up and to the left when it cannot be exactly in the middle.
+    #[default]
+    Centre,
+    /// With its top left cell at this cell of the board.
+    At(CellCoord),
+}
+
+impl Display for Placement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Placement::Centre => write!(f, "centre"),
+            Placement::At(at) => write!(f, "{},{}", at.x, at.y),
+        }
+    }
+}
+
+/// ParsePlacementError is a placement that is neither `centre` nor `x,y`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePlacementError(pub String);
+
+impl Display for ParsePlacementError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown placement {}, expected centre or x,y", self.0)
+    }
+}
+
+impl std::error::Error for ParsePlacementError {}
+
+impl FromStr for Placement {
+    type Err = ParsePlacementError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let placement = s.trim().to_ascii_lowercase();
+        if placement == "centre" || placement == "center" {
+            return Ok(Placement::Centre);
+        }
+        placement.split_once(',')
+            .and_then(|(x, y)| Some(Placement::At(CellCoord::new(x.trim().parse().ok()?, y.trim().parse().ok()?))))
+            .ok_or_else(|| ParsePlacementError(s.to_string()))
+    }
+}
@@ -0,0 +1,65 @@
+use crate::gol::io::pattern::{dead_cells, Pattern};
+use crate::util::cell::CellState;
+use anyhow::{bail, Context, Result};
+
+/// Parse a pattern in the LifeWiki's plaintext format, for example
+/// ``` text
+/// !Name: Glider
+/// !The smallest spaceship.
+/// .O.
+/// ..O
+/// OOO
+/// ```
+/// `!Name:` names the pattern and other lines starting with `!` are comments. Each other line
+/// is a row, with `.` for dead and `O` or `*` for alive cells, and rows shorter than the longest
+/// are dead to the right. Blank lines after the last row are left out.
+pub fn parse(text: &str) -> Result<Pattern> {
+    let mut pattern = Pattern::default();
+    let mut rows = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        if let Some(comment) = line.strip_prefix('!') {
+            match comment.strip_prefix("Name:") {
+                Some(name) => pattern.name = Some(name.trim().to_string()),
+                None => pattern.comments.push(comment.trim().to_string()),
+            }
+            continue;
+        }
+        let row = line.bytes()
+            .map(|byte| match byte {
+                b'.' => Ok(CellState::DEAD),
+                b'O' | b'*' => Ok(CellState::ALIVE),
+                byte => bail!("The plaintext pattern has an unexpected {:?} on line {}", byte as char, number + 1),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        rows.push(row);
+    }
+    while rows.last().is_some_and(Vec::is_empty) {
+        rows.pop();
+    }
+
+    pattern.width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    pattern.height = rows.len();
+    pattern.cells = dead_cells(pattern.width, pattern.height).context("The plaintext pattern is too large")?;
+    for (y, row) in rows.iter().enumerate() {
+        pattern.cells[y * pattern.width..][..row.len()].copy_from_slice(row);
+    }
+    Ok(pattern)
+}
+
+/// Write a pattern in the plaintext format, as described in [`parse`]. Every cell of the pattern
+/// is written, so that it is read back at the same size.
+pub fn write(pattern: &Pattern) -> String {
+    let mut text = String::new();
+    if let Some(name) = &pattern.name {
+        text.push_str(&format!("!Name: {}\n", name));
+    }
+    for comment in &pattern.comments {
+        text.push_str(&format!("!{}\n", comment));
+    }
+    for row in pattern.cells.chunks(pattern.width.max(1)) {
+        text.extend(row.iter().map(|cell| if cell.is_alive() { 'O' } else { '.' }));
+        text.push('\n');
+    }
+    text
+}
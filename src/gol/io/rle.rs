@@ -0,0 +1,204 @@
+use crate::gol::io::pattern::{dead_cells, Pattern};
+use crate::gol::rule::Rule;
+use crate::util::cell::{CellCoord, CellState};
+use anyhow::{bail, Context, Result};
+
+/// The longest line written, as Golly and the LifeWiki keep to.
+const LINE_LENGTH: usize = 70;
+
+/// Parse a pattern in Golly's run length encoding, for example
+/// ``` text
+/// #N Glider
+/// #C The smallest spaceship.
+/// x = 3, y = 3, rule = B3/S23
+/// bob$2bo$3o!
+/// ```
+/// `#N` names the pattern, `#C` and `#c` lines are comments and `#R` or `#P` give the world
+/// coordinate of its top left cell. The header gives the size of the pattern and optionally its rule,
+/// and the runs after it may span any number of lines up to the `!` that ends them. Two state
+/// patterns use `b` for dead and `o` for alive cells, and patterns with more states use `.` for dead
+/// and `A` to `X`, then `pA` to `yO`, for the states from 1 to 255.
+pub fn parse(text: &str) -> Result<Pattern> {
+    let mut pattern = Pattern::default();
+    let mut lines = text.lines().enumerate();
+    let (header, size) = loop {
+        let (number, line) = lines.next().context("The RLE pattern has no header")?;
+        let line = line.trim();
+        match line.strip_prefix('#') {
+            Some(comment) => read_comment(&mut pattern, comment)
+                .with_context(|| format!("The RLE pattern has an invalid comment on line {}", number + 1))?,
+            None if line.is_empty() => (),
+            None => break (number + 1, read_header(&mut pattern, line)
+                .with_context(|| format!("The RLE pattern has an invalid header on line {}", number + 1))?),
+        }
+    };
+    (pattern.width, pattern.height) = size;
+    pattern.cells = dead_cells(size.0, size.1).context("The RLE pattern is too large")?;
+
+    let mut cell = CellCoord::<usize>::new(0, 0);
+    let mut count = None::<usize>;
+    let mut prefix = None::<u8>;
+    'lines: for (number, line) in lines {
+        let context = || format!("The RLE pattern has invalid runs on line {} after the header on line {}", number + 1, header);
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        for byte in line.bytes() {
+            let state = match byte {
+                b'0'..=b'9' if prefix.is_none() => {
+                    let digit = (byte - b'0') as usize;
+                    count = Some(count.unwrap_or(0).checked_mul(10).and_then(|count| count.checked_add(digit))
+                        .with_context(context)?);
+                    continue;
+                },
+                b'p'..=b'y' if prefix.is_none() => {
+                    prefix = Some(byte);
+                    continue;
+                },
+                b'A'..=b'X' => {
+                    let high = prefix.take().map_or(0, |prefix| (prefix - b'p' + 1) as u32);
+                    let state = high * 24 + (byte - b'A' + 1) as u32;
+                    u8::try_from(state).ok().with_context(|| format!("{}: there is no state {}", context(), state))?
+                },
+                b'b' | b'.' if prefix.is_none() => 0,
+                b'o' if prefix.is_none() => 1,
+                b'$' if prefix.is_none() => {
+                    cell = CellCoord::new(0, cell.y.saturating_add(count.take().unwrap_or(1)));
+                    continue;
+                },
+                b'!' if prefix.is_none() => break 'lines,
+                byte if byte.is_ascii_whitespace() && prefix.is_none() => continue,
+                byte => bail!("{}: unexpected {:?}", context(), byte as char),
+            };
+            let run = count.take().unwrap_or(1);
+            if state != 0 {
+                if cell.y >= pattern.height || cell.x.checked_add(run).is_none_or(|end| end > pattern.width) {
+                    bail!("{}: the cells run past the {}x{} the header gives", context(), pattern.width, pattern.height);
+                }
+                let start = cell.y * pattern.width + cell.x;
+                pattern.cells[start..start + run].fill(CellState::new(state));
+            }
+            cell.x = cell.x.saturating_add(run);
+        }
+    }
+    Ok(pattern)
+}
+
+/// Read a comment line, without its `#`.
+fn read_comment(pattern: &mut Pattern, comment: &str) -> Result<()> {
+    let mut chars = comment.chars();
+    let (kind, text) = (chars.next(), chars.as_str().trim());
+    match kind {
+        Some('N') => pattern.name = Some(text.to_string()),
+        Some('C' | 'c') => pattern.comments.push(text.to_string()),
+        Some('R' | 'P') => {
+            let (x, y) = text.split_once(char::is_whitespace).context("The position needs an x and a y")?;
+            pattern.origin = Some(CellCoord::new(x.trim().parse()?, y.trim().parse()?));
+        },
+        // The author, the rule as XLife writes it and anything else are left out.
+        _ => (),
+    }
+    Ok(())
+}
+
+/// Read a header such as `x = 3, y = 3, rule = B3/S23`, setting the rule and returning the size.
+fn read_header(pattern: &mut Pattern, header: &str) -> Result<(usize, usize)> {
+    let (mut width, mut height) = (None, None);
+    let mut rest = header;
+    while let Some((key, after)) = rest.split_once('=') {
+        // Larger than Life rules have commas of their own, so the rule runs to the end of the header.
+        let (value, next) = match key.trim() {
+            "rule" => (after, ""),
+            _ => after.split_once(',').unwrap_or((after, "")),
+        };
+        let value = value.trim();
+        match key.trim() {
+            "x" => width = Some(value.parse::<usize>().with_context(|| format!("Invalid width {}", value))?),
+            "y" => height = Some(value.parse::<usize>().with_context(|| format!("Invalid height {}", value))?),
+            // Golly appends the bounded grid after a colon, which is left to the topology of the run.
+            "rule" => pattern.rule = Some(value.split(':').next().unwrap_or(value).parse::<Rule>()?),
+            _ => (),
+        }
+        rest = next;
+    }
+    if !rest.trim().is_empty() {
+        bail!("{} is not of the form key = value", rest.trim());
+    }
+    Ok((width.context("The header has no x")?, height.context("The header has no y")?))
+}
+
+/// Write a pattern in Golly's run length encoding, as described in [`parse`].
+/// Trailing dead cells are left out of each row, as are blank rows at the bottom.
+pub fn write(pattern: &Pattern) -> String {
+    let mut text = String::new();
+    if let Some(name) = &pattern.name {
+        text.push_str(&format!("#N {}\n", name));
+    }
+    for comment in &pattern.comments {
+        text.push_str(&format!("#C {}\n", comment));
+    }
+    if let Some(origin) = pattern.origin {
+        text.push_str(&format!("#R {} {}\n", origin.x, origin.y));
+    }
+    text.push_str(&format!("x = {}, y = {}", pattern.width, pattern.height));
+    if let Some(rule) = pattern.rule {
+        text.push_str(&format!(", rule = {}", rule));
+    }
+    text.push('\n');
+
+    let generations = pattern.rule.is_some_and(|rule| rule.is_generations())
+        || pattern.cells.iter().any(|cell| cell.get() > 1);
+    let mut runs = Vec::new();
+    let mut blank_rows = 0;
+    for row in pattern.cells.chunks(pattern.width.max(1)) {
+        let mut x = 0;
+        while x < row.len() {
+            let state = row[x];
+            let run = row[x..].iter().take_while(|&&cell| cell == state).count();
+            if !state.is_dead() || x + run < row.len() {
+                if blank_rows > 0 {
+                    runs.push(run_of(blank_rows, "$".to_string()));
+                    blank_rows = 0;
+                }
+                runs.push(run_of(run, tag(state, generations)));
+            }
+            x += run;
+        }
+        blank_rows += 1;
+    }
+    runs.push("!".to_string());
+
+    let mut line = String::new();
+    for run in runs {
+        if line.len() + run.len() > LINE_LENGTH {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        }
+        line.push_str(&run);
+    }
+    text.push_str(&line);
+    text.push('\n');
+    text
+}
+
+fn run_of(count: usize, tag: String) -> String {
+    match count {
+        1 => tag,
+        count => format!("{}{}", count, tag),
+    }
+}
+
+/// The letters a cell state is written as.
+fn tag(state: CellState, generations: bool) -> String {
+    match (state.get(), generations) {
+        (0, false) => "b".to_string(),
+        (1, false) => "o".to_string(),
+        (0, true) => ".".to_string(),
+        (state @ 1..=24, _) => ((b'A' + state - 1) as char).to_string(),
+        (state, _) => {
+            let (high, low) = ((state - 1) / 24, (state - 1) % 24);
+            format!("{}{}", (b'p' + high - 1) as char, (b'A' + low) as char)
+        },
+    }
+}
@@ -0,0 +1,129 @@
+use crate::gol::topology::Topology;
+use std::fmt::Display;
+
+/// Offsets of the 8 neighbours of a square cell, in row-major order.
+const SQUARE: [(isize, isize); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+
+/// Offsets of the 6 neighbours of a hexagon in an even row, and in an odd row shifted half a cell right.
+const HEXAGONAL: [[(isize, isize); 6]; 2] = [
+    [(-1, -1), (0, -1), (-1, 0), (1, 0), (-1, 1), (0, 1)],
+    [(0, -1), (1, -1), (-1, 0), (1, 0), (0, 1), (1, 1)],
+];
+
+/// Offsets of the 12 triangles sharing a corner with a triangle pointing up, and with one pointing down.
+const TRIANGULAR: [[(isize, isize); 12]; 2] = [
+    [
+        (-1, -1), (0, -1), (1, -1),
+        (-2, 0), (-1, 0), (1, 0), (2, 0),
+        (-2, 1), (-1, 1), (0, 1), (1, 1), (2, 1),
+    ],
+    [
+        (-2, -1), (-1, -1), (0, -1), (1, -1), (2, -1),
+        (-2, 0), (-1, 0), (1, 0), (2, 0),
+        (-1, 1), (0, 1), (1, 1),
+    ],
+];
+
+/// Lattice is the shape of the cells and how they are stored in the rows of the world and of PGM images.
+///
+/// Every lattice is stored as a raster of `width` by `height` cells, so images and events need no
+/// conversion, but the cells at raster `(x, y)` are laid out differently:
+///
+/// - Square cells are laid out as stored.
+/// - Hexagons are stored in offset rows, with every odd row shifted half a cell to the right.
+///   The hexagon at axial coordinates `(q, r)` is at raster `(q + floor(r / 2), r)`,
+///   see [`Lattice::axial_to_raster`], so a torus of hexagons needs an even height.
+/// - Triangles alternate between pointing up and down along each row. The triangle at raster `(x, y)`
+///   points up when `x + y` is even, so a torus of triangles needs an even width and height.
+///
+/// ## Examples
+/// ``` ignore
+/// let rule: Rule = "B2/S34H".parse()?;
+/// assert_eq!(rule.lattice(), Lattice::Hexagonal);
+/// assert_eq!(Lattice::axial_to_raster(3, -1), (2, -1));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Lattice {
+    /// Squares with the 8 cells around each one as neighbours.
+    #[default]
+    Square,
+    /// Hexagons with the 6 cells sharing an edge as neighbours.
+    Hexagonal,
+    /// Triangles with the 12 cells sharing a corner as neighbours.
+    Triangular,
+}
+
+impl Lattice {
+    /// The number of neighbours of each cell.
+    pub fn neighbours(self) -> u16 {
+        match self {
+            Lattice::Square => 8,
+            Lattice::Hexagonal => 6,
+            Lattice::Triangular => 12,
+        }
+    }
+
+    /// The raster offsets of the neighbours of the cell at `(x, y)`, which depend on its row for
+    /// hexagons and on which way it points for triangles.
+    pub fn offsets(self, x: usize, y: usize) -> &'static [(isize, isize)] {
+        match self {
+            Lattice::Square => &SQUARE,
+            Lattice::Hexagonal => &HEXAGONAL[y % 2],
+            Lattice::Triangular => &TRIANGULAR[(x + y) % 2],
+        }
+    }
+
+    /// How many cells away horizontally a neighbour can be.
+    pub fn reach(self) -> usize {
+        match self {
+            Lattice::Square | Lattice::Hexagonal => 1,
+            Lattice::Triangular => 2,
+        }
+    }
+
+    /// Whether the triangle at raster `(x, y)` points up.
+    pub fn points_up(x: i64, y: i64) -> bool {
+        (x + y).rem_euclid(2) == 0
+    }
+
+    /// The raster coordinates of the hexagon at axial coordinates `(q, r)`.
+    pub fn axial_to_raster(q: i64, r: i64) -> (i64, i64) {
+        (q + r.div_euclid(2), r)
+    }
+
+    /// The axial coordinates of the hexagon at raster `(x, y)`.
+    pub fn raster_to_axial(x: i64, y: i64) -> (i64, i64) {
+        (x - y.div_euclid(2), y)
+    }
+
+    /// The letter that follows a B/S rulestring on this lattice, as Golly and LifeViewer write them.
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Lattice::Square => "",
+            Lattice::Hexagonal => "H",
+            Lattice::Triangular => "L",
+        }
+    }
+
+    /// Whether a `width` by `height` world of this lattice can join its edges as `topology` does.
+    /// Hexagons and triangles only keep their shape across the seams of a torus whose rows and
+    /// columns alternate evenly, and only squares can be mirrored across a seam.
+    pub fn supports(self, topology: Topology, width: usize, height: usize) -> bool {
+        match (self, topology) {
+            (Lattice::Square, _) | (_, Topology::Plane) => true,
+            (Lattice::Hexagonal, Topology::Torus) => height.is_multiple_of(2),
+            (Lattice::Triangular, Topology::Torus) => width.is_multiple_of(2) && height.is_multiple_of(2),
+            _ => false,
+        }
+    }
+}
+
+impl Display for Lattice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Lattice::Square => write!(f, "square"),
+            Lattice::Hexagonal => write!(f, "hexagonal"),
+            Lattice::Triangular => write!(f, "triangular"),
+        }
+    }
+}
@@ -1,15 +1,32 @@
-use crate::args::Args;
+use crate::args::{Args, DEFAULT_SIZE};
 use crate::gol::distributor::{DistributorChannels, distributor};
+use crate::gol::engine::{Balance, EngineKind};
 use crate::gol::event::Event;
-use crate::gol::io::{start_io, IoChannels};
+use crate::gol::io::{pattern::Placement, start_io, IoChannels};
+use crate::gol::rule::Rule;
+use crate::gol::topology::Topology;
+use crate::util::cell::CellState;
 use anyhow::Result;
 use flume::{Receiver, Sender};
 use io::IoCommand;
 use sdl2::keyboard::Keycode;
+use snapshot::SnapshotHeader;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 
 pub mod distributor;
+pub mod engine;
 pub mod event;
+pub mod history;
 pub mod io;
+pub mod lattice;
+pub mod protocol;
+pub mod remote;
+pub mod rule;
+pub mod snapshot;
+pub mod stability;
+pub mod topology;
+pub mod worker;
 
 /// `Params` provides the details of how to run the Game of Life and which image to load.
 #[derive(Clone, Debug)]
@@ -18,6 +35,23 @@ pub struct Params {
     pub threads: usize,
     pub image_width: usize,
     pub image_height: usize,
+    pub input: Option<PathBuf>,
+    pub place: Placement,
+    pub output_dir: PathBuf,
+    pub output_name: String,
+    pub rule: Rule,
+    pub topology: Topology,
+    pub unbounded: bool,
+    pub engine: EngineKind,
+    pub hashlife_memory: usize,
+    pub balance: Balance,
+    pub balance_interval: u64,
+    pub history: usize,
+    pub stop_on_stable: bool,
+    pub snapshot_every: usize,
+    pub resume: Option<PathBuf>,
+    pub workers: Vec<SocketAddr>,
+    pub worker_timeout: u64,
 }
 
 pub async fn run<P: Into<Params>>(
@@ -26,44 +60,71 @@ pub async fn run<P: Into<Params>>(
     key_presses: Receiver<Keycode>,
 ) -> Result<()> {
     let params: Params = params.into();
-    // TODO: Put the missing channels in here.
 
     let (io_command_tx, io_command_rx) = flume::unbounded::<IoCommand>();
     let (io_idle_tx, io_idle_rx) = flume::unbounded::<bool>();
+    let (io_filename_tx, io_filename_rx) = flume::unbounded::<String>();
+    let (io_input_tx, io_input_rx) = flume::unbounded::<CellState>();
+    let (io_output_tx, io_output_rx) = flume::unbounded::<CellState>();
+    let (io_snapshot_tx, io_snapshot_rx) = flume::unbounded::<Result<SnapshotHeader>>();
 
     let io_channels = IoChannels {
         command: Some(io_command_rx),
         idle: Some(io_idle_tx),
-        filename: None,
-        input: None,
-        output: None,
+        filename: Some(io_filename_rx),
+        input: Some(io_input_tx),
+        output: Some(io_output_rx),
+        snapshot: Some(io_snapshot_tx),
     };
 
-    tokio::spawn(start_io(params.clone(), io_channels));
+    let io = tokio::spawn(start_io(params.clone(), io_channels));
 
     let distributor_channels = DistributorChannels {
         events: Some(events),
         key_presses: Some(key_presses),
         io_command: Some(io_command_tx),
         io_idle: Some(io_idle_rx),
-        io_filename: None,
-        io_input: None,
-        io_output: None,
+        io_filename: Some(io_filename_tx),
+        io_input: Some(io_input_rx),
+        io_output: Some(io_output_tx),
+        io_snapshot: Some(io_snapshot_rx),
     };
 
-    tokio::task::spawn_blocking(move ||
-        distributor(params, distributor_channels)).await??;
+    let distributed = tokio::task::spawn_blocking(move ||
+        distributor(params, distributor_channels)).await?;
 
-    Ok(())
+    // The Io stops at its first error, which leaves the distributor on a closed channel,
+    // so an error from the Io says more about what went wrong.
+    io.await??;
+    distributed
 }
 
 impl From<Args> for Params {
+    /// Copy the arguments, with a width or height left out as 512. A size taken from the input image
+    /// has to be settled with [`Args::size`] beforehand, as `app::run` does, or the Io reports the mismatch.
     fn from(args: Args) -> Self {
         Params {
             turns: args.turns,
             threads: args.threads,
-            image_width: args.image_width,
-            image_height: args.image_height,
+            image_width: args.image_width.unwrap_or(DEFAULT_SIZE),
+            image_height: args.image_height.unwrap_or(DEFAULT_SIZE),
+            input: args.input,
+            place: args.place,
+            output_dir: args.output_dir,
+            output_name: args.output_name,
+            rule: args.rule,
+            topology: args.topology,
+            unbounded: args.unbounded,
+            engine: args.engine,
+            hashlife_memory: args.hashlife_memory,
+            balance: args.balance,
+            balance_interval: args.balance_interval,
+            history: args.history,
+            stop_on_stable: args.stop_on_stable,
+            snapshot_every: args.snapshot_every,
+            resume: args.resume,
+            workers: args.workers,
+            worker_timeout: args.worker_timeout,
         }
     }
 }
@@ -0,0 +1,310 @@
+use crate::gol::rule::Rule;
+use crate::gol::topology::Topology;
+use crate::util::cell::{CellCoord, CellState};
+use anyhow::{bail, Context, Result};
+use std::io::{ErrorKind, Read, Write};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+const LOAD: u8 = 1;
+const STEP: u8 = 2;
+const SHUTDOWN: u8 = 3;
+const STEPPED: u8 = 4;
+const FAILED: u8 = 5;
+const HEARTBEAT: u8 = 6;
+
+/// Load gives a worker its strip of the world and tells it where the other strips are,
+/// so that it can exchange the cells around its strip with the workers holding them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Load {
+    pub rule: Rule,
+    pub topology: Topology,
+    pub width: usize,
+    pub height: usize,
+    /// Which of the strips this worker holds. A worker past the last strip is left idle.
+    pub index: usize,
+    /// Counts the loads of the broker, so that halo connections left over from a load
+    /// that failed are not mistaken for connections of this one.
+    pub epoch: usize,
+    /// How long to wait on a silent peer before giving up on it. The worker sends the broker
+    /// heartbeats four times as often while it works, so the broker can wait as long on it.
+    pub timeout: Duration,
+    /// The rows `[start, end)` of every strip, top to bottom.
+    pub strips: Vec<(usize, usize)>,
+    /// The address each worker holding a strip exchanges halo cells on.
+    pub peers: Vec<SocketAddr>,
+    /// The cells of this worker's strip in row-major order.
+    pub cells: Vec<CellState>,
+}
+
+/// Request is a message from a broker to a worker.
+///
+/// Every message starts with a tag byte. Integers are little endian `u32`, and strings are
+/// a `u16` byte length followed by UTF-8, like snapshots. Cells are one state index byte each.
+///
+/// | Request | Tag | Fields |
+/// | --- | --- | --- |
+/// | `Load` | 1 | rule string, topology string, width, height, index, epoch, timeout in milliseconds, strip count, start and end of each strip, the address string of each strip, cell count, cells |
+/// | `Step` | 2 | turns |
+/// | `Shutdown` | 3 | |
+///
+/// A worker first sends the broker the address string it exchanges halo cells on, and answers
+/// every `Load` and `Step` with a [`Reply`]. Workers exchange halo cells with each other as a
+/// cell count followed by the cells, after a connecting worker has sent its strip index and epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    Load(Box<Load>),
+    /// Step the strip `turns` turns, exchanging halo cells with the other workers after each.
+    Step { turns: u32 },
+    /// Stop the worker, as when `K` is pressed.
+    Shutdown,
+}
+
+/// Reply is a message from a worker to the broker, starting with a tag byte like a [`Request`].
+///
+/// | Reply | Tag | Fields |
+/// | --- | --- | --- |
+/// | `Stepped` | 4 | `u64` population, `u64` halo bytes, flip count, the x, y and state byte of each flip |
+/// | `Failed` | 5 | reason string |
+/// | `Heartbeat` | 6 | |
+///
+/// A worker sends any number of heartbeats while it works on a `Load` or `Step`, and then one
+/// `Stepped` or `Failed` for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    Stepped(Stepped),
+    /// The worker could not take the step, usually because it lost a peer, and has dropped its strip.
+    Failed(String),
+    Heartbeat,
+}
+
+/// Stepped is a worker's answer to a `Load` or `Step`: the alive cells left in its strip and the
+/// cells whose state changed, in world coordinates.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stepped {
+    pub population: u64,
+    pub halo_bytes: u64,
+    pub flips: Vec<(CellCoord, CellState)>,
+}
+
+pub fn write_request<W: Write>(writer: &mut W, request: &Request) -> Result<()> {
+    match request {
+        Request::Load(load) => {
+            writer.write_all(&[LOAD])?;
+            write_string(writer, &load.rule.to_string())?;
+            write_string(writer, &load.topology.to_string())?;
+            for field in [load.width, load.height, load.index, load.epoch, usize::try_from(load.timeout.as_millis())?, load.strips.len()] {
+                write_u32(writer, field)?;
+            }
+            for &(start, end) in &load.strips {
+                write_u32(writer, start)?;
+                write_u32(writer, end)?;
+            }
+            write_u32(writer, load.peers.len())?;
+            for peer in &load.peers {
+                write_string(writer, &peer.to_string())?;
+            }
+            write_cells(writer, &load.cells)?;
+        },
+        &Request::Step { turns } => {
+            writer.write_all(&[STEP])?;
+            writer.write_all(&turns.to_le_bytes())?;
+        },
+        Request::Shutdown => writer.write_all(&[SHUTDOWN])?,
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read the next request, or `None` once the broker has closed the connection.
+pub fn read_request<R: Read>(reader: &mut R) -> Result<Option<Request>> {
+    let mut tag = [0_u8];
+    match reader.read_exact(&mut tag) {
+        Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    Ok(Some(match tag[0] {
+        LOAD => {
+            let rule = read_string(reader)?.parse::<Rule>().context("The broker sent an invalid rule")?;
+            let topology = read_string(reader)?.parse::<Topology>().context("The broker sent an invalid topology")?;
+            let (width, height, index) = (read_u32(reader)?, read_u32(reader)?, read_u32(reader)?);
+            let (epoch, timeout) = (read_u32(reader)?, Duration::from_millis(read_u32(reader)? as u64));
+            // Every strip holds a row, and every length is checked against the world before anything is allocated for it.
+            let strips = (0..read_count(reader, height.max(1))?)
+                .map(|_| Ok((read_u32(reader)?, read_u32(reader)?)))
+                .collect::<Result<Vec<_>>>()?;
+            if strips.iter().any(|&(start, end)| start > end || end > height) {
+                bail!("The broker sent a strip that does not fit a {}x{} world", width, height);
+            }
+            let peers = (0..read_count(reader, strips.len())?)
+                .map(|_| Ok(read_string(reader)?.parse::<SocketAddr>()?))
+                .collect::<Result<Vec<_>>>()?;
+            let rows = strips.get(index).map_or(0, |&(start, end)| end - start);
+            let cells = read_cells(reader, width.checked_mul(rows).context("The broker sent too large a strip")?)?;
+            if peers.len() != strips.len() || cells.len() != width * rows {
+                bail!("The broker sent a strip of {} cells that does not fit a {}x{} world", cells.len(), width, height);
+            }
+            Request::Load(Box::new(Load { rule, topology, width, height, index, epoch, timeout, strips, peers, cells }))
+        },
+        STEP => {
+            let mut turns = [0_u8; 4];
+            reader.read_exact(&mut turns)?;
+            Request::Step { turns: u32::from_le_bytes(turns) }
+        },
+        SHUTDOWN => Request::Shutdown,
+        tag => bail!("The broker sent an unknown request {}", tag),
+    }))
+}
+
+pub fn write_reply<W: Write>(writer: &mut W, reply: &Reply) -> Result<()> {
+    match reply {
+        Reply::Stepped(stepped) => {
+            writer.write_all(&[STEPPED])?;
+            writer.write_all(&stepped.population.to_le_bytes())?;
+            writer.write_all(&stepped.halo_bytes.to_le_bytes())?;
+            write_u32(writer, stepped.flips.len())?;
+            for &(cell, state) in &stepped.flips {
+                write_u32(writer, cell.x)?;
+                write_u32(writer, cell.y)?;
+                writer.write_all(&[state.get()])?;
+            }
+        },
+        Reply::Failed(reason) => {
+            writer.write_all(&[FAILED])?;
+            write_string(writer, reason)?;
+        },
+        Reply::Heartbeat => writer.write_all(&[HEARTBEAT])?,
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read the next reply of a worker holding a strip of `cells` cells, which no more than those can have changed in.
+pub fn read_reply<R: Read>(reader: &mut R, cells: usize) -> Result<Reply> {
+    let mut tag = [0_u8];
+    reader.read_exact(&mut tag)?;
+    Ok(match tag[0] {
+        STEPPED => Reply::Stepped(read_stepped(reader, cells)?),
+        FAILED => Reply::Failed(read_string(reader)?),
+        HEARTBEAT => Reply::Heartbeat,
+        tag => bail!("The worker sent an unknown reply {}", tag),
+    })
+}
+
+fn read_stepped<R: Read>(reader: &mut R, cells: usize) -> Result<Stepped> {
+    let mut bytes = [0_u8; 8];
+    reader.read_exact(&mut bytes)?;
+    let population = u64::from_le_bytes(bytes);
+    reader.read_exact(&mut bytes)?;
+    let halo_bytes = u64::from_le_bytes(bytes);
+    let flips = (0..read_count(reader, cells)?)
+        .map(|_| {
+            let cell = CellCoord::new(read_u32(reader)?, read_u32(reader)?);
+            let mut state = [0_u8];
+            reader.read_exact(&mut state)?;
+            Ok((cell, CellState::new(state[0])))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Stepped { population, halo_bytes, flips })
+}
+
+pub fn write_address<W: Write>(writer: &mut W, address: SocketAddr) -> Result<()> {
+    write_string(writer, &address.to_string())?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn read_address<R: Read>(reader: &mut R) -> Result<SocketAddr> {
+    Ok(read_string(reader)?.parse()?)
+}
+
+pub fn write_u32<W: Write>(writer: &mut W, value: usize) -> Result<()> {
+    writer.write_all(&u32::try_from(value)?.to_le_bytes())?;
+    Ok(())
+}
+
+pub fn read_u32<R: Read>(reader: &mut R) -> Result<usize> {
+    let mut bytes = [0_u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes) as usize)
+}
+
+/// Read a `u32` count of the items that follow, refusing more than `max` of them,
+/// so that a bad count cannot make the reader allocate more than the message can hold.
+pub fn read_count<R: Read>(reader: &mut R, max: usize) -> Result<usize> {
+    let count = read_u32(reader)?;
+    if count > max {
+        bail!("Expected at most {} items, got a count of {}", max, count);
+    }
+    Ok(count)
+}
+
+/// Write a cell count and the cells, returning how many bytes that took.
+pub fn write_cells<W: Write>(writer: &mut W, cells: &[CellState]) -> Result<u64> {
+    write_u32(writer, cells.len())?;
+    writer.write_all(bytemuck::cast_slice(cells))?;
+    Ok(4 + cells.len() as u64)
+}
+
+/// Read a cell count and no more than `max` cells. The cells are read as they arrive rather than
+/// into a buffer of the count, so a count that fits a huge world costs only the bytes actually sent.
+pub fn read_cells<R: Read>(reader: &mut R, max: usize) -> Result<Vec<CellState>> {
+    let count = read_count(reader, max)?;
+    let mut bytes = Vec::new();
+    reader.by_ref().take(count as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < count {
+        return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+    }
+    Ok(bytes.into_iter().map(CellState::new).collect())
+}
+
+pub fn write_string<W: Write>(writer: &mut W, string: &str) -> Result<()> {
+    writer.write_all(&u16::try_from(string.len())?.to_le_bytes())?;
+    writer.write_all(string.as_bytes())?;
+    Ok(())
+}
+
+pub fn read_string<R: Read>(reader: &mut R) -> Result<String> {
+    let mut length = [0_u8; 2];
+    reader.read_exact(&mut length)?;
+    let mut string = vec![0_u8; u16::from_le_bytes(length) as usize];
+    reader.read_exact(&mut string)?;
+    Ok(String::from_utf8(string)?)
+}
+
+/// Counted passes reads and writes through, counting the bytes that went past.
+#[derive(Debug)]
+pub struct Counted<T> {
+    inner: T,
+    bytes: u64,
+}
+
+impl<T> Counted<T> {
+    pub fn new(inner: T) -> Self {
+        Counted { inner, bytes: 0 }
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl<T: Read> Read for Counted<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.bytes += read as u64;
+        Ok(read)
+    }
+}
+
+impl<T: Write> Write for Counted<T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
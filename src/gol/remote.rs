@@ -0,0 +1,441 @@
+use crate::gol::{self, event::{Event, State}, Params};
+use crate::gol::protocol::{read_count, read_string, read_u32, write_string, write_u32};
+use crate::gol::rule::Rule;
+use crate::util::cell::{CellCoord, CellState};
+use anyhow::{bail, Context, Result};
+use flume::{Receiver, Sender};
+use sdl2::keyboard::Keycode;
+use std::collections::HashMap;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use tokio::{select, try_join};
+
+const ALIVE_CELLS_COUNT: u8 = 1;
+const IMAGE_OUTPUT_COMPLETE: u8 = 2;
+const STATE_CHANGE: u8 = 3;
+const CELL_FLIPPED: u8 = 4;
+const CELLS_FLIPPED: u8 = 5;
+const CELLS_CHANGED: u8 = 6;
+const TURN_COMPLETE: u8 = 7;
+const STABILISED: u8 = 8;
+const FINAL_TURN_COMPLETE: u8 = 9;
+
+/// Hello is the first thing a served run sends a controller that attaches to it,
+/// with what it needs to open a window for the run. It is encoded as the `u32` width and height,
+/// the rule string and a byte that is 1 for an unbounded world.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hello {
+    pub width: usize,
+    pub height: usize,
+    pub rule: Rule,
+    pub unbounded: bool,
+}
+
+impl Hello {
+    /// The most cells an event of the run can list, which is every cell of a bounded world.
+    /// An unbounded world has no such limit, so its lists only grow as their cells arrive.
+    pub fn max_cells(&self) -> usize {
+        if self.unbounded { usize::MAX } else { self.width.saturating_mul(self.height) }
+    }
+}
+
+/// Controller is whatever shows the events of a run and sends it key presses:
+/// the SDL window or headless logger of this process, or one attached over TCP.
+struct Controller {
+    events: Sender<Event>,
+    key_presses: Receiver<Keycode>,
+}
+
+/// Run the Game of Life like [`gol::run`], except that pressing `Q` detaches the controller
+/// and leaves the run going in the background instead of finishing it. Until the run finishes,
+/// another controller can [`attach`] over `listener` and carry on from the current board.
+/// The controller given by `events` and `key_presses` is attached to start with.
+pub async fn run<P: Into<Params>>(
+    params: P,
+    listener: TcpListener,
+    events: Sender<Event>,
+    key_presses: Receiver<Keycode>,
+) -> Result<()> {
+    let params: Params = params.into();
+    let hello = Hello {
+        width: params.image_width,
+        height: params.image_height,
+        rule: params.rule,
+        unbounded: params.unbounded,
+    };
+    let (gol_events_tx, gol_events_rx) = flume::bounded::<Event>(1000);
+    let (gol_key_presses_tx, gol_key_presses_rx) = flume::bounded::<Keycode>(10);
+    let (attached_tx, attached_rx) = flume::unbounded::<Controller>();
+    let rule = params.rule;
+    std::thread::Builder::new()
+        .name("gol-attach".to_owned())
+        .spawn(move || accept(listener, hello, attached_tx))?;
+    try_join!(
+        gol::run(params, gol_events_tx, gol_key_presses_rx),
+        relay(rule, gol_events_rx, gol_key_presses_tx, Controller { events, key_presses }, attached_rx),
+    )?;
+    Ok(())
+}
+
+/// Attach to a run served with [`run`], returning its [`Hello`], its events and where to send it
+/// key presses. The events start with every cell that is not dead and the turn the run is on,
+/// and close once `Q` is sent to detach again or the run finishes.
+pub fn attach(address: SocketAddr) -> Result<(Hello, Receiver<Event>, Sender<Keycode>)> {
+    let stream = TcpStream::connect(address)
+        .with_context(|| format!("Cannot attach to the run at {}", address))?;
+    stream.set_nodelay(true)?;
+    // Read straight from the stream, so nothing after the hello is buffered away from the events.
+    let hello = read_hello(&mut &stream)?;
+    let (key_presses_tx, key_presses_rx) = flume::bounded::<Keycode>(10);
+    let max_cells = hello.max_cells();
+    let events = bridge(stream, key_presses_rx, write_key, move |reader| read_event(reader, max_cells))?;
+    Ok((hello, events, key_presses_tx))
+}
+
+/// Hand every controller that connects to `listener` over to the relay, until the run finishes.
+fn accept(listener: TcpListener, hello: Hello, attached: Sender<Controller>) {
+    for stream in listener.incoming() {
+        match stream.map_err(Into::into).and_then(|stream| greet(stream, &hello)) {
+            Ok(controller) => if attached.send(controller).is_err() {
+                return;
+            },
+            Err(error) => log::warn!(target: "Main", "Cannot attach a controller: {:#}", error),
+        }
+    }
+}
+
+/// Send a controller that has just connected the hello, and bridge its connection to channels.
+fn greet(stream: TcpStream, hello: &Hello) -> Result<Controller> {
+    log::info!(target: "Main", "Controller attached from {}", stream.peer_addr()?);
+    stream.set_nodelay(true)?;
+    write_hello(&mut &stream, hello)?;
+    let (events_tx, events_rx) = flume::bounded::<Event>(1000);
+    let key_presses = bridge(stream, events_rx, write_event, read_key)?;
+    Ok(Controller { events: events_tx, key_presses })
+}
+
+/// Pass events from the run to whichever controller is attached, and its key presses back.
+/// `Q` detaches the controller rather than reaching the run, as does a controller going away.
+/// The board is followed all along, so that a controller attaching later can be brought up to date.
+async fn relay(
+    rule: Rule,
+    events: Receiver<Event>,
+    key_presses: Sender<Keycode>,
+    local: Controller,
+    attached: Receiver<Controller>,
+) -> Result<()> {
+    let mut board = Board::default();
+    let mut controller = Some(local);
+    loop {
+        select! {
+            event = events.recv_async() => {
+                let Ok(event) = event else {
+                    break;
+                };
+                board.update(&event);
+                if let Some(current) = &controller {
+                    if current.events.send_async(event).await.is_err() {
+                        controller = None;
+                    }
+                }
+            },
+            key = async {
+                match &controller {
+                    Some(current) => current.key_presses.recv_async().await.ok(),
+                    None => std::future::pending().await,
+                }
+            } => match key {
+                Some(Keycode::Q) | None => {
+                    log::info!(target: "Main", "Controller detached at turn {}, the run carries on", board.turn);
+                    controller = None;
+                },
+                Some(key) => {
+                    // The run may have just finished, in which case its last events are still to come.
+                    let _ = key_presses.send_async(key).await;
+                },
+            },
+            Ok(next) = attached.recv_async() => {
+                let mut greeted = true;
+                for event in board.burst(&rule) {
+                    if next.events.send_async(event).await.is_err() {
+                        greeted = false;
+                        break;
+                    }
+                }
+                // Any controller that was attached is detached by dropping it.
+                if greeted {
+                    controller = Some(next);
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Board follows the cells of a run from its events.
+#[derive(Debug, Default)]
+struct Board {
+    cells: HashMap<CellCoord<i64>, CellState>,
+    turn: u32,
+    paused: bool,
+}
+
+impl Board {
+    fn update(&mut self, event: &Event) {
+        self.turn = event.get_completed_turns();
+        match event {
+            &Event::CellFlipped { cell, .. } => self.flip(cell),
+            Event::CellsFlipped { cells, .. } => cells.iter().for_each(|&cell| self.flip(cell)),
+            Event::CellsChanged { cells, .. } =>
+                for &(cell, state) in cells {
+                    if state.is_dead() {
+                        self.cells.remove(&cell);
+                    } else {
+                        self.cells.insert(cell, state);
+                    }
+                },
+            &Event::StateChange { new_state, .. } => self.paused = new_state == State::Pause,
+            _ => (),
+        }
+    }
+
+    fn flip(&mut self, cell: CellCoord<i64>) {
+        if self.cells.remove(&cell).is_none() {
+            self.cells.insert(cell, CellState::ALIVE);
+        }
+    }
+
+    /// The events that bring a controller attaching now up to date, as if it had seen every event so far.
+    fn burst(&self, rule: &Rule) -> Vec<Event> {
+        let completed_turns = self.turn;
+        let mut events = Vec::new();
+        if self.cells.is_empty() {
+            // There is nothing to flip.
+        } else if rule.is_generations() {
+            let cells = self.cells.iter().map(|(&cell, &state)| (cell, state)).collect();
+            events.push(Event::CellsChanged { completed_turns, cells });
+        } else {
+            let cells = self.cells.keys().copied().collect();
+            events.push(Event::CellsFlipped { completed_turns, cells });
+        }
+        events.push(Event::TurnComplete { completed_turns });
+        if self.paused {
+            events.push(Event::StateChange { completed_turns, new_state: State::Pause });
+        }
+        events
+    }
+}
+
+/// Write each of `outgoing` to the stream and read everything that comes back into the returned
+/// receiver, each on a thread of its own. Either side closing closes the connection.
+fn bridge<A: Send + 'static, B: Send + 'static>(
+    stream: TcpStream,
+    outgoing: Receiver<A>,
+    write: fn(&mut BufWriter<TcpStream>, &A) -> Result<()>,
+    mut read: impl FnMut(&mut BufReader<TcpStream>) -> Result<Option<B>> + Send + 'static,
+) -> Result<Receiver<B>> {
+    let (incoming_tx, incoming_rx) = flume::bounded::<B>(1000);
+    let mut writer = BufWriter::new(stream.try_clone()?);
+    let mut reader = BufReader::new(stream);
+    std::thread::Builder::new()
+        .name("gol-remote-writer".to_owned())
+        .spawn(move || {
+            while let Ok(item) = outgoing.recv() {
+                // Flush once nothing else is waiting, rather than after every event of a turn.
+                let written = write(&mut writer, &item)
+                    .and_then(|_| if outgoing.is_empty() { Ok(writer.flush()?) } else { Ok(()) });
+                if written.is_err() {
+                    break;
+                }
+            }
+            let _ = writer.flush();
+            let _ = writer.get_ref().shutdown(Shutdown::Both);
+        })?;
+    std::thread::Builder::new()
+        .name("gol-remote-reader".to_owned())
+        .spawn(move || {
+            loop {
+                match read(&mut reader) {
+                    Ok(Some(item)) => if incoming_tx.send(item).is_err() {
+                        break;
+                    },
+                    Ok(None) => break,
+                    Err(error) => {
+                        log::warn!(target: "Main", "Dropped a remote connection: {:#}", error);
+                        break;
+                    },
+                }
+            }
+            let _ = reader.get_ref().shutdown(Shutdown::Both);
+        })?;
+    Ok(incoming_rx)
+}
+
+pub fn write_hello<W: Write>(writer: &mut W, hello: &Hello) -> Result<()> {
+    write_u32(writer, hello.width)?;
+    write_u32(writer, hello.height)?;
+    write_string(writer, &hello.rule.to_string())?;
+    writer.write_all(&[hello.unbounded as u8])?;
+    Ok(())
+}
+
+pub fn read_hello<R: Read>(reader: &mut R) -> Result<Hello> {
+    let (width, height) = (read_u32(reader)?, read_u32(reader)?);
+    let rule = read_string(reader)?.parse::<Rule>().context("The run sent an invalid rule")?;
+    let mut unbounded = [0_u8];
+    reader.read_exact(&mut unbounded)?;
+    Ok(Hello { width, height, rule, unbounded: unbounded[0] == 1 })
+}
+
+/// Write an event as a tag byte, the `u32` completed turns and the fields of the event.
+/// Cells are an `i64` x and y each, followed by a state byte under `CellsChanged`,
+/// and lists of cells are preceded by their `u32` length.
+///
+/// | Event | Tag | Fields |
+/// | --- | --- | --- |
+/// | `AliveCellsCount` | 1 | `u32` count |
+/// | `ImageOutputComplete` | 2 | filename string |
+/// | `StateChange` | 3 | 0 for executing, 1 for paused or 2 for quitting |
+/// | `CellFlipped` | 4 | cell |
+/// | `CellsFlipped` | 5 | cells |
+/// | `CellsChanged` | 6 | cells with their states |
+/// | `TurnComplete` | 7 | |
+/// | `Stabilised` | 8 | `u32` period |
+/// | `FinalTurnComplete` | 9 | alive cells |
+pub fn write_event<W: Write>(writer: &mut W, event: &Event) -> Result<()> {
+    let tag = match event {
+        Event::AliveCellsCount { .. } => ALIVE_CELLS_COUNT,
+        Event::ImageOutputComplete { .. } => IMAGE_OUTPUT_COMPLETE,
+        Event::StateChange { .. } => STATE_CHANGE,
+        Event::CellFlipped { .. } => CELL_FLIPPED,
+        Event::CellsFlipped { .. } => CELLS_FLIPPED,
+        Event::CellsChanged { .. } => CELLS_CHANGED,
+        Event::TurnComplete { .. } => TURN_COMPLETE,
+        Event::Stabilised { .. } => STABILISED,
+        Event::FinalTurnComplete { .. } => FINAL_TURN_COMPLETE,
+    };
+    writer.write_all(&[tag])?;
+    writer.write_all(&event.get_completed_turns().to_le_bytes())?;
+    match event {
+        Event::AliveCellsCount { cells_count, .. } => writer.write_all(&cells_count.to_le_bytes())?,
+        Event::ImageOutputComplete { filename, .. } => write_string(writer, filename)?,
+        Event::StateChange { new_state, .. } => writer.write_all(&[match new_state {
+            State::Executing => 0,
+            State::Pause => 1,
+            State::Quitting => 2,
+        }])?,
+        &Event::CellFlipped { cell, .. } => write_cell(writer, cell)?,
+        Event::CellsFlipped { cells, .. } | Event::FinalTurnComplete { alive: cells, .. } => {
+            write_u32(writer, cells.len())?;
+            for &cell in cells {
+                write_cell(writer, cell)?;
+            }
+        },
+        Event::CellsChanged { cells, .. } => {
+            write_u32(writer, cells.len())?;
+            for &(cell, state) in cells {
+                write_cell(writer, cell)?;
+                writer.write_all(&[state.get()])?;
+            }
+        },
+        Event::TurnComplete { .. } => (),
+        Event::Stabilised { period, .. } => writer.write_all(&period.to_le_bytes())?,
+    }
+    Ok(())
+}
+
+/// Read the next event, or `None` once the run has closed the connection.
+/// Lists of more than `max_cells` cells are refused, as in [`Hello::max_cells`].
+pub fn read_event<R: Read>(reader: &mut R, max_cells: usize) -> Result<Option<Event>> {
+    let mut tag = [0_u8];
+    match reader.read_exact(&mut tag) {
+        Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    let completed_turns = read_u32(reader)? as u32;
+    Ok(Some(match tag[0] {
+        ALIVE_CELLS_COUNT => Event::AliveCellsCount { completed_turns, cells_count: read_u32(reader)? as u32 },
+        IMAGE_OUTPUT_COMPLETE => Event::ImageOutputComplete { completed_turns, filename: read_string(reader)? },
+        STATE_CHANGE => {
+            let mut state = [0_u8];
+            reader.read_exact(&mut state)?;
+            let new_state = match state[0] {
+                0 => State::Executing,
+                1 => State::Pause,
+                2 => State::Quitting,
+                state => bail!("The run sent an unknown state {}", state),
+            };
+            Event::StateChange { completed_turns, new_state }
+        },
+        CELL_FLIPPED => Event::CellFlipped { completed_turns, cell: read_cell(reader)? },
+        CELLS_FLIPPED => Event::CellsFlipped { completed_turns, cells: read_cells(reader, max_cells)? },
+        CELLS_CHANGED => {
+            let mut cells = Vec::new();
+            for _ in 0..read_count(reader, max_cells)? {
+                let cell = read_cell(reader)?;
+                let mut state = [0_u8];
+                reader.read_exact(&mut state)?;
+                cells.push((cell, CellState::new(state[0])));
+            }
+            Event::CellsChanged { completed_turns, cells }
+        },
+        TURN_COMPLETE => Event::TurnComplete { completed_turns },
+        STABILISED => Event::Stabilised { completed_turns, period: read_u32(reader)? as u32 },
+        FINAL_TURN_COMPLETE => Event::FinalTurnComplete { completed_turns, alive: read_cells(reader, max_cells)? },
+        tag => bail!("The run sent an unknown event {}", tag),
+    }))
+}
+
+/// Write a key press as the byte of its letter. Only the keys a run handles can be sent.
+pub fn write_key<W: Write>(writer: &mut W, key: &Keycode) -> Result<()> {
+    let letter = match *key {
+        Keycode::S => b'S',
+        Keycode::P => b'P',
+        Keycode::Q => b'Q',
+        Keycode::K => b'K',
+        Keycode::B => b'B',
+        key => bail!("The key {:?} cannot be sent to a run", key),
+    };
+    writer.write_all(&[letter])?;
+    Ok(())
+}
+
+/// Read the next key press, or `None` once the controller has closed the connection.
+pub fn read_key<R: Read>(reader: &mut R) -> Result<Option<Keycode>> {
+    let mut letter = [0_u8];
+    match reader.read_exact(&mut letter) {
+        Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    Ok(Some(match letter[0] {
+        b'S' => Keycode::S,
+        b'P' => Keycode::P,
+        b'Q' => Keycode::Q,
+        b'K' => Keycode::K,
+        b'B' => Keycode::B,
+        letter => bail!("The controller sent an unknown key {}", letter),
+    }))
+}
+
+fn write_cell<W: Write>(writer: &mut W, cell: CellCoord<i64>) -> Result<()> {
+    writer.write_all(&cell.x.to_le_bytes())?;
+    writer.write_all(&cell.y.to_le_bytes())?;
+    Ok(())
+}
+
+fn read_cell<R: Read>(reader: &mut R) -> Result<CellCoord<i64>> {
+    let mut bytes = [0_u8; 8];
+    reader.read_exact(&mut bytes)?;
+    let x = i64::from_le_bytes(bytes);
+    reader.read_exact(&mut bytes)?;
+    Ok(CellCoord::new(x, i64::from_le_bytes(bytes)))
+}
+
+/// Read a list of at most `max` cells, growing it only as the cells arrive.
+fn read_cells<R: Read>(reader: &mut R, max: usize) -> Result<Vec<CellCoord<i64>>> {
+    let mut cells = Vec::new();
+    for _ in 0..read_count(reader, max)? {
+        cells.push(read_cell(reader)?);
+    }
+    Ok(cells)
+}
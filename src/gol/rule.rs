@@ -0,0 +1,623 @@
+use crate::gol::lattice::Lattice;
+use crate::util::cell::CellState;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// The largest neighbour count in the Moore neighbourhood.
+const MAX_NEIGHBOURS: u8 = 8;
+
+/// The largest range of a Larger than Life neighbourhood.
+pub const MAX_RANGE: u8 = 10;
+
+/// The largest neighbour count of any neighbourhood, a range 10 Moore square with its middle cell.
+const MAX_COUNT: usize = (2 * MAX_RANGE as usize + 1) * (2 * MAX_RANGE as usize + 1);
+
+/// The bit of a 3x3 neighbourhood index that holds the cell itself.
+const CENTRE: u16 = 1 << 4;
+
+/// The bit of a 3x3 neighbourhood index holding each neighbour, clockwise from north.
+const RING: [u16; 8] = [1 << 1, 1 << 2, 1 << 5, 1 << 8, 1 << 7, 1 << 6, 1 << 3, 1 << 0];
+
+/// Hensel letters for 1 to 4 neighbours in canonical order, each with one of its neighbour
+/// configurations as a set of `RING` positions: bit 0 is north, bit 1 north-east and so on clockwise.
+/// A letter for 5 to 7 neighbours is the complement of the same letter for 8 minus as many.
+const HENSEL: [&[(char, u8)]; 4] = [
+    &[('c', 0x02), ('e', 0x01)],
+    &[('c', 0x0A), ('e', 0x05), ('k', 0x09), ('a', 0x03), ('i', 0x11), ('n', 0x22)],
+    &[
+        ('c', 0x2A), ('e', 0x15), ('k', 0x25), ('a', 0x07), ('i', 0x83),
+        ('n', 0x0B), ('y', 0x29), ('q', 0x23), ('j', 0x43), ('r', 0x13),
+    ],
+    &[
+        ('c', 0xAA), ('e', 0x55), ('k', 0x4B), ('a', 0x0F), ('i', 0x1B), ('n', 0x8B), ('y', 0x2B),
+        ('q', 0x27), ('j', 0x53), ('r', 0x17), ('t', 0x93), ('w', 0x63), ('z', 0x33),
+    ],
+];
+
+/// The Hensel letters for `n` neighbours with one configuration of each.
+/// 0 and 8 neighbours have a single configuration and no letter.
+fn hensel_letters(n: u8) -> Vec<(Option<char>, u8)> {
+    match n {
+        0 => vec![(None, 0x00)],
+        1..=4 => HENSEL[n as usize - 1].iter().map(|&(letter, ring)| (Some(letter), ring)).collect(),
+        5..=7 => HENSEL[7 - n as usize].iter().map(|&(letter, ring)| (Some(letter), !ring)).collect(),
+        _ => vec![(None, 0xFF)],
+    }
+}
+
+/// The 3x3 neighbourhood indices of every rotation and reflection of a set of `RING` positions.
+fn symmetries(ring: u8) -> impl Iterator<Item = u16> {
+    let reflected = (0..8).filter(|i| ring >> i & 1 == 1).fold(0_u8, |mirror, i| mirror | 1 << ((8 - i) % 8));
+    [ring, reflected].into_iter()
+        .flat_map(|ring| (0..4).map(move |quarter| ring.rotate_left(2 * quarter)))
+        .map(|ring| (0..8).filter(|i| ring >> i & 1 == 1).fold(0, |index, i| index | RING[i]))
+}
+
+/// `Neighbourhood` is the shape of the cells counted around each cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Neighbourhood {
+    /// The square of cells within `range` in both directions.
+    Moore,
+    /// The diamond of cells within a Manhattan distance of `range`.
+    VonNeumann,
+}
+
+/// Counts is the set of neighbour counts from 0 to `MAX_COUNT` that cause a birth or allow survival.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Counts([u64; MAX_COUNT / 64 + 1]);
+
+impl Counts {
+    const EMPTY: Counts = Counts([0; MAX_COUNT / 64 + 1]);
+
+    fn contains(&self, n: u16) -> bool {
+        let n = n as usize;
+        n <= MAX_COUNT && self.0[n / 64] >> (n % 64) & 1 == 1
+    }
+
+    fn insert(&mut self, n: u16) {
+        self.0[n as usize / 64] |= 1 << (n % 64);
+    }
+
+    fn from_interval(min: u16, max: u16) -> Self {
+        let mut counts = Counts::EMPTY;
+        (min..=max).for_each(|n| counts.insert(n));
+        counts
+    }
+
+    /// The smallest and largest counts in the set.
+    fn bounds(&self) -> Option<(u16, u16)> {
+        let mut counts = (0..=MAX_COUNT as u16).filter(|&n| self.contains(n));
+        let min = counts.next()?;
+        Some((min, counts.next_back().unwrap_or(min)))
+    }
+}
+
+/// Transitions is a 512-entry table of whether a cell is alive in the next turn, indexed by its
+/// 3x3 neighbourhood: bit `3 * (dy + 1) + (dx + 1)` of the index is the cell at `(dx, dy)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Transitions([u64; 8]);
+
+impl Transitions {
+    const EMPTY: Transitions = Transitions([0; 8]);
+
+    fn contains(&self, neighbourhood: u16) -> bool {
+        self.0[neighbourhood as usize / 64] >> (neighbourhood % 64) & 1 == 1
+    }
+
+    fn insert(&mut self, neighbourhood: u16) {
+        self.0[neighbourhood as usize / 64] |= 1 << (neighbourhood % 64);
+    }
+}
+
+/// Rule is a Life-like (outer totalistic) rule: a dead cell is born, or an alive cell
+/// survives, depending only on how many of its 8 neighbours are alive.
+/// Generations rules add refractory states: an alive cell that does not survive
+/// decays through them one turn at a time before it is dead and can be born again.
+/// Isotropic non-totalistic rules also depend on the shape of the neighbours, given by Hensel letters,
+/// and are stored as a lookup table indexed by the 3x3 neighbourhood.
+/// Larger than Life rules count the alive cells within a range of up to 10 instead,
+/// optionally including the middle cell, and give births and survival as intervals.
+/// Totalistic rules can also run on hexagons or triangles, see [`Lattice`].
+/// ## Examples
+/// ``` ignore
+/// let highlife: Rule = "B36/S23".parse()?;
+/// assert!(highlife.next(false, 6));
+/// assert_eq!(highlife, "23/36".parse()?);
+/// let brians_brain: Rule = "B2/S/C3".parse()?;
+/// assert_eq!(brians_brain.states(), 3);
+/// let rule: Rule = "B2-a/S12".parse()?;
+/// assert!(!rule.transition(0b000_000_011));
+/// let bosco: Rule = "R5,C0,M1,S34..58,B34..45,NM".parse()?;
+/// assert_eq!(bosco.range(), 5);
+/// let hexagonal: Rule = "B2/S34H".parse()?;
+/// assert_eq!(hexagonal.max_neighbours(), 6);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rule {
+    birth: Counts,
+    survival: Counts,
+    states: u8,
+    range: u8,
+    neighbourhood: Neighbourhood,
+    middle: bool,
+    lattice: Lattice,
+    transitions: Option<Transitions>,
+}
+
+impl Rule {
+    /// Conway's Game of Life, B3/S23.
+    pub const CONWAY: Rule = Rule {
+        birth: Counts([1 << 3, 0, 0, 0, 0, 0, 0]),
+        survival: Counts([1 << 2 | 1 << 3, 0, 0, 0, 0, 0, 0]),
+        states: 2,
+        range: 1,
+        neighbourhood: Neighbourhood::Moore,
+        middle: false,
+        lattice: Lattice::Square,
+        transitions: None,
+    };
+
+    /// Create a rule from the neighbour counts that cause a birth and allow survival.
+    /// Counts above 8 are ignored.
+    pub fn new(birth: &[u8], survival: &[u8]) -> Self {
+        let counts = |counts: &[u8]| counts.iter()
+            .filter(|&&n| n <= MAX_NEIGHBOURS)
+            .fold(Counts::EMPTY, |mut set, &n| {
+                set.insert(n as u16);
+                set
+            });
+        Rule { birth: counts(birth), survival: counts(survival), ..Rule::CONWAY }
+    }
+
+    /// Create a rule from its 512-entry table, keeping only the neighbour counts when the table is totalistic.
+    fn from_transitions(transitions: Transitions) -> Self {
+        // Whether every, and whether any, neighbourhood with `n` neighbours is alive next, by centre cell.
+        let mut every = [[true; 9]; 2];
+        let mut any = [[false; 9]; 2];
+        for neighbourhood in 0..512 {
+            let centre = (neighbourhood & CENTRE != 0) as usize;
+            let n = (neighbourhood & !CENTRE).count_ones() as usize;
+            every[centre][n] &= transitions.contains(neighbourhood);
+            any[centre][n] |= transitions.contains(neighbourhood);
+        }
+        let counts = |every: [bool; 9]| (0..=MAX_NEIGHBOURS)
+            .filter(|&n| every[n as usize])
+            .collect::<Vec<_>>();
+        let rule = Rule::new(&counts(every[0]), &counts(every[1]));
+        Rule { transitions: (every != any).then_some(transitions), ..rule }
+    }
+
+    /// The same rule with `states` cell states in total, including dead and alive.
+    /// More than 2 states makes it a Generations rule.
+    pub fn with_states(self, states: u8) -> Self {
+        Rule { states: states.max(2), ..self }
+    }
+
+    /// The number of cell states, 2 for Life-like rules.
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    /// Whether the rule has refractory states.
+    pub fn is_generations(&self) -> bool {
+        self.states > 2
+    }
+
+    /// How far away the counted cells can be, 1 for Life-like rules.
+    pub fn range(&self) -> u8 {
+        self.range
+    }
+
+    pub fn neighbourhood(&self) -> Neighbourhood {
+        self.neighbourhood
+    }
+
+    /// Whether a cell counts itself as one of its neighbours.
+    pub fn middle(&self) -> bool {
+        self.middle
+    }
+
+    /// The shape of the cells the rule runs on.
+    pub fn lattice(&self) -> Lattice {
+        self.lattice
+    }
+
+    /// How many cells away horizontally or vertically a counted cell can be.
+    pub fn reach(&self) -> usize {
+        self.lattice.reach().max(self.range as usize)
+    }
+
+    /// Whether the rule counts anything other than the 8 cells around each square cell.
+    pub fn is_larger_than_life(&self) -> bool {
+        self.range > 1 || self.neighbourhood != Neighbourhood::Moore || self.middle
+    }
+
+    /// Whether the next state depends only on the number of alive neighbours, not their positions.
+    pub fn is_totalistic(&self) -> bool {
+        self.transitions.is_none()
+    }
+
+    /// Whether the rule is a plain two-state rule over the 8 cells around each square cell.
+    pub fn is_life_like(&self) -> bool {
+        !self.is_generations() && !self.is_larger_than_life() && self.lattice == Lattice::Square
+    }
+
+    /// The largest neighbour count of the neighbourhood.
+    pub fn max_neighbours(&self) -> u16 {
+        if self.lattice != Lattice::Square {
+            return self.lattice.neighbours();
+        }
+        let range = self.range as u16;
+        let cells = match self.neighbourhood {
+            Neighbourhood::Moore => (2 * range + 1) * (2 * range + 1),
+            Neighbourhood::VonNeumann => 2 * range * (range + 1) + 1,
+        };
+        if self.middle { cells } else { cells - 1 }
+    }
+
+    /// Whether a cell with `neighbours` alive neighbours is alive in the next turn.
+    pub fn next(&self, alive: bool, neighbours: u16) -> bool {
+        if alive { self.survives(neighbours) } else { self.births(neighbours) }
+    }
+
+    /// Whether a cell is alive in the next turn, given the 3x3 `neighbourhood` of alive cells around it,
+    /// with bit `3 * (dy + 1) + (dx + 1)` set for an alive cell at `(dx, dy)` and bit 4 for the cell itself.
+    pub fn transition(&self, neighbourhood: u16) -> bool {
+        match &self.transitions {
+            Some(transitions) => transitions.contains(neighbourhood),
+            None => self.next(neighbourhood & CENTRE != 0, (neighbourhood & !CENTRE).count_ones() as u16),
+        }
+    }
+
+    /// The next state of a cell with `neighbours` alive neighbours.
+    pub fn next_state(&self, state: CellState, neighbours: u16) -> CellState {
+        match state {
+            CellState::DEAD if self.births(neighbours) => CellState::ALIVE,
+            CellState::DEAD => CellState::DEAD,
+            CellState::ALIVE if self.survives(neighbours) => CellState::ALIVE,
+            _ => self.decay(state),
+        }
+    }
+
+    /// The next state of a cell with the 3x3 `neighbourhood` of alive cells, see [`Rule::transition`].
+    /// The bit for the cell itself is ignored in favour of `state`.
+    pub fn next_state_isotropic(&self, state: CellState, neighbourhood: u16) -> CellState {
+        match state {
+            CellState::DEAD if self.transition(neighbourhood & !CENTRE) => CellState::ALIVE,
+            CellState::DEAD => CellState::DEAD,
+            CellState::ALIVE if self.transition(neighbourhood | CENTRE) => CellState::ALIVE,
+            _ => self.decay(state),
+        }
+    }
+
+    /// The state a cell was in one turn before it changed to `state`. Cells are only born from dead,
+    /// decay one state at a time and die from the last state, so there is only one such state.
+    /// ## Examples
+    /// ``` ignore
+    /// let rule: Rule = "B2/S/C3".parse()?;
+    /// assert_eq!(rule.previous_state(CellState::ALIVE), CellState::DEAD);
+    /// assert_eq!(rule.previous_state(CellState::new(2)), CellState::ALIVE);
+    /// assert_eq!(rule.previous_state(CellState::DEAD), CellState::new(2));
+    /// ```
+    pub fn previous_state(&self, state: CellState) -> CellState {
+        match state {
+            CellState::DEAD => CellState::new(self.states - 1),
+            CellState::ALIVE => CellState::DEAD,
+            state => CellState::new(state.get() - 1),
+        }
+    }
+
+    /// The state after a non-dead cell that does not survive, passing through any refractory states.
+    fn decay(&self, state: CellState) -> CellState {
+        if state.get() + 1 < self.states { CellState::new(state.get() + 1) } else { CellState::DEAD }
+    }
+
+    /// Whether a dead cell with `neighbours` alive neighbours is born.
+    /// For isotropic non-totalistic rules, whether it is born for every arrangement of them.
+    pub fn births(&self, neighbours: u16) -> bool {
+        self.birth.contains(neighbours)
+    }
+
+    /// Whether an alive cell with `neighbours` alive neighbours survives.
+    /// For isotropic non-totalistic rules, whether it survives every arrangement of them.
+    pub fn survives(&self, neighbours: u16) -> bool {
+        self.survival.contains(neighbours)
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Rule::CONWAY
+    }
+}
+
+impl Display for Rule {
+    /// Format the rule in B/S notation, for example `B36/S23`, `B2-a/S12` with Hensel letters,
+    /// or `B2/S/C3` with refractory states.
+    /// Larger than Life rules use Golly's notation, for example `R5,C0,M1,S34..58,B34..45,NM`.
+    /// Rules on other lattices end with the lattice letter, for example `B2/S34H` or `B45/S34abL`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.lattice != Lattice::Square {
+            let counts = |counts: Counts| (0..=self.lattice.neighbours())
+                .filter(|&n| counts.contains(n))
+                .map(|n| format!("{:x}", n))
+                .collect::<String>();
+            write!(f, "B{}/S{}", counts(self.birth), counts(self.survival))?;
+            if self.is_generations() {
+                write!(f, "/C{}", self.states)?;
+            }
+            return write!(f, "{}", self.lattice.suffix());
+        }
+        if self.is_larger_than_life() {
+            let interval = |counts: Counts| counts.bounds()
+                .map_or(String::new(), |(min, max)| format!("{}..{}", min, max));
+            return write!(
+                f, "R{},C{},M{},S{},B{},N{}",
+                self.range,
+                if self.is_generations() { self.states } else { 0 },
+                self.middle as u8,
+                interval(self.survival),
+                interval(self.birth),
+                match self.neighbourhood { Neighbourhood::Moore => 'M', Neighbourhood::VonNeumann => 'N' },
+            );
+        }
+        // Each count is written alone when every configuration applies, otherwise with
+        // whichever of its letters, or the letters it excludes after a `-`, is shorter.
+        let hensel = |centre: u16| (0..=MAX_NEIGHBOURS)
+            .map(|n| {
+                let (present, absent): (Vec<_>, Vec<_>) = hensel_letters(n).into_iter()
+                    .partition(|&(_, ring)| self.transition(symmetries(ring).next().unwrap_or(0) | centre));
+                let letters = |letters: Vec<(Option<char>, u8)>| letters.into_iter()
+                    .filter_map(|(letter, _)| letter)
+                    .collect::<String>();
+                match (present.len(), absent.len()) {
+                    (0, _) => String::new(),
+                    (_, 0) => n.to_string(),
+                    (p, a) if p <= a => format!("{}{}", n, letters(present)),
+                    _ => format!("{}-{}", n, letters(absent)),
+                }
+            })
+            .collect::<String>();
+        write!(f, "B{}/S{}", hensel(0), hensel(CENTRE))?;
+        if self.is_generations() {
+            write!(f, "/C{}", self.states)?;
+        }
+        Ok(())
+    }
+}
+
+/// ParseRuleError describes why a rulestring could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseRuleError {
+    /// The rulestring was empty.
+    Empty,
+    /// The rulestring does not have exactly one `/` between its two parts.
+    MissingSeparator(String),
+    /// Both parts of a B/S rulestring are for births, or both are for survival.
+    DuplicatePart(String),
+    /// Only one of the two parts has a `B` or `S` prefix.
+    MixedNotation(String),
+    /// A character that is not a neighbour count from 0 to 8.
+    InvalidCount(char),
+    /// A character that is not a neighbour count on a hexagonal or triangular lattice.
+    InvalidLatticeCount(Lattice, char),
+    /// A letter that is not a Hensel letter for the neighbour count before it.
+    InvalidLetter(u8, char),
+    /// The same neighbour count appears twice in one part.
+    RepeatedCount(u8),
+    /// The Generations state count is not a number from 2 to 255.
+    InvalidStates(String),
+    /// A Larger than Life rulestring is missing a part or has one it does not expect.
+    InvalidLargerThanLife(String),
+    /// The Larger than Life range is not a number from 1 to 10.
+    InvalidRange(String),
+    /// A Larger than Life interval is empty or counts more cells than the neighbourhood has.
+    InvalidInterval(String),
+}
+
+impl Display for ParseRuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseRuleError::Empty =>
+                write!(f, "The rulestring is empty"),
+            ParseRuleError::MissingSeparator(rule) =>
+                write!(f, "Expected a rulestring of the form B3/S23, 23/3 or B2/S/C3, got {:?}", rule),
+            ParseRuleError::DuplicatePart(part) =>
+                write!(f, "The rulestring has two {:?} parts", part),
+            ParseRuleError::MixedNotation(rule) =>
+                write!(f, "Either both or neither parts of {:?} should have a B or S prefix", rule),
+            ParseRuleError::InvalidCount(c) =>
+                write!(f, "{:?} is not a neighbour count from 0 to {}", c, MAX_NEIGHBOURS),
+            ParseRuleError::InvalidLatticeCount(lattice, c) =>
+                write!(f, "{:?} is not a neighbour count from 0 to {:x} on a {} lattice", c, lattice.neighbours(), lattice),
+            ParseRuleError::InvalidLetter(n, letter) =>
+                write!(f, "{:?} is not a Hensel letter for {} neighbours", letter, n),
+            ParseRuleError::RepeatedCount(n) =>
+                write!(f, "The neighbour count {} appears more than once", n),
+            ParseRuleError::InvalidStates(states) =>
+                write!(f, "{:?} is not a number of states from 2 to 255", states),
+            ParseRuleError::InvalidLargerThanLife(part) =>
+                write!(f, "Expected a rulestring of the form R5,C0,M1,S34..58,B34..45,NM, got the part {:?}", part),
+            ParseRuleError::InvalidRange(range) =>
+                write!(f, "{:?} is not a range from 1 to {}", range, MAX_RANGE),
+            ParseRuleError::InvalidInterval(interval) =>
+                write!(f, "{:?} is not an interval of neighbour counts within the neighbourhood", interval),
+        }
+    }
+}
+
+impl std::error::Error for ParseRuleError {}
+
+impl FromStr for Rule {
+    type Err = ParseRuleError;
+
+    /// Parse `B3/S23` (in either order, any case), or the older `S/B` form `23/3`.
+    /// Each count may be followed by Hensel letters, or a `-` and the letters to leave out, as in `B2-a/S12`.
+    /// Generations rules add a state count as a third part, as in `B2/S/C3` or `345/2/4`.
+    /// Larger than Life rules use Golly's `R5,C0,M1,S34..58,B34..45,NM` notation.
+    /// Rules for hexagons end in `H` and rules for triangles in `L`, as in `B2/S34H`,
+    /// with the triangular counts 10 to 12 written as `a` to `c`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ParseRuleError::Empty);
+        }
+        if s.contains(',') {
+            return parse_larger_than_life(s);
+        }
+        let (s, lattice) = match s.as_bytes()[s.len() - 1] {
+            b'H' | b'h' => (&s[..s.len() - 1], Lattice::Hexagonal),
+            b'L' | b'l' => (&s[..s.len() - 1], Lattice::Triangular),
+            _ => (s, Lattice::Square),
+        };
+        let parts = s.split('/').collect::<Vec<_>>();
+        let (first, second, states) = match parts[..] {
+            [first, second] => (first, second, None),
+            [first, second, states] => (first, second, Some(states)),
+            _ => return Err(ParseRuleError::MissingSeparator(s.to_string())),
+        };
+        let states = states.map(parse_states).transpose()?.unwrap_or(2);
+
+        let (birth, survival) = match (prefix(first), prefix(second)) {
+            (None, None) => (second, first),
+            (Some('b'), Some('s')) => (&first[1..], &second[1..]),
+            (Some('s'), Some('b')) => (&second[1..], &first[1..]),
+            (Some(part), Some(_)) => return Err(ParseRuleError::DuplicatePart(part.to_string())),
+            _ => return Err(ParseRuleError::MixedNotation(s.to_string())),
+        };
+        if lattice != Lattice::Square {
+            let rule = Rule {
+                birth: parse_lattice_counts(birth, lattice)?,
+                survival: parse_lattice_counts(survival, lattice)?,
+                lattice,
+                ..Rule::CONWAY
+            };
+            return Ok(rule.with_states(states));
+        }
+        let mut transitions = Transitions::EMPTY;
+        parse_counts(birth, 0, &mut transitions)?;
+        parse_counts(survival, CENTRE, &mut transitions)?;
+        Ok(Rule::from_transitions(transitions).with_states(states))
+    }
+}
+
+/// Parse a Larger than Life rulestring such as `R5,C0,M1,S34..58,B34..45,NM`.
+/// The parts must come in this order, and the neighbourhood part may be left out for `NM`.
+fn parse_larger_than_life(s: &str) -> Result<Rule, ParseRuleError> {
+    let mut parts = s.split(',');
+    let mut part = |prefix: char| {
+        let part = parts.next().ok_or_else(|| ParseRuleError::InvalidLargerThanLife(prefix.to_string()))?;
+        part.strip_prefix([prefix, prefix.to_ascii_lowercase()])
+            .ok_or_else(|| ParseRuleError::InvalidLargerThanLife(part.to_string()))
+    };
+
+    let range = part('R')?;
+    let range = range.parse::<u8>().ok()
+        .filter(|range| (1..=MAX_RANGE).contains(range))
+        .ok_or_else(|| ParseRuleError::InvalidRange(range.to_string()))?;
+    // Golly writes two-state rules as C0, and treats C1 the same way.
+    let states = match part('C')? {
+        "0" | "1" => 2,
+        states => parse_states(states)?,
+    };
+    let middle = match part('M')? {
+        "0" => false,
+        "1" => true,
+        middle => return Err(ParseRuleError::InvalidLargerThanLife(format!("M{}", middle))),
+    };
+    let survival = part('S')?;
+    let birth = part('B')?;
+    let neighbourhood = match parts.next() {
+        None => Neighbourhood::Moore,
+        Some(part) if part.eq_ignore_ascii_case("NM") => Neighbourhood::Moore,
+        Some(part) if part.eq_ignore_ascii_case("NN") => Neighbourhood::VonNeumann,
+        Some(part) => return Err(ParseRuleError::InvalidLargerThanLife(part.to_string())),
+    };
+    if let Some(extra) = parts.next() {
+        return Err(ParseRuleError::InvalidLargerThanLife(extra.to_string()));
+    }
+
+    let rule = Rule { range, neighbourhood, middle, states, ..Rule::CONWAY };
+    Ok(Rule {
+        birth: parse_interval(birth, rule.max_neighbours())?,
+        survival: parse_interval(survival, rule.max_neighbours())?,
+        ..rule
+    })
+}
+
+/// Parse an interval of neighbour counts such as `34..58`, up to `max_neighbours`.
+fn parse_interval(part: &str, max_neighbours: u16) -> Result<Counts, ParseRuleError> {
+    part.split_once("..")
+        .and_then(|(min, max)| Some((min.parse::<u16>().ok()?, max.parse::<u16>().ok()?)))
+        .filter(|&(min, max)| min <= max && max <= max_neighbours)
+        .map(|(min, max)| Counts::from_interval(min, max))
+        .ok_or_else(|| ParseRuleError::InvalidInterval(part.to_string()))
+}
+
+/// Parse the neighbour counts of one part of a rulestring for hexagons or triangles,
+/// written as hexadecimal digits up to the number of neighbours.
+fn parse_lattice_counts(part: &str, lattice: Lattice) -> Result<Counts, ParseRuleError> {
+    let mut counts = Counts::EMPTY;
+    for c in part.chars() {
+        let n = c.to_digit(16)
+            .filter(|&n| n <= lattice.neighbours() as u32)
+            .ok_or(ParseRuleError::InvalidLatticeCount(lattice, c))? as u16;
+        if counts.contains(n) {
+            return Err(ParseRuleError::RepeatedCount(n as u8));
+        }
+        counts.insert(n);
+    }
+    Ok(counts)
+}
+
+/// Parse a Generations state count such as `C3` or `3`.
+fn parse_states(part: &str) -> Result<u8, ParseRuleError> {
+    let digits = part.strip_prefix(['C', 'c']).unwrap_or(part);
+    digits.parse::<u8>().ok()
+        .filter(|&states| states >= 2)
+        .ok_or_else(|| ParseRuleError::InvalidStates(part.to_string()))
+}
+
+/// The lowercase `b` or `s` a rulestring part starts with, if any.
+fn prefix(part: &str) -> Option<char> {
+    part.chars().next()
+        .map(|c| c.to_ascii_lowercase())
+        .filter(|&c| c == 'b' || c == 's')
+}
+
+/// Parse the neighbour counts and Hensel letters of one part of a rulestring into `transitions`,
+/// for neighbourhoods with the given `centre` bit.
+fn parse_counts(part: &str, centre: u16, transitions: &mut Transitions) -> Result<(), ParseRuleError> {
+    let mut seen = 0_u16;
+    let mut chars = part.chars().peekable();
+    while let Some(c) = chars.next() {
+        let n = c.to_digit(10)
+            .filter(|&n| n <= MAX_NEIGHBOURS as u32)
+            .ok_or(ParseRuleError::InvalidCount(c))? as u8;
+        if seen >> n & 1 == 1 {
+            return Err(ParseRuleError::RepeatedCount(n));
+        }
+        seen |= 1 << n;
+
+        let excluded = chars.next_if_eq(&'-').is_some();
+        let mut letters = String::new();
+        while let Some(letter) = chars.next_if(char::is_ascii_alphabetic) {
+            letters.push(letter);
+        }
+        let configurations = hensel_letters(n);
+        if let Some(letter) = letters.chars()
+            .find(|&letter| !configurations.iter().any(|&(known, _)| known == Some(letter))) {
+            return Err(ParseRuleError::InvalidLetter(n, letter));
+        }
+        if excluded && letters.is_empty() {
+            return Err(ParseRuleError::InvalidLetter(n, '-'));
+        }
+        for (letter, ring) in configurations {
+            let listed = letter.is_some_and(|letter| letters.contains(letter));
+            if letters.is_empty() || listed != excluded {
+                symmetries(ring).for_each(|neighbourhood| transitions.insert(neighbourhood | centre));
+            }
+        }
+    }
+    Ok(())
+}
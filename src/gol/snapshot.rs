@@ -0,0 +1,188 @@
+use crate::gol::rule::Rule;
+use crate::gol::topology::Topology;
+use crate::gol::Params;
+use crate::util::cell::{CellCoord, CellState};
+use anyhow::{bail, Context, Result};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use std::io::{Read, Write};
+
+/// The first bytes of every snapshot.
+pub const MAGIC: &[u8; 8] = b"GOLSNAP\n";
+
+/// The version of the snapshot layout written by this build. Older versions are read as long as
+/// the layout is still understood, and newer ones are rejected rather than misread.
+pub const VERSION: u16 = 1;
+
+/// SnapshotHeader is everything about a run that a snapshot records besides its cells.
+///
+/// A snapshot is laid out as follows, with every integer little endian and every
+/// string a `u16` byte length followed by UTF-8:
+///
+/// | Field | Encoding |
+/// | --- | --- |
+/// | magic | the 8 bytes of [`MAGIC`] |
+/// | version | `u16`, see [`VERSION`] |
+/// | turn | `u32` |
+/// | image width, image height | `u64` each |
+/// | rule | string, as printed by [`Rule`] |
+/// | topology | string, as printed by [`Topology`] |
+/// | unbounded | `u8`, 0 or 1 |
+/// | origin x, origin y | `i64` each |
+/// | width, height | `u64` each |
+/// | payload length | `u64` |
+/// | payload | zlib compressed state index of each of the `width` by `height` cells, row by row |
+///
+/// The cells are the whole world the engine steps, which for an unbounded world may have grown
+/// past the image and has its top left cell at `origin` in world coordinates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotHeader {
+    pub turn: u32,
+    pub image_width: usize,
+    pub image_height: usize,
+    pub rule: Rule,
+    pub topology: Topology,
+    pub unbounded: bool,
+    pub origin: CellCoord<i64>,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl SnapshotHeader {
+    /// Describe the world of a run with `params` after `turn` turns.
+    pub fn new(params: &Params, turn: u32, origin: CellCoord<i64>, width: usize, height: usize) -> Self {
+        SnapshotHeader {
+            turn,
+            image_width: params.image_width,
+            image_height: params.image_height,
+            rule: params.rule,
+            topology: params.topology,
+            unbounded: params.unbounded,
+            origin,
+            width,
+            height,
+        }
+    }
+
+    /// Check that a run with `params` can carry on from this snapshot and end as the run that took it would.
+    pub fn check(&self, params: &Params) -> Result<()> {
+        if (self.image_width, self.image_height) != (params.image_width, params.image_height) {
+            bail!(
+                "The snapshot is of a {}x{} image but this run is of a {}x{} image",
+                self.image_width, self.image_height, params.image_width, params.image_height
+            );
+        }
+        if self.rule != params.rule {
+            bail!("The snapshot was taken under the rule {} but this run uses the rule {}", self.rule, params.rule);
+        }
+        if self.topology != params.topology {
+            bail!("The snapshot was taken on the {} topology but this run uses the {} topology", self.topology, params.topology);
+        }
+        if self.unbounded != params.unbounded {
+            bail!("The snapshot was taken with unbounded {} but this run has unbounded {}", self.unbounded, params.unbounded);
+        }
+        if self.turn as usize > params.turns {
+            bail!("The snapshot was taken after {} turns, past the {} turns of this run", self.turn, params.turns);
+        }
+        Ok(())
+    }
+}
+
+/// Encode a snapshot of `cells`, which are `header.width` by `header.height` in row-major order.
+pub fn encode(header: &SnapshotHeader, cells: &[CellState]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytemuck::cast_slice(cells))?;
+    let payload = encoder.finish()?;
+
+    let mut bytes = Vec::with_capacity(payload.len() + 128);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&header.turn.to_le_bytes());
+    bytes.extend_from_slice(&(header.image_width as u64).to_le_bytes());
+    bytes.extend_from_slice(&(header.image_height as u64).to_le_bytes());
+    write_string(&mut bytes, &header.rule.to_string());
+    write_string(&mut bytes, &header.topology.to_string());
+    bytes.push(header.unbounded as u8);
+    bytes.extend_from_slice(&header.origin.x.to_le_bytes());
+    bytes.extend_from_slice(&header.origin.y.to_le_bytes());
+    bytes.extend_from_slice(&(header.width as u64).to_le_bytes());
+    bytes.extend_from_slice(&(header.height as u64).to_le_bytes());
+    bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    Ok(bytes)
+}
+
+/// Decode a snapshot into its header and row-major cells.
+pub fn decode(bytes: &[u8]) -> Result<(SnapshotHeader, Vec<CellState>)> {
+    let mut reader = Reader { bytes };
+    if reader.take(MAGIC.len())? != MAGIC {
+        bail!("This is not a snapshot, it does not start with {:?}", String::from_utf8_lossy(MAGIC));
+    }
+    let version = u16::from_le_bytes(reader.array()?);
+    if version == 0 || version > VERSION {
+        bail!("The snapshot is version {} but only versions 1 to {} can be read", version, VERSION);
+    }
+    let turn = u32::from_le_bytes(reader.array()?);
+    let image_width = reader.usize()?;
+    let image_height = reader.usize()?;
+    let rule = reader.string()?.parse::<Rule>().context("The snapshot has an invalid rule")?;
+    let topology = reader.string()?.parse::<Topology>().context("The snapshot has an invalid topology")?;
+    let unbounded = match reader.take(1)?[0] {
+        0 => false,
+        1 => true,
+        byte => bail!("The snapshot has an invalid unbounded flag {}", byte),
+    };
+    let origin = CellCoord::new(i64::from_le_bytes(reader.array()?), i64::from_le_bytes(reader.array()?));
+    let width = reader.usize()?;
+    let height = reader.usize()?;
+    let length = reader.usize()?;
+    let payload = reader.take(length)?;
+    if !reader.bytes.is_empty() {
+        bail!("The snapshot has {} bytes after its payload", reader.bytes.len());
+    }
+
+    let cells = width.checked_mul(height).context("The snapshot is too large")?;
+    let mut states = Vec::new();
+    ZlibDecoder::new(payload).read_to_end(&mut states).context("The snapshot payload is corrupt")?;
+    if states.len() != cells {
+        bail!("The snapshot payload has {} cells but should have {}x{}", states.len(), width, height);
+    }
+    if let Some(&state) = states.iter().find(|&&state| state >= rule.states()) {
+        bail!("The snapshot has a cell in state {} but the rule {} only has {} states", state, rule, rule.states());
+    }
+    let header = SnapshotHeader { turn, image_width, image_height, rule, topology, unbounded, origin, width, height };
+    Ok((header, states.into_iter().map(CellState::new).collect()))
+}
+
+fn write_string(bytes: &mut Vec<u8>, string: &str) {
+    bytes.extend_from_slice(&(string.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(string.as_bytes());
+}
+
+/// Reader takes fields off the front of a snapshot, failing on any that are cut short.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8]> {
+        if length > self.bytes.len() {
+            bail!("The snapshot is truncated");
+        }
+        let (field, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(field)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into()?)
+    }
+
+    fn usize(&mut self) -> Result<usize> {
+        Ok(u64::from_le_bytes(self.array()?).try_into()?)
+    }
+
+    fn string(&mut self) -> Result<&'a str> {
+        let length = u16::from_le_bytes(self.array()?) as usize;
+        Ok(std::str::from_utf8(self.take(length)?)?)
+    }
+}
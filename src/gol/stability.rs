@@ -0,0 +1,104 @@
+use crate::util::cell::{CellCoord, CellState};
+use std::collections::VecDeque;
+
+/// How many recent turns are remembered, which is the longest period that can be detected.
+pub const MAX_PERIOD: usize = 1024;
+
+/// Stability watches for the world repeating itself.
+///
+/// The world is hashed by XORing a pseudo-random key for every cell that is not dead, so the hash
+/// is kept up to date from the cells each step changes rather than rehashing the whole world.
+/// Keys are taken from world coordinates, so an unbounded world hashes the same after it grows.
+///
+/// A turn whose hash was already seen within the last [`MAX_PERIOD`] turns repeats with a period of
+/// the turns in between, as long as every turn in between was seen. Engines that jump many turns at
+/// a time skip some, so a repeat across a jump only gives a multiple of the period. The world is then
+/// [settling](Stability::is_settling) and should be stepped one turn at a time until the exact period is found.
+/// ## Examples
+/// ``` ignore
+/// let mut stability = Stability::new();
+/// stability.change(CellCoord::new(0, 0), CellState::DEAD, CellState::ALIVE);
+/// assert_eq!(stability.seen(0, 0), None);
+/// assert_eq!(stability.seen(1, 1), Some(1)); // nothing changed, so it is a still life
+/// assert_eq!(stability.seen(2, 1), None); // and that is only reported once
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Stability {
+    hash: u64,
+    /// The turns seen most recently with the hash of the world at each, oldest first.
+    recent: VecDeque<(u32, u64)>,
+    /// The turn the last jump landed on. A repeat of a turn from before it skipped some turns in between.
+    exact_since: u32,
+    settling: bool,
+    /// The turn the world was first found to repeat at.
+    stabilised: Option<u32>,
+}
+
+impl Stability {
+    pub fn new() -> Self {
+        Stability::default()
+    }
+
+    /// Update the hash for a cell that changed from `before` to `after`.
+    pub fn change(&mut self, cell: CellCoord<i64>, before: CellState, after: CellState) {
+        self.hash ^= key(cell, before) ^ key(cell, after);
+    }
+
+    /// Remember the world as it is at `turn`, after a step of `turns` turns, and return its period
+    /// the first time it repeats.
+    pub fn seen(&mut self, turn: u32, turns: u64) -> Option<u32> {
+        if turns > 1 {
+            self.exact_since = turn;
+        }
+        let repeat = self.recent.iter().rev()
+            .find(|&&(_, hash)| hash == self.hash)
+            .map(|&(earlier, _)| earlier);
+        if self.recent.len() == MAX_PERIOD {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((turn, self.hash));
+        if self.stabilised.is_some() {
+            return None;
+        }
+        match repeat {
+            Some(earlier) if earlier >= self.exact_since => {
+                self.settling = false;
+                self.stabilised = Some(turn);
+                Some(turn - earlier)
+            },
+            Some(_) => {
+                self.settling = true;
+                None
+            },
+            None => None,
+        }
+    }
+
+    /// Whether the world repeated across a jump, so the next turns should be taken one at a time.
+    pub fn is_settling(&self) -> bool {
+        self.settling
+    }
+
+    /// Forget the turns after `turn` once the world has been stepped back to it,
+    /// so that stabilising again is reported again.
+    pub fn rewind(&mut self, turn: u32) {
+        self.recent.retain(|&(seen, _)| seen <= turn);
+        self.exact_since = self.exact_since.min(turn);
+        if self.stabilised.is_some_and(|stabilised| stabilised > turn) {
+            self.stabilised = None;
+        }
+    }
+}
+
+/// The key of a cell in a state, mixed with the SplitMix64 finaliser. Dead cells are not hashed.
+fn key(cell: CellCoord<i64>, state: CellState) -> u64 {
+    if state.is_dead() {
+        return 0;
+    }
+    let mut z = (cell.x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (cell.y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ (state.get() as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
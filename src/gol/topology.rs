@@ -0,0 +1,110 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Topology is how the edges of the world are joined, which decides the neighbours of border cells.
+/// ## Examples
+/// ``` ignore
+/// let klein: Topology = "klein".parse()?;
+/// assert_eq!(klein.map(-1, 0, 16, 16), Some((15, 0)));
+/// assert_eq!(klein.map(3, -1, 16, 16), Some((12, 15)));
+/// assert_eq!(Topology::Plane.map(-1, 0, 16, 16), None);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Topology {
+    /// Both pairs of edges are joined straight across.
+    #[default]
+    Torus,
+    /// Nothing is joined: every cell past an edge is dead.
+    Plane,
+    /// The left and right edges are joined straight across, the top and bottom edges mirrored,
+    /// so anything wrapping vertically comes back reflected left to right.
+    Klein,
+    /// Both pairs of edges are joined mirrored, making a projective plane.
+    /// Only two cells meet at each corner point, so there is nothing diagonally past a corner.
+    CrossSurface,
+    /// Like a torus, but anything wrapping vertically is shifted `offset` cells to the right.
+    TwistedTorus(usize),
+}
+
+impl Topology {
+    /// The cell of a `width` by `height` world that `(x, y)` refers to, where the coordinates may lie
+    /// past the edges. Returns `None` for cells past the edge of a plane, or diagonally past a cross-surface corner.
+    pub fn map(&self, x: isize, y: isize, width: usize, height: usize) -> Option<(usize, usize)> {
+        let (width, height) = (width as isize, height as isize);
+        // How many times the point crossed each pair of edges, and where it ends up inside the world.
+        let (across, x_inside) = (x.div_euclid(width), x.rem_euclid(width));
+        let (down, y_inside) = (y.div_euclid(height), y.rem_euclid(height));
+        let (x, y) = match *self {
+            Topology::Torus => (x_inside, y_inside),
+            Topology::Plane if across != 0 || down != 0 => return None,
+            Topology::Plane => (x, y),
+            Topology::Klein if down % 2 != 0 => (width - 1 - x_inside, y_inside),
+            Topology::Klein => (x_inside, y_inside),
+            Topology::CrossSurface if across != 0 && down != 0 => return None,
+            Topology::CrossSurface => (
+                if down % 2 != 0 { width - 1 - x_inside } else { x_inside },
+                if across % 2 != 0 { height - 1 - y_inside } else { y_inside },
+            ),
+            Topology::TwistedTorus(offset) => ((x + down * offset as isize).rem_euclid(width), y_inside),
+        };
+        Some((x as usize, y as usize))
+    }
+
+    pub fn is_torus(&self) -> bool {
+        matches!(self, Topology::Torus)
+    }
+}
+
+impl Display for Topology {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Topology::Torus => write!(f, "torus"),
+            Topology::Plane => write!(f, "plane"),
+            Topology::Klein => write!(f, "klein"),
+            Topology::CrossSurface => write!(f, "cross-surface"),
+            Topology::TwistedTorus(offset) => write!(f, "twisted-torus:{}", offset),
+        }
+    }
+}
+
+/// ParseTopologyError describes why a topology name could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseTopologyError {
+    /// The name is not one of the known topologies.
+    UnknownTopology(String),
+    /// The twisted torus offset is not a whole number of cells.
+    InvalidOffset(String),
+}
+
+impl Display for ParseTopologyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseTopologyError::UnknownTopology(name) => write!(
+                f, "Expected torus, plane, klein, cross-surface or twisted-torus:<offset>, got {:?}", name
+            ),
+            ParseTopologyError::InvalidOffset(offset) =>
+                write!(f, "{:?} is not a twisted torus offset in cells", offset),
+        }
+    }
+}
+
+impl std::error::Error for ParseTopologyError {}
+
+impl FromStr for Topology {
+    type Err = ParseTopologyError;
+
+    /// Parse a topology name in any case, with the offset of a twisted torus after a colon.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        match s.split_once(':') {
+            None if s == "torus" => Ok(Topology::Torus),
+            None if s == "plane" => Ok(Topology::Plane),
+            None if s == "klein" => Ok(Topology::Klein),
+            None if s == "cross-surface" => Ok(Topology::CrossSurface),
+            Some(("twisted-torus", offset)) => offset.parse()
+                .map(Topology::TwistedTorus)
+                .map_err(|_| ParseTopologyError::InvalidOffset(offset.to_string())),
+            _ => Err(ParseTopologyError::UnknownTopology(s)),
+        }
+    }
+}
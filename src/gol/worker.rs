@@ -0,0 +1,298 @@
+use crate::gol::engine::{naive::calculate_next_strip, padding, padding_cells};
+use crate::gol::protocol::{self, Load, Reply, Request, Stepped};
+use crate::gol::topology::Topology;
+use crate::util::cell::{CellCoord, CellState};
+use anyhow::{anyhow, bail, Context, Result};
+use flume::RecvTimeoutError;
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+/// Serve brokers one at a time until one of them asks the worker to shut down.
+/// A broker that disconnects or misbehaves only ends its own connection.
+pub fn serve(listener: TcpListener) -> Result<()> {
+    loop {
+        let (stream, broker) = listener.accept()?;
+        log::info!(target: "Worker", "Serving the broker at {}", broker);
+        match serve_connection(stream) {
+            Ok(true) => {
+                log::info!(target: "Worker", "Shutting down at the request of {}", broker);
+                return Ok(());
+            },
+            Ok(false) => log::info!(target: "Worker", "The broker at {} disconnected", broker),
+            Err(error) => log::warn!(target: "Worker", "Dropped the broker at {}: {:#}", broker, error),
+        }
+    }
+}
+
+/// Step the strip of a single broker until it disconnects, returning whether it asked for a shutdown.
+/// Halo cells are exchanged with the other workers of the broker on a listener of our own,
+/// whose address is the first thing sent to the broker.
+pub fn serve_connection(stream: TcpStream) -> Result<bool> {
+    stream.set_nodelay(true)?;
+    let halo = TcpListener::bind(SocketAddr::new(stream.local_addr()?.ip(), 0))?;
+    halo.set_nonblocking(true)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream.try_clone()?);
+    protocol::write_address(&mut writer, halo.local_addr()?)?;
+    let mut strip: Option<Strip> = None;
+    loop {
+        let reply = match protocol::read_request(&mut reader)? {
+            Some(Request::Load(load)) => {
+                // Close the connections to the old peers before making new ones.
+                drop(strip.take());
+                with_heartbeats(&stream, load.timeout / 4, || {
+                    strip = Strip::load(*load, &halo)?;
+                    Ok(Stepped { population: strip.as_ref().map_or(0, Strip::population), ..Stepped::default() })
+                })?
+            },
+            Some(Request::Step { turns }) => {
+                let Some(loaded) = strip.as_mut() else {
+                    bail!("The broker stepped a worker it has not loaded");
+                };
+                let reply = with_heartbeats(&stream, loaded.load.timeout / 4, || loaded.step(turns))?;
+                if matches!(reply, Reply::Failed(_)) {
+                    // The strip is part way through the turns, so wait for the broker to load it again.
+                    strip = None;
+                }
+                reply
+            },
+            Some(Request::Shutdown) => return Ok(true),
+            None => return Ok(false),
+        };
+        protocol::write_reply(&mut writer, &reply)?;
+    }
+}
+
+/// Do the work for a request while sending the broker a heartbeat every `interval`, so that
+/// it can tell a busy worker from a lost one. Work that fails becomes a `Failed` reply instead
+/// of ending the connection, as it is usually a peer that has been lost rather than the broker.
+fn with_heartbeats(stream: &TcpStream, interval: Duration, work: impl FnOnce() -> Result<Stepped>) -> Result<Reply> {
+    let (done_tx, done_rx) = flume::bounded::<()>(0);
+    std::thread::scope(|scope| {
+        // Heartbeats only go out while the work is under way, so they cannot interleave with replies.
+        let heartbeats = scope.spawn(move || -> Result<()> {
+            let mut stream = stream;
+            while let Err(RecvTimeoutError::Timeout) = done_rx.recv_timeout(interval) {
+                protocol::write_reply(&mut stream, &Reply::Heartbeat)?;
+            }
+            Ok(())
+        });
+        let reply = match work() {
+            Ok(stepped) => Reply::Stepped(stepped),
+            Err(error) => {
+                log::warn!(target: "Worker", "Dropping the strip: {:#}", error);
+                Reply::Failed(format!("{:#}", error))
+            },
+        };
+        drop(done_tx);
+        heartbeats.join().map_err(|_| anyhow!("The heartbeat sender panicked"))??;
+        Ok(reply)
+    })
+}
+
+/// Where a cell of the padding around a strip comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    /// Past the edge of a plane, or diagonally past a cross-surface corner.
+    Dead,
+    /// A cell of this strip, by row within the strip and column.
+    Local(usize, usize),
+    /// The `k`th cell received from the `peer`th peer each turn.
+    Remote(usize, usize),
+}
+
+/// Peer is another worker that needs cells of this strip, or has cells this strip needs.
+struct Peer {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+    /// The cells of this strip the peer needs, by row within the strip and column, in the order it expects them.
+    sends: Vec<(usize, usize)>,
+    /// How many cells the peer sends this strip each turn.
+    receives: usize,
+}
+
+/// Strip is the rows of the world a worker holds between turns, with everything needed
+/// to gather the cells around them from the workers holding the rest of the world.
+struct Strip {
+    load: Load,
+    rows: Vec<Vec<CellState>>,
+    /// The padding of the strip, as in [`padding`](crate::gol::engine::padding).
+    left: usize,
+    top: usize,
+    padded_width: usize,
+    padded_height: usize,
+    /// Each cell of the padded strip outside the strip itself, by index into the padded strip.
+    fills: Vec<(usize, Source)>,
+    peers: Vec<Peer>,
+}
+
+impl Strip {
+    /// Take over a strip and connect to the peers it exchanges halo cells with,
+    /// or return `None` for a worker left idle.
+    fn load(load: Load, halo: &TcpListener) -> Result<Option<Self>> {
+        let Some(&(start, end)) = load.strips.get(load.index) else {
+            return Ok(None);
+        };
+        let owner = |y: usize| load.strips.partition_point(|&(_, end)| end <= y);
+
+        // Every worker works out the padding of every strip the same way,
+        // so the cells one needs from another arrive in the order it expects them.
+        let mut needs = vec![Vec::new(); load.strips.len()];
+        let mut sends = vec![Vec::new(); load.strips.len()];
+        let mut fills = Vec::new();
+        for (j, &(other_start, other_end)) in load.strips.iter().enumerate() {
+            for (index, cell) in padding_cells(&load.rule, load.topology, load.width, load.height, other_start, other_end) {
+                let source = cell.map(|(x, y)| (owner(y), x, y));
+                if j == load.index {
+                    fills.push((index, match source {
+                        None => Source::Dead,
+                        Some((i, x, y)) if i == load.index => Source::Local(y - start, x),
+                        Some((i, x, y)) => {
+                            needs[i].push((x, y));
+                            Source::Remote(i, needs[i].len() - 1)
+                        },
+                    }));
+                } else if let Some((_, x, y)) = source.filter(|&(i, _, _)| i == load.index) {
+                    sends[j].push((y - start, x));
+                }
+            }
+        }
+
+        // Connect to the peers after this one and wait for the peers before it to connect.
+        // Connecting never waits on a peer accepting, so every worker can do this at once.
+        let mut connected = (0..load.strips.len()).map(|_| None).collect::<Vec<_>>();
+        let linked = |j: usize| j != load.index && !(needs[j].is_empty() && sends[j].is_empty());
+        for j in (load.index + 1..load.strips.len()).filter(|&j| linked(j)) {
+            let stream = TcpStream::connect_timeout(&load.peers[j], load.timeout)
+                .with_context(|| format!("Cannot connect to the peer at {}", load.peers[j]))?;
+            let stream = peer_stream(stream, load.timeout)?;
+            let mut writer = BufWriter::new(stream.try_clone()?);
+            protocol::write_u32(&mut writer, load.index)?;
+            protocol::write_u32(&mut writer, load.epoch)?;
+            writer.flush()?;
+            connected[j] = Some((BufReader::new(stream), writer));
+        }
+        let mut waiting = (0..load.index).filter(|&j| linked(j)).count();
+        let deadline = Instant::now() + load.timeout;
+        while waiting > 0 {
+            let stream = match halo.accept() {
+                Ok((stream, _)) => peer_stream(stream, load.timeout)?,
+                Err(error) if error.kind() == ErrorKind::WouldBlock && Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(5));
+                    continue;
+                },
+                Err(error) if error.kind() == ErrorKind::WouldBlock => bail!("{} peers did not connect in time", waiting),
+                Err(error) => return Err(error.into()),
+            };
+            let mut reader = BufReader::new(stream.try_clone()?);
+            let (j, epoch) = (protocol::read_u32(&mut reader)?, protocol::read_u32(&mut reader)?);
+            if epoch != load.epoch {
+                // Left over from a load that failed before this worker accepted it.
+                continue;
+            }
+            if j >= load.index || !linked(j) || connected[j].is_some() {
+                bail!("A peer connected as strip {}, which strip {} does not exchange with", j, load.index);
+            }
+            connected[j] = Some((reader, BufWriter::new(stream)));
+            waiting -= 1;
+        }
+        let mut peers = Vec::new();
+        let mut slots = vec![0; load.strips.len()];
+        for (j, connection) in connected.into_iter().enumerate() {
+            if let Some((reader, writer)) = connection {
+                slots[j] = peers.len();
+                peers.push(Peer { reader, writer, sends: std::mem::take(&mut sends[j]), receives: needs[j].len() });
+            }
+        }
+        for (_, source) in fills.iter_mut() {
+            if let Source::Remote(peer, _) = source {
+                *peer = slots[*peer];
+            }
+        }
+
+        let (left, top, padded_width, padded_height) = padding(&load.rule, load.width, start, end);
+        let rows = load.cells.chunks(load.width).map(<[CellState]>::to_vec).collect();
+        Ok(Some(Strip { load: Load { cells: Vec::new(), ..load }, rows, left, top, padded_width, padded_height, fills, peers }))
+    }
+
+    fn population(&self) -> u64 {
+        self.rows.iter().flatten().filter(|cell| cell.is_alive()).count() as u64
+    }
+
+    /// Step the strip `turns` turns, returning the cells that changed over all of them.
+    fn step(&mut self, turns: u32) -> Result<Stepped> {
+        let before = self.rows.clone();
+        let mut halo_bytes = 0;
+        for _ in 0..turns {
+            halo_bytes += self.step_once()?;
+        }
+        let start = self.load.strips[self.load.index].0;
+        let flips = before.iter().zip(&self.rows).enumerate()
+            .flat_map(|(y, (before, after))|
+                before.iter().zip(after).enumerate()
+                    .filter(|(_, (before, after))| before != after)
+                    .map(move |(x, (_, &after))| (CellCoord::new(x, start + y), after)))
+            .collect();
+        Ok(Stepped { population: self.population(), halo_bytes, flips })
+    }
+
+    /// Exchange halo cells with the peers and step the strip one turn, returning the halo bytes sent.
+    fn step_once(&mut self) -> Result<u64> {
+        let (received, sent) = self.exchange()?;
+        let mut padded = vec![vec![CellState::DEAD; self.padded_width]; self.padded_height];
+        for (row, padded) in self.rows.iter().zip(&mut padded[self.top..]) {
+            padded[self.left..self.left + row.len()].copy_from_slice(row);
+        }
+        for &(index, source) in &self.fills {
+            padded[index / self.padded_width][index % self.padded_width] = match source {
+                Source::Dead => CellState::DEAD,
+                Source::Local(y, x) => self.rows[y][x],
+                Source::Remote(peer, k) => received[peer][k],
+            };
+        }
+        let mut next = padded[self.top..self.top + self.rows.len()].to_vec();
+        calculate_next_strip(&self.load.rule, Topology::Plane, &padded, self.top, &mut next);
+        for (row, next) in self.rows.iter_mut().zip(next) {
+            row.copy_from_slice(&next[self.left..self.left + self.load.width]);
+        }
+        Ok(sent)
+    }
+
+    /// Send every peer the cells it needs while receiving the cells needed from it.
+    /// Sending happens on threads of its own, so that peers sending to each other cannot both
+    /// block on full buffers.
+    fn exchange(&mut self) -> Result<(Vec<Vec<CellState>>, u64)> {
+        let rows = &self.rows;
+        std::thread::scope(|scope| {
+            let mut senders = Vec::new();
+            let mut readers = Vec::new();
+            for Peer { reader, writer, sends, receives } in self.peers.iter_mut() {
+                let cells = sends.iter().map(|&(y, x)| rows[y][x]).collect::<Vec<_>>();
+                senders.push(scope.spawn(move || -> Result<u64> {
+                    let sent = protocol::write_cells(writer, &cells)?;
+                    writer.flush()?;
+                    Ok(sent)
+                }));
+                readers.push((reader, *receives));
+            }
+            let received = readers.into_iter()
+                .map(|(reader, receives)| protocol::read_cells(reader, receives).context("Lost a peer"))
+                .collect::<Result<Vec<_>>>()?;
+            let mut sent = 0;
+            for sender in senders {
+                sent += sender.join().map_err(|_| anyhow::anyhow!("A halo sender panicked"))??;
+            }
+            Ok((received, sent))
+        })
+    }
+}
+
+/// Set up a connection to a peer, which is given up on if it is silent for longer than `timeout`.
+fn peer_stream(stream: TcpStream, timeout: Duration) -> Result<TcpStream> {
+    stream.set_nonblocking(false)?;
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    Ok(stream)
+}
@@ -1,3 +1,4 @@
+pub mod app;
 pub mod args;
 pub mod gol;
 pub mod sdl;
@@ -1,10 +1,12 @@
-use crate::args::Args;
+use crate::args::{Args, DEFAULT_SIZE};
 use crate::gol::event::{Event, State};
+use crate::sdl::viewport::Viewport;
 use crate::sdl::window::Window;
 use crate::util::avgturns::AvgTurns;
 use anyhow::Result;
 use flume::{Receiver, Sender};
 use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
 use sdl2::event::Event as SdlEvent;
 use tokio::select;
 use std::time::Duration;
@@ -14,12 +16,19 @@ pub async fn run(
     events: Receiver<Event>,
     key_presses: Sender<Keycode>
 ) -> Result<()> {
-    let mut sdl = Window::new(
+    // The size was settled before the window was made, so reading the input image again is not needed.
+    let (width, height) = (args.image_width.unwrap_or(DEFAULT_SIZE), args.image_height.unwrap_or(DEFAULT_SIZE));
+    let mut sdl = Window::with_lattice(
         "Gol GUI",
-        args.image_width as u32,
-        args.image_height as u32,
+        width as u32,
+        height as u32,
+        args.rule.lattice(),
     )?;
 
+    // Only an unbounded world can grow past the window, so only then can the arrow keys pan it.
+    let mut viewport = Viewport::new(width as u32, height as u32, args.unbounded);
+    // Panning by an even number of cells keeps hexagon rows and triangle directions where they are drawn.
+    let pan_step = (width.min(height) as i64 / 8).max(2) & !1;
     let mut event_pump = sdl.take_event_pump()?;
     let mut dirty = false;
     let mut refresh_interval = tokio::time::interval(
@@ -41,6 +50,25 @@ pub async fn run(
                         key_presses.send_async(Keycode::Q).await?,
                     Some(SdlEvent::KeyDown { keycode: Some(Keycode::K), .. }) =>
                         key_presses.send_async(Keycode::K).await?,
+                    Some(SdlEvent::KeyDown { keycode: Some(Keycode::B), .. }) =>
+                        key_presses.send_async(Keycode::B).await?,
+                    Some(SdlEvent::KeyDown { keycode: Some(key @ (Keycode::Left | Keycode::Right | Keycode::Up | Keycode::Down)), .. }) => {
+                        let (dx, dy) = match key {
+                            Keycode::Left => (-pan_step, 0),
+                            Keycode::Right => (pan_step, 0),
+                            Keycode::Up => (0, -pan_step),
+                            _ => (0, pan_step),
+                        };
+                        if viewport.pan(dx, dy) {
+                            sdl.clear();
+                            for ((x, y), state) in viewport.visible() {
+                                let grey = state.grey(args.rule.states());
+                                sdl.set_cell(x, y, Color::RGBA(grey, grey, grey, grey));
+                            }
+                            log::info!(target: "Event", "Viewport at {}", viewport.origin());
+                            dirty = true;
+                        }
+                    },
                     _ => (),
                 }
                 if dirty {
@@ -51,9 +79,20 @@ pub async fn run(
             gol_event = events.recv_async() => {
                 match gol_event {
                     Ok(Event::CellFlipped { cell, .. }) =>
-                        sdl.flip_pixel(cell.x as u32, cell.y as u32),
+                        if let Some((x, y)) = viewport.flip(cell) {
+                            sdl.flip_cell(x, y)
+                        },
                     Ok(Event::CellsFlipped { cells, ..}) =>
-                        cells.iter().for_each(|cell| sdl.flip_pixel(cell.x as u32, cell.y as u32)),
+                        cells.into_iter()
+                            .filter_map(|cell| viewport.flip(cell))
+                            .for_each(|(x, y)| sdl.flip_cell(x, y)),
+                    Ok(Event::CellsChanged { cells, .. }) =>
+                        cells.into_iter()
+                            .filter_map(|(cell, state)| viewport.set(cell, state).map(|position| (position, state)))
+                            .for_each(|((x, y), state)| {
+                                let grey = state.grey(args.rule.states());
+                                sdl.set_cell(x, y, Color::RGBA(grey, grey, grey, grey))
+                            }),
                     Ok(Event::TurnComplete { .. }) =>
                         dirty = true,
                     Ok(Event::AliveCellsCount { completed_turns, .. }) =>
@@ -64,6 +103,8 @@ pub async fn run(
                         ),
                     Ok(Event::ImageOutputComplete { .. }) =>
                         log::info!(target: "Event", "{}", gol_event?),
+                    Ok(Event::Stabilised { .. }) =>
+                        log::info!(target: "Event", "{}", gol_event?),
                     Ok(Event::FinalTurnComplete { .. }) =>
                         log::info!(target: "Event", "{}", gol_event?),
                     Ok(Event::StateChange { new_state, .. }) => {
@@ -94,6 +135,8 @@ pub async fn run_headless(events: Receiver<Event>) -> Result<()> {
                 ),
             Ok(Event::ImageOutputComplete { .. }) =>
                 log::info!(target: "Event", "{}", gol_event?),
+            Ok(Event::Stabilised { .. }) =>
+                log::info!(target: "Event", "{}", gol_event?),
             Ok(Event::FinalTurnComplete { .. }) =>
                 log::info!(target: "Event", "{}", gol_event?),
             Ok(Event::StateChange { new_state, .. }) => {
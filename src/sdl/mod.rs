@@ -1,2 +1,3 @@
 pub mod r#loop;
 pub mod window;
+pub mod viewport;
@@ -0,0 +1,76 @@
+use crate::util::cell::{CellCoord, CellState};
+use std::collections::HashMap;
+
+/// Viewport is the `width` by `height` part of the world shown in the window,
+/// with its top left cell at `origin` in world coordinates.
+///
+/// A movable viewport remembers every cell that is not dead, wherever it is,
+/// so it can redraw the window after panning. A fixed one stays at the origin
+/// and only maps cells to window positions, which keeps bounded worlds as cheap to draw as before.
+pub struct Viewport {
+    origin: CellCoord<i64>,
+    width: u32,
+    height: u32,
+    cells: Option<HashMap<CellCoord<i64>, CellState>>,
+}
+
+impl Viewport {
+    pub fn new(width: u32, height: u32, movable: bool) -> Self {
+        Viewport {
+            origin: CellCoord::new(0, 0),
+            width,
+            height,
+            cells: movable.then(HashMap::new),
+        }
+    }
+
+    pub fn origin(&self) -> CellCoord<i64> {
+        self.origin
+    }
+
+    /// The position of `cell` in the window, if it is in view.
+    pub fn position(&self, cell: CellCoord<i64>) -> Option<(u32, u32)> {
+        let (x, y) = (cell.x - self.origin.x, cell.y - self.origin.y);
+        ((0..self.width as i64).contains(&x) && (0..self.height as i64).contains(&y))
+            .then_some((x as u32, y as u32))
+    }
+
+    /// Record that `cell` flipped between dead and alive, returning its position if it is in view.
+    pub fn flip(&mut self, cell: CellCoord<i64>) -> Option<(u32, u32)> {
+        if let Some(cells) = self.cells.as_mut() {
+            if cells.remove(&cell).is_none() {
+                cells.insert(cell, CellState::ALIVE);
+            }
+        }
+        self.position(cell)
+    }
+
+    /// Record the new state of `cell`, returning its position if it is in view.
+    pub fn set(&mut self, cell: CellCoord<i64>, state: CellState) -> Option<(u32, u32)> {
+        if let Some(cells) = self.cells.as_mut() {
+            if state.is_dead() {
+                cells.remove(&cell);
+            } else {
+                cells.insert(cell, state);
+            }
+        }
+        self.position(cell)
+    }
+
+    /// Move the viewport by `dx` cells right and `dy` cells down.
+    /// Returns whether it moved, in which case the window needs redrawing from [`Viewport::visible`].
+    pub fn pan(&mut self, dx: i64, dy: i64) -> bool {
+        if self.cells.is_none() {
+            return false;
+        }
+        self.origin = CellCoord::new(self.origin.x + dx, self.origin.y + dy);
+        true
+    }
+
+    /// The position and state of every remembered cell in view.
+    pub fn visible(&self) -> impl Iterator<Item = ((u32, u32), CellState)> + '_ {
+        self.cells.iter()
+            .flatten()
+            .filter_map(|(&cell, &state)| self.position(cell).map(|position| (position, state)))
+    }
+}
@@ -1,10 +1,14 @@
+use crate::gol::lattice::Lattice;
 use anyhow::{anyhow, Result, Context};
 use sdl2::EventPump;
 use sdl2::pixels::{PixelFormatEnum, Color};
 use sdl2::render::{Texture, Canvas};
 use sdl2::video::Window as SdlWindow;
 
+/// Window draws the world into a streaming texture. Square cells are single pixels;
+/// hexagons and triangles are drawn as small tiling shapes, see [`Window::with_lattice`].
 pub struct Window {
+    lattice: Lattice,
     width: u32,
     height: u32,
     pitch: u32,
@@ -21,6 +25,23 @@ impl Window {
         width: u32,
         height: u32
     ) -> Result<Self> {
+        Window::with_lattice(title, width, height, Lattice::Square)
+    }
+
+    /// Create a window for a `width` by `height` world of `lattice` cells.
+    /// Hexagons are 2x2 pixel bricks with odd rows shifted right by one pixel, and triangles are
+    /// two pixel rows of 2 and 4 pixels, overlapping their neighbours so that they tile each row.
+    pub fn with_lattice<T: AsRef<str>>(
+        title: T,
+        width: u32,
+        height: u32,
+        lattice: Lattice,
+    ) -> Result<Self> {
+        let (width, height) = match lattice {
+            Lattice::Square => (width, height),
+            Lattice::Hexagonal => (2 * width + 1, 2 * height),
+            Lattice::Triangular => (3 * width + 1, 2 * height),
+        };
         let context = sdl2::init().map_err(|e| anyhow!(e))?;
         let video = context.video().map_err(|e| anyhow!(e))?;
         let window = video
@@ -40,6 +61,7 @@ impl Window {
         let pixels = vec![0_u8; (height * pitch) as usize];
 
         Ok(Window {
+            lattice,
             width,
             height,
             pitch,
@@ -58,13 +80,13 @@ impl Window {
         let texture = self.texture.as_mut().context("Missing texture")?;
         texture.update(None, &self.pixels, self.pitch as usize)?;
         self.canvas.clear();
-        self.canvas.copy(&texture, None, None).map_err(|e| anyhow!(e))?;
+        self.canvas.copy(texture, None, None).map_err(|e| anyhow!(e))?;
         self.canvas.present();
         Ok(())
     }
 
     pub fn set_pixel(&mut self, x: u32, y: u32, color: Color) {
-        self.pixels[4 * (y * self.width + x) as usize + 0] = color.a;
+        self.pixels[4 * (y * self.width + x) as usize] = color.a;
         self.pixels[4 * (y * self.width + x) as usize + 1] = color.r;
         self.pixels[4 * (y * self.width + x) as usize + 2] = color.g;
         self.pixels[4 * (y * self.width + x) as usize + 3] = color.b;
@@ -76,8 +98,8 @@ impl Window {
             "Cell flipped at ({}, {}) is outside the bounds of the window.",
             x, y
         );
-        self.pixels[4 * (y * self.width + x) as usize + 0] =
-            !self.pixels[4 * (y * self.width + x) as usize + 0];
+        self.pixels[4 * (y * self.width + x) as usize] =
+            !self.pixels[4 * (y * self.width + x) as usize];
         self.pixels[4 * (y * self.width + x) as usize + 1] =
             !self.pixels[4 * (y * self.width + x) as usize + 1];
         self.pixels[4 * (y * self.width + x) as usize + 2] =
@@ -86,11 +108,52 @@ impl Window {
             !self.pixels[4 * (y * self.width + x) as usize + 3];
     }
 
+    /// Turn every pixel black, ready to draw a different part of the world.
+    pub fn clear(&mut self) {
+        self.pixels.fill(0);
+    }
+
+    /// The pixels of the cell at `(x, y)`.
+    fn cell_pixels(&self, x: u32, y: u32) -> Vec<(u32, u32)> {
+        match self.lattice {
+            Lattice::Square => vec![(x, y)],
+            Lattice::Hexagonal => {
+                let left = 2 * x + y % 2;
+                vec![(left, 2 * y), (left + 1, 2 * y), (left, 2 * y + 1), (left + 1, 2 * y + 1)]
+            },
+            Lattice::Triangular => {
+                // The narrow row is the top of a triangle pointing up and the bottom of one pointing down.
+                let (narrow, wide) = if Lattice::points_up(x as i64, y as i64) {
+                    (2 * y, 2 * y + 1)
+                } else {
+                    (2 * y + 1, 2 * y)
+                };
+                (3 * x + 1..3 * x + 3).map(|px| (px, narrow))
+                    .chain((3 * x..3 * x + 4).map(|px| (px, wide)))
+                    .collect()
+            },
+        }
+    }
+
+    /// Flip every pixel of the cell at `(x, y)`.
+    pub fn flip_cell(&mut self, x: u32, y: u32) {
+        for (px, py) in self.cell_pixels(x, y) {
+            self.flip_pixel(px, py);
+        }
+    }
+
+    /// Colour every pixel of the cell at `(x, y)`.
+    pub fn set_cell(&mut self, x: u32, y: u32, color: Color) {
+        for (px, py) in self.cell_pixels(x, y) {
+            self.set_pixel(px, py, color);
+        }
+    }
+
     pub fn count_pixels(&self) -> u32 {
         self.pixels
             .chunks(4)
             .filter(|&chunk|
-                chunk.get(0).map_or(false, |&x| x == 0xFF_u8))
+                chunk.first() == Some(&0xFF_u8))
             .count() as u32
     }
 
@@ -32,3 +32,9 @@ impl AvgTurns {
         turns / duration.clamp(1, u32::MAX)
     }
 }
+
+impl Default for AvgTurns {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,252 @@
+use crate::gol::rule::Rule;
+use crate::gol::topology::Topology;
+use crate::util::cell::{CellCoord, CellValue};
+use crate::util::traits::AsBytes;
+use std::borrow::Cow;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// BitWorld is a packed world representation storing 64 cells per `u64`.
+/// Bit `i` of word `w` in a row holds the cell at `x = w * 64 + i`.
+/// Any bits past the end of a row are always kept at zero.
+/// ## Examples
+/// ``` ignore
+/// let world = vec![CellValue::Dead; 64 * 64];
+/// let bits = BitWorld::from_cells(64, 64, &world);
+/// assert_eq!(bits.to_cells(), world);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitWorld {
+    width: usize,
+    height: usize,
+    words_per_row: usize,
+    words: Vec<u64>,
+}
+
+impl BitWorld {
+    /// Create a new world of the given size with every cell `Dead`.
+    pub fn new(width: usize, height: usize) -> Self {
+        let words_per_row = width.div_ceil(WORD_BITS);
+        BitWorld {
+            width,
+            height,
+            words_per_row,
+            words: vec![0; words_per_row * height],
+        }
+    }
+
+    /// Pack a row-major `CellValue` slice into a new world.
+    pub fn from_cells(width: usize, height: usize, cells: &[CellValue]) -> Self {
+        Self::from_bytes(width, height, cells.as_bytes())
+    }
+
+    /// Pack row-major PGM bytes (0 or 255) into a new world.
+    pub fn from_bytes(width: usize, height: usize, bytes: &[u8]) -> Self {
+        assert_eq!(bytes.len(), width * height, "Byte count does not match the world size");
+        let mut world = Self::new(width, height);
+        for (y, row) in bytes.chunks(width).enumerate() {
+            for (x, &byte) in row.iter().enumerate() {
+                if CellValue::from(byte).is_alive() {
+                    world.words[y * world.words_per_row + x / WORD_BITS] |= 1 << (x % WORD_BITS);
+                }
+            }
+        }
+        world
+    }
+
+    /// Unpack the world into a row-major `CellValue` vector.
+    pub fn to_cells(&self) -> Vec<CellValue> {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| self.get(x, y)))
+            .collect()
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn words_per_row(&self) -> usize {
+        self.words_per_row
+    }
+
+    /// The packed words of the row at `y`.
+    pub fn row(&self, y: usize) -> &[u64] {
+        &self.words[y * self.words_per_row..(y + 1) * self.words_per_row]
+    }
+
+    /// The packed words of rows `[start_y, end_y)`, mutably.
+    pub fn rows_mut(&mut self, start_y: usize, end_y: usize) -> &mut [u64] {
+        &mut self.words[start_y * self.words_per_row..end_y * self.words_per_row]
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> CellValue {
+        if self.row(y)[x / WORD_BITS] >> (x % WORD_BITS) & 1 == 1 {
+            CellValue::Alive
+        } else {
+            CellValue::Dead
+        }
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: CellValue) {
+        let word = &mut self.words[y * self.words_per_row + x / WORD_BITS];
+        match value {
+            CellValue::Alive => *word |= 1 << (x % WORD_BITS),
+            CellValue::Dead => *word &= !(1 << (x % WORD_BITS)),
+        }
+    }
+
+    /// Count the alive cells.
+    pub fn population(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// Coordinates of every alive cell, in row-major order.
+    pub fn alive_cells(&self) -> Vec<CellCoord> {
+        self.ones(|y, w| self.row(y)[w])
+    }
+
+    /// Coordinates of every cell that differs between `self` and `other`, in row-major order.
+    pub fn diff(&self, other: &BitWorld) -> Vec<CellCoord> {
+        assert_eq!((self.width, self.height), (other.width, other.height));
+        self.ones(|y, w| self.row(y)[w] ^ other.row(y)[w])
+    }
+
+    fn ones(&self, word_at: impl Fn(usize, usize) -> u64) -> Vec<CellCoord> {
+        let mut cells = Vec::new();
+        for y in 0..self.height {
+            for w in 0..self.words_per_row {
+                let mut word = word_at(y, w);
+                while word != 0 {
+                    cells.push(CellCoord::new(w * WORD_BITS + word.trailing_zeros() as usize, y));
+                    word &= word - 1;
+                }
+            }
+        }
+        cells
+    }
+
+    /// Advance the whole world by one turn of `rule`, with edges joined by `topology`.
+    pub fn step(&self, rule: &Rule, topology: Topology) -> BitWorld {
+        let mut next = BitWorld::new(self.width, self.height);
+        self.step_rows(rule, topology, 0, &mut next.words);
+        next
+    }
+
+    /// Compute the next state of the rows starting at `start_y` into `out`,
+    /// which holds a whole number of packed rows.
+    /// Neighbour counts are summed 64 cells at a time with bitwise full adders.
+    pub fn step_rows(&self, rule: &Rule, topology: Topology, start_y: usize, out: &mut [u64]) {
+        let births = (0..=8_u8).filter(|&n| rule.births(n.into())).collect::<Vec<_>>();
+        let survivals = (0..=8_u8).filter(|&n| rule.survives(n.into())).collect::<Vec<_>>();
+        let last_mask = self.last_word_mask();
+        for (dy, out_row) in out.chunks_mut(self.words_per_row).enumerate() {
+            let y = (start_y + dy) as isize;
+            let above = self.neighbour_row(topology, y - 1);
+            let current = self.neighbour_row(topology, y);
+            let below = self.neighbour_row(topology, y + 1);
+            for (w, out_word) in out_row.iter_mut().enumerate() {
+                let next = if rule.is_totalistic() {
+                    let mut count = [0_u64; 4];
+                    for row in [&above, &below] {
+                        add(&mut count, self.west(row, w));
+                        add(&mut count, row.words[w]);
+                        add(&mut count, self.east(row, w));
+                    }
+                    add(&mut count, self.west(&current, w));
+                    add(&mut count, self.east(&current, w));
+
+                    let alive = current.words[w];
+                    let born = births.iter().fold(0, |lanes, &n| lanes | equals(&count, n));
+                    let survived = survivals.iter().fold(0, |lanes, &n| lanes | equals(&count, n));
+                    (!alive & born) | (alive & survived)
+                } else {
+                    // Non-totalistic rules look up each lane's 3x3 neighbourhood in the rule's table.
+                    let neighbourhood = [&above, &current, &below]
+                        .map(|row| [self.west(row, w), row.words[w], self.east(row, w)]);
+                    (0..WORD_BITS).fold(0, |next, lane| {
+                        let index = neighbourhood.iter().flatten().enumerate()
+                            .fold(0, |index, (i, word)| index | ((word >> lane & 1) as u16) << i);
+                        next | (rule.transition(index) as u64) << lane
+                    })
+                };
+                *out_word = if w + 1 == self.words_per_row { next & last_mask } else { next };
+            }
+        }
+    }
+
+    /// The row at `y`, which may be one row past either edge, as seen through `topology`.
+    /// Rows past an edge are only borrowed on a torus, anywhere else they are rebuilt cell by cell.
+    fn neighbour_row(&self, topology: Topology, y: isize) -> NeighbourRow<'_> {
+        let (width, height) = (self.width, self.height);
+        let cell = |x: isize| topology.map(x, y, width, height)
+            .map_or(0, |(x, y)| self.row(y)[x / WORD_BITS] >> (x % WORD_BITS) & 1);
+        let words = if (0..height as isize).contains(&y) || topology.is_torus() {
+            Cow::Borrowed(self.row(y.rem_euclid(height as isize) as usize))
+        } else {
+            let mut words = vec![0; self.words_per_row];
+            for x in 0..width {
+                words[x / WORD_BITS] |= cell(x as isize) << (x % WORD_BITS);
+            }
+            Cow::Owned(words)
+        };
+        NeighbourRow { words, west: cell(-1), east: cell(width as isize) }
+    }
+
+    /// Mask of the valid cells in the last word of each row.
+    fn last_word_mask(&self) -> u64 {
+        match self.width % WORD_BITS {
+            0 => u64::MAX,
+            bits => (1 << bits) - 1,
+        }
+    }
+
+    /// The west neighbour of every cell in word `w` of `row`.
+    fn west(&self, row: &NeighbourRow, w: usize) -> u64 {
+        let carry = if w == 0 { row.west } else { row.words[w - 1] >> (WORD_BITS - 1) };
+        row.words[w] << 1 | carry
+    }
+
+    /// The east neighbour of every cell in word `w` of `row`.
+    fn east(&self, row: &NeighbourRow, w: usize) -> u64 {
+        if w + 1 < self.words_per_row {
+            row.words[w] >> 1 | row.words[w + 1] << (WORD_BITS - 1)
+        } else {
+            let last = (self.width - 1) % WORD_BITS;
+            row.words[w] >> 1 | row.east << last
+        }
+    }
+}
+
+/// NeighbourRow is a packed row as a neighbouring row sees it,
+/// with the cells just past its west and east ends in the lowest bit of `west` and `east`.
+struct NeighbourRow<'a> {
+    words: Cow<'a, [u64]>,
+    west: u64,
+    east: u64,
+}
+
+impl AsBytes for BitWorld {
+    /// Cast the packed words to a byte slice, row by row.
+    fn as_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.words)
+    }
+}
+
+/// Add a one-bit value to every lane of a 4-bit bit-sliced counter.
+fn add(count: &mut [u64; 4], mut carry: u64) {
+    for bit in count.iter_mut() {
+        let next_carry = *bit & carry;
+        *bit ^= carry;
+        carry = next_carry;
+    }
+}
+
+/// Lanes of a bit-sliced counter whose value equals `n`.
+fn equals(count: &[u64; 4], n: u8) -> u64 {
+    count.iter().enumerate().fold(u64::MAX, |lanes, (i, &bit)|
+        lanes & if n >> i & 1 == 1 { bit } else { !bit })
+}
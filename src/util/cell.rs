@@ -4,7 +4,7 @@ use bytemuck::NoUninit;
 use num_traits::PrimInt;
 
 /// CellCoord (Cell coordinate) represents the coordinate of a cell in the world.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct CellCoord<T = usize>
     where T: PrimInt
 {
@@ -19,6 +19,18 @@ impl<T: PrimInt> CellCoord<T> {
     }
 }
 
+impl CellCoord {
+    /// The coordinate of this cell in a world whose top left cell is at `origin`.
+    /// ## Examples
+    /// ``` ignore
+    /// let cell = CellCoord::new(3, 4).offset(CellCoord::new(-64, 10));
+    /// assert_eq!(cell, CellCoord::new(-61_i64, 14));
+    /// ```
+    pub fn offset(self, origin: CellCoord<i64>) -> CellCoord<i64> {
+        CellCoord::new(origin.x + self.x as i64, origin.y + self.y as i64)
+    }
+}
+
 impl<T> Display for CellCoord<T>
     where T: PrimInt + std::fmt::Debug
 {
@@ -150,3 +162,98 @@ impl Display for CellValue {
         write!(f, "{:?}", self)
     }
 }
+
+/// CellState (Cell state) represents the state of a cell under a multi-state rule.
+/// State 0 is dead and state 1 is alive. Under Generations rules with `C` states,
+/// an alive cell that does not survive passes through the refractory states
+/// `2..C` in order before it is dead again, and only alive cells count as neighbours.
+/// ## Examples
+/// ``` ignore
+/// let cell = CellState::from(CellValue::Alive);
+/// assert!(cell.is_alive());
+/// assert_eq!(CellState::new(2).grey(3), 127); // half way between alive and dead
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, NoUninit)]
+#[repr(transparent)]
+pub struct CellState(u8);
+
+impl CellState {
+    pub const DEAD: CellState = CellState(0);
+    pub const ALIVE: CellState = CellState(1);
+
+    /// Create a cell in the given state.
+    pub fn new(state: u8) -> Self {
+        CellState(state)
+    }
+
+    /// The state index of the cell.
+    pub fn get(&self) -> u8 {
+        self.0
+    }
+
+    /// Check if the cell is dead.
+    pub fn is_dead(&self) -> bool {
+        *self == CellState::DEAD
+    }
+
+    /// Check if the cell is alive.
+    pub fn is_alive(&self) -> bool {
+        *self == CellState::ALIVE
+    }
+
+    /// The PGM grey level of the cell for a rule with `states` states.
+    /// Alive is 255, dead is 0 and refractory states are evenly spaced in between,
+    /// getting darker as they decay.
+    /// ## Examples
+    /// ``` ignore
+    /// assert_eq!(CellState::ALIVE.grey(4), 255);
+    /// assert_eq!(CellState::new(2).grey(4), 170);
+    /// assert_eq!(CellState::new(3).grey(4), 85);
+    /// ```
+    pub fn grey(&self, states: u8) -> u8 {
+        match self.0 {
+            0 => 0,
+            state => (255 * states.saturating_sub(state) as u32 / (states - 1).max(1) as u32) as u8,
+        }
+    }
+
+    /// The state whose grey level is nearest to `grey` for a rule with `states` states.
+    /// This is the inverse of [`CellState::grey`].
+    pub fn from_grey(grey: u8, states: u8) -> Self {
+        if grey == 0 {
+            return CellState::DEAD;
+        }
+        (1..states).map(CellState)
+            .min_by_key(|state| (state.grey(states) as i32 - grey as i32).abs())
+            .unwrap_or(CellState::ALIVE)
+    }
+}
+
+impl From<CellValue> for CellState {
+    fn from(value: CellValue) -> Self {
+        match value {
+            CellValue::Dead => CellState::DEAD,
+            CellValue::Alive => CellState::ALIVE,
+        }
+    }
+}
+
+impl From<CellState> for CellValue {
+    /// Only alive cells are `Alive`, refractory cells are `Dead`.
+    fn from(state: CellState) -> Self {
+        if state.is_alive() { CellValue::Alive } else { CellValue::Dead }
+    }
+}
+
+impl AsBytes for [CellState] {
+    /// Cast a `CellState` slice to a byte slice of state indices.
+    fn as_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(self)
+    }
+}
+
+impl Display for CellState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
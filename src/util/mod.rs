@@ -1,4 +1,5 @@
 pub mod avgturns;
+pub mod bitworld;
 pub mod cell;
 pub mod logger;
 pub mod traits;
@@ -0,0 +1,101 @@
+use anyhow::Result;
+use colored::Colorize;
+use gol_rs::{args::Args, gol::{self, engine::Balance, event::{Event, State}, Params}, util::logger};
+use gol_rs::gol::engine::{naive::Naive, parallel::Parallel, Engine};
+use gol_rs::gol::{rule::Rule, topology::Topology};
+use log::Level;
+use sdl2::keyboard::Keycode;
+use utils::{io::read_alive_cells, visualise::assert_eq_board, world::glider_gun};
+
+mod utils;
+
+#[tokio::main]
+async fn main() {
+    let start = std::time::Instant::now();
+    logger::set_panic_hook();
+    logger::init(Level::Debug, false);
+
+    let passed_tests = test_gol(Args::default()).await.unwrap()
+        + test_sparse().unwrap();
+
+    println!(
+        "\ntest result: {}. {} passed; finished in {:.2}s\n",
+        "ok".green(),
+        passed_tests,
+        start.elapsed().as_secs_f32()
+    );
+    std::process::exit(0);
+}
+
+/// Balancing never changes the result, however often the strips are recut.
+async fn test_gol(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    let expected_alive = read_alive_cells("check/images/512x512x100.pgm", 512, 512)?;
+    for balance in [Balance::Off, Balance::Time, Balance::Work] {
+        for interval in [1, 7] {
+            let args = args.clone()
+                .balance(balance)
+                .balance_interval(interval)
+                .threads(5)
+                .turns(100)
+                .image_width(512)
+                .image_height(512);
+            log::debug!(target: "Test", "{} - {:?}", "Testing balanced strips".cyan(), Params::from(args.clone()));
+            let (_key_presses_tx, key_presses_rx) = flume::bounded::<Keycode>(10);
+            let (events_tx, events_rx) = flume::bounded::<Event>(1000);
+            tokio::spawn(gol::run(args.clone(), events_tx, key_presses_rx));
+            let mut final_turn_complete = false;
+            loop {
+                match events_rx.recv_async().await {
+                    Ok(Event::FinalTurnComplete { completed_turns, alive }) => {
+                        final_turn_complete = true;
+                        assert_eq!(completed_turns, 100);
+                        assert_eq_board(args.clone(), &alive, &expected_alive);
+                    },
+                    Ok(Event::StateChange { new_state: State::Quitting, .. }) if final_turn_complete => break,
+                    Err(_) => panic!("No FinalTurnComplete events received {:?}", Params::from(args)),
+                    _ => (),
+                };
+            }
+            passed_tests += 1;
+        }
+    }
+    Ok(passed_tests)
+}
+
+/// With a glider gun in the top rows of an otherwise empty plane, balancing by work gives the rows
+/// around the gun to more workers, and cuts the same run the same way every time.
+fn test_sparse() -> Result<usize> {
+    log::debug!(target: "Test", "{}", "Testing balanced strips on a glider gun".cyan());
+    let world = glider_gun(256, 256);
+    let threads = 8;
+    let build = |balance| -> Result<Parallel> {
+        let mut engine = Parallel::new(threads, Rule::CONWAY, Topology::Plane)?.balance(balance, 4);
+        engine.load(&world)?;
+        Ok(engine)
+    };
+    let (mut work, mut again, mut time) = (build(Balance::Work)?, build(Balance::Work)?, build(Balance::Time)?);
+    let mut naive = Naive::new(Rule::CONWAY, Topology::Plane);
+    naive.load(&world)?;
+    for _ in 0..60 {
+        for engine in [&mut work as &mut dyn Engine, &mut again, &mut time, &mut naive] {
+            engine.step(1)?;
+        }
+        assert_eq!(work.strips(), again.strips(), "Balancing by work should cut the same run the same way");
+        for engine in [&work, &time] {
+            let strips = engine.strips();
+            assert_eq!(strips.len(), threads);
+            assert_eq!(strips[0].0, 0);
+            assert_eq!(strips[threads - 1].1, 256);
+            assert!(strips.windows(2).all(|pair| pair[0].1 == pair[1].0 && pair[0].0 < pair[0].1), "Strips {:?}", strips);
+        }
+        assert_eq!(work.diff(), naive.diff());
+        assert_eq!(time.diff(), naive.diff());
+    }
+    let (start, end) = work.strips()[0];
+    assert!(end - start < 256 / threads, "The strip holding the gun should be shorter than the rest, not {:?}", work.strips());
+    assert_eq!(work.world(), naive.world());
+    assert_eq!(time.world(), naive.world());
+    Ok(1)
+}
+
@@ -0,0 +1,71 @@
+use anyhow::Result;
+use colored::Colorize;
+use gol_rs::{args::Args, gol::{rule::Rule, topology::Topology}, util::{bitworld::BitWorld, cell::{CellCoord, CellValue}, logger}};
+use image::ImageReader;
+use log::Level;
+use utils::{io::read_alive_cells, visualise::assert_eq_board};
+
+mod utils;
+
+#[tokio::main]
+async fn main() {
+    let start = std::time::Instant::now();
+    logger::set_panic_hook();
+    logger::init(Level::Debug, false);
+
+    let passed_tests = test_round_trip().unwrap() + test_step().unwrap();
+
+    println!(
+        "\ntest result: {}. {} passed; finished in {:.2}s\n",
+        "ok".green(),
+        passed_tests,
+        start.elapsed().as_secs_f32()
+    );
+    std::process::exit(0);
+}
+
+/// Packing and unpacking must be lossless, including widths that are not a multiple of 64.
+fn test_round_trip() -> Result<usize> {
+    let mut passed_tests = 0;
+    for (width, height) in [(16_usize, 16_usize), (64, 64), (100, 3), (512, 512)] {
+        log::debug!(target: "Test", "{} - {}x{}", "Testing BitWorld round trip".cyan(), width, height);
+        let cells = (0..width * height)
+            .map(|i| if (i * 7 + i / width) % 3 == 0 { CellValue::Alive } else { CellValue::Dead })
+            .collect::<Vec<_>>();
+        let bits = BitWorld::from_cells(width, height, &cells);
+        assert_eq!(bits.to_cells(), cells, "Round trip changed the world");
+        assert_eq!(
+            bits.population(),
+            cells.iter().filter(|cell| cell.is_alive()).count(),
+            "Population does not match"
+        );
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
+
+/// BitWorld stepping is checked against the 16x16, 64x64 and 512x512 goldens on 0, 1 and 100 turns.
+fn test_step() -> Result<usize> {
+    let mut passed_tests = 0;
+    for (width, height) in [(16_usize, 16_usize), (64, 64), (512, 512)] {
+        let input = ImageReader::open(format!("images/{}x{}.pgm", width, height))?.decode()?;
+        let mut world = BitWorld::from_bytes(width, height, input.as_bytes());
+        let mut completed_turns = 0;
+        for turns in [0_usize, 1, 100] {
+            log::debug!(target: "Test", "{} - {}x{}x{}", "Testing BitWorld step".cyan(), width, height, turns);
+            while completed_turns < turns {
+                world = world.step(&Rule::CONWAY, Topology::Torus);
+                completed_turns += 1;
+            }
+            let expected = read_alive_cells(
+                format!("check/images/{}x{}x{}.pgm", width, height, turns), width, height)?;
+            let args = Args::default().turns(turns).image_width(width).image_height(height);
+            let alive = world.alive_cells().into_iter()
+                .map(|cell| cell.offset(CellCoord::new(0, 0)))
+                .collect::<Vec<_>>();
+            assert_eq_board(args, &alive, &expected);
+            passed_tests += 1;
+        }
+    }
+    Ok(passed_tests)
+}
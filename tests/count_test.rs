@@ -72,6 +72,7 @@ async fn test_alive(
                 }
                 ddl.abort();
 
+                #[allow(clippy::manual_is_multiple_of)]
                 let expected = if completed_turns <= 10000 {
                     *alive_map.get(&completed_turns).unwrap()
                 } else if completed_turns % 2 == 0 { 5565 } else { 5567 };
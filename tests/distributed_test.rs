@@ -0,0 +1,575 @@
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use gol_rs::args::Args;
+use gol_rs::gol::{self, engine::EngineKind, event::{Event, State}, Params};
+use gol_rs::gol::engine::{distributed::Distributed, Engine};
+use gol_rs::gol::protocol::{self, Load, Reply, Request, Stepped};
+use gol_rs::gol::rule::Rule;
+use gol_rs::gol::topology::Topology;
+use gol_rs::gol::worker;
+use gol_rs::util::{cell::{CellCoord, CellState}, logger};
+use log::Level;
+use sdl2::keyboard::Keycode;
+use std::io::{BufRead, BufReader, Cursor};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+use utils::{common::deadline, io::read_alive_cells, visualise::assert_eq_board};
+
+mod utils;
+
+#[tokio::main]
+async fn main() {
+    let start = std::time::Instant::now();
+    logger::set_panic_hook();
+    logger::init(Level::Debug, false);
+
+    let mut workers = Workers::start(3).unwrap();
+    let passed_tests = test_protocol().unwrap()
+        + test_engines(&workers.addresses).unwrap()
+        + test_traffic(&workers.addresses).unwrap()
+        + test_gol(Args::default().workers(workers.addresses.clone())).await.unwrap()
+        + test_keys(Args::default().workers(workers.addresses.clone()), &mut workers).await.unwrap()
+        + test_broker(&mut workers).unwrap()
+        + test_faults(Args::default()).await.unwrap()
+        + test_frozen_load().unwrap()
+        + test_failures().unwrap()
+        + test_kill(Args::default()).await.unwrap();
+    // Exiting skips destructors, so stop the workers first.
+    drop(workers);
+
+    println!(
+        "\ntest result: {}. {} passed; finished in {:.2}s\n",
+        "ok".green(),
+        passed_tests,
+        start.elapsed().as_secs_f32()
+    );
+    std::process::exit(0);
+}
+
+/// Workers are worker processes listening on free ports of 127.0.0.1.
+struct Workers {
+    children: Vec<Child>,
+    addresses: Vec<SocketAddr>,
+}
+
+impl Workers {
+    fn start(count: usize) -> Result<Self> {
+        let mut workers = Workers { children: Vec::new(), addresses: Vec::new() };
+        for _ in 0..count {
+            let mut child = Command::new(env!("CARGO_BIN_EXE_worker"))
+                .args(["--address", "127.0.0.1:0"])
+                .stdout(Stdio::piped())
+                .spawn()?;
+            let mut line = String::new();
+            BufReader::new(child.stdout.take().context("The worker has no stdout")?).read_line(&mut line)?;
+            let address = line.trim().strip_prefix("Worker listening on ")
+                .with_context(|| format!("The worker did not print its address: {:?}", line))?;
+            workers.addresses.push(address.parse()?);
+            workers.children.push(child);
+        }
+        Ok(workers)
+    }
+
+    /// Whether every worker is still running.
+    fn running(&mut self) -> Result<bool> {
+        for child in &mut self.children {
+            if child.try_wait()?.is_some() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Kill the `i`th worker outright, as if its machine had gone away.
+    fn kill(&mut self, i: usize) -> Result<()> {
+        self.children[i].kill()?;
+        self.children[i].wait()?;
+        Ok(())
+    }
+
+    /// Stop the `i`th worker without closing its connections, so that it is only noticed by its silence.
+    fn freeze(&mut self, i: usize) -> Result<()> {
+        let status = Command::new("kill").args(["-STOP", &self.children[i].id().to_string()]).status()?;
+        assert!(status.success(), "Cannot stop the worker: {}", status);
+        Ok(())
+    }
+
+    /// Wait up to `timeout` for every worker to exit.
+    fn wait(&mut self, timeout: Duration) -> Result<Vec<ExitStatus>> {
+        let start = Instant::now();
+        let mut statuses = Vec::new();
+        for child in &mut self.children {
+            loop {
+                if let Some(status) = child.try_wait()? {
+                    statuses.push(status);
+                    break;
+                }
+                if start.elapsed() > timeout {
+                    bail!("The worker {} did not exit within {:?}", child.id(), timeout);
+                }
+                std::thread::sleep(Duration::from_millis(20));
+            }
+        }
+        Ok(statuses)
+    }
+}
+
+impl Drop for Workers {
+    fn drop(&mut self) {
+        for child in &mut self.children {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Requests and replies read back as they were written, a closed connection reads as no request,
+/// and strips that do not fit the world are rejected.
+fn test_protocol() -> Result<usize> {
+    log::debug!(target: "Test", "{}", "Testing protocol".cyan());
+    let load = Load {
+        rule: "B2/S/C3".parse::<Rule>()?,
+        topology: Topology::TwistedTorus(3),
+        width: 6,
+        height: 5,
+        index: 1,
+        epoch: 4,
+        timeout: Duration::from_millis(1500),
+        strips: vec![(0, 2), (2, 5)],
+        peers: vec!["127.0.0.1:8030".parse()?, "[::1]:8031".parse()?],
+        cells: (0..18).map(|i| CellState::new(i % 3)).collect(),
+    };
+    let mut bytes = Vec::new();
+    protocol::write_request(&mut bytes, &Request::Load(Box::new(load.clone())))?;
+    protocol::write_request(&mut bytes, &Request::Step { turns: 7 })?;
+    protocol::write_request(&mut bytes, &Request::Shutdown)?;
+    let mut reader = Cursor::new(bytes);
+    assert_eq!(protocol::read_request(&mut reader)?, Some(Request::Load(Box::new(load.clone()))));
+    assert_eq!(protocol::read_request(&mut reader)?, Some(Request::Step { turns: 7 }));
+    assert_eq!(protocol::read_request(&mut reader)?, Some(Request::Shutdown));
+    assert_eq!(protocol::read_request(&mut reader)?, None);
+
+    let stepped = Stepped {
+        population: 12,
+        halo_bytes: 1 << 40,
+        flips: vec![(CellCoord::new(3, 4), CellState::new(2)), (CellCoord::new(0, 0), CellState::DEAD)],
+    };
+    let replies = [Reply::Heartbeat, Reply::Stepped(stepped), Reply::Failed("Lost a peer".to_owned())];
+    let mut bytes = Vec::new();
+    for reply in &replies {
+        protocol::write_reply(&mut bytes, reply)?;
+    }
+    let mut reader = Cursor::new(bytes);
+    for reply in replies {
+        assert_eq!(protocol::read_reply(&mut reader, 2)?, reply);
+    }
+    let error = protocol::read_reply(&mut Cursor::new(vec![1]), 2).expect_err("The reply should be unknown");
+    assert!(error.to_string().contains("unknown reply"), "Unexpected error: {}", error);
+    let mut bytes = Vec::new();
+    protocol::write_reply(&mut bytes, &Reply::Stepped(Stepped { flips: vec![(CellCoord::new(0, 0), CellState::ALIVE); 3], ..Stepped::default() }))?;
+    let error = protocol::read_reply(&mut Cursor::new(bytes), 2).expect_err("A strip of 2 cells cannot flip 3");
+    assert!(error.to_string().contains("at most 2"), "Unexpected error: {}", error);
+
+    let mut bytes = Vec::new();
+    protocol::write_request(&mut bytes, &Request::Load(Box::new(Load { height: 4, ..load.clone() })))?;
+    let error = protocol::read_request(&mut Cursor::new(bytes)).expect_err("The strip should not fit");
+    assert!(error.to_string().contains("does not fit a 6x4 world"), "Unexpected error: {}", error);
+    // A strip claiming billions of cells is refused before they are allocated.
+    let mut bytes = Vec::new();
+    protocol::write_request(&mut bytes, &Request::Load(Box::new(Load { cells: Vec::new(), ..load.clone() })))?;
+    bytes.truncate(bytes.len() - 4);
+    bytes.extend(u32::MAX.to_le_bytes());
+    let error = protocol::read_request(&mut Cursor::new(bytes)).expect_err("The strip should be too large");
+    assert!(error.to_string().contains("at most 18"), "Unexpected error: {}", error);
+    // A huge world whose strip count fits it only costs the few cells that actually arrive.
+    let mut bytes = Vec::new();
+    protocol::write_request(&mut bytes, &Request::Load(Box::new(Load { width: 1 << 30, cells: Vec::new(), ..load })))?;
+    bytes.truncate(bytes.len() - 4);
+    bytes.extend((3_u32 << 30).to_le_bytes());
+    bytes.extend([1, 2, 0]);
+    let error = protocol::read_request(&mut Cursor::new(bytes)).expect_err("The strip should be cut short");
+    let kind = error.downcast_ref::<std::io::Error>().map(std::io::Error::kind);
+    assert_eq!(kind, Some(std::io::ErrorKind::UnexpectedEof), "Unexpected error: {}", error);
+    let error = protocol::read_request(&mut Cursor::new(vec![9])).expect_err("The request should be unknown");
+    assert!(error.to_string().contains("unknown request"), "Unexpected error: {}", error);
+    Ok(1)
+}
+
+/// A pseudo-random world with about a third of the cells in each state.
+fn soup(width: usize, height: usize, states: u8) -> Vec<Vec<CellState>> {
+    let mut seed = 0x2545_F491_4F6C_DD1D_u64;
+    (0..height).map(|_| (0..width).map(|_| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        CellState::new(if seed.is_multiple_of(3) { (seed / 3 % states as u64) as u8 } else { 0 })
+    }).collect()).collect()
+}
+
+/// The workers step the same worlds as the naive engine, for every kind of rule and topology,
+/// and for worlds with fewer rows than workers.
+fn test_engines(workers: &[SocketAddr]) -> Result<usize> {
+    let mut passed_tests = 0;
+    let cases = [
+        ("B3/S23", Topology::Torus, 70, 33),
+        ("B3/S23", Topology::Torus, 16, 2),
+        ("B36/S23", Topology::Plane, 64, 64),
+        ("B2/S/C3", Topology::Klein, 70, 33),
+        ("B2-a/S12", Topology::CrossSurface, 64, 64),
+        ("B3/S23", Topology::TwistedTorus(5), 70, 33),
+        ("R5,C0,M1,S34..58,B34..45,NM", Topology::Torus, 64, 64),
+        ("R2,C3,M0,S2..5,B3..4,NN", Topology::Plane, 33, 20),
+        ("B2/S34H", Topology::Torus, 64, 64),
+        ("B2/S34H", Topology::Plane, 33, 17),
+        ("B3/S234L", Topology::Torus, 64, 64),
+        ("B3/S234L", Topology::Plane, 35, 21),
+    ];
+    for (rulestring, topology, width, height) in cases {
+        let rule = rulestring.parse::<Rule>()?;
+        let params = Params::from(Args::default()
+            .rule(rule)
+            .topology(topology)
+            .image_width(width)
+            .image_height(height)
+            .workers(workers.to_vec()));
+        log::debug!(target: "Test", "{} - {} {} {}x{}", "Testing distributed engine".cyan(), rule, topology, width, height);
+        let world = soup(width, height, rule.states());
+        let mut distributed = EngineKind::Distributed.build(&params)?;
+        let mut naive = EngineKind::Naive.build(&params)?;
+        distributed.load(&world)?;
+        naive.load(&world)?;
+        for turn in 1..=30 {
+            let turns = if turn % 10 == 0 { 3 } else { 1 };
+            distributed.step(turns)?;
+            naive.step(turns)?;
+            assert_eq!(distributed.world(), naive.world(), "{} on the {} topology differs at step {}", rule, topology, turn);
+            let mut diff = distributed.diff();
+            let mut expected = naive.diff();
+            diff.sort_by_key(|cell| (cell.y, cell.x));
+            expected.sort_by_key(|cell| (cell.y, cell.x));
+            assert_eq!(diff, expected, "{} on the {} topology changed different cells at step {}", rule, topology, turn);
+        }
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
+
+/// Workers only exchange the rows around their strips with each other, and the broker only sends
+/// the turns to take and gets back the cells that changed, rather than the whole world every turn.
+fn test_traffic(workers: &[SocketAddr]) -> Result<usize> {
+    log::debug!(target: "Test", "{}", "Testing distributed traffic".cyan());
+    let size = 512;
+    let world = soup(size, size, 2);
+    let mut engine = Distributed::new(workers, 0, Duration::from_secs(2), Rule::CONWAY, Topology::Torus)?;
+    engine.load(&world)?;
+    for _ in 0..10 {
+        engine.step(1)?;
+    }
+    let traffic = engine.traffic();
+    // Each of the 3 strips receives the row above and the row below it, with a cell either side,
+    // from the strips next to it, each as a cell count and the cells.
+    assert_eq!(traffic.halo, 10 * 3 * 2 * (4 + size as u64 + 2), "Unexpected halo traffic {:?}", traffic);
+
+    engine.step(5)?;
+    assert_eq!(engine.traffic().halo - traffic.halo, 5 * 3 * 2 * (4 + size as u64 + 2));
+    assert_eq!(engine.traffic().turns, 15);
+
+    // A lone glider flips a handful of cells a turn, so the broker moves far less than a row of the world.
+    let mut world = vec![vec![CellState::DEAD; size]; size];
+    for (x, y) in [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+        world[y + 100][x + 100] = CellState::ALIVE;
+    }
+    engine.load(&world)?;
+    for _ in 0..10 {
+        engine.step(1)?;
+    }
+    let traffic = engine.traffic();
+    assert!(traffic.broker < 10 * size as u64, "The broker moved too much: {:?}", traffic);
+    Ok(1)
+}
+
+/// Runs on the workers finish with the expected images, including unbounded ones.
+async fn test_gol(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    for (size, turns, unbounded) in [(16_usize, 0_usize, false), (16, 1, false), (16, 100, false), (64, 1, false), (64, 100, false), (512, 100, false), (16, 100, true)] {
+        let args = args.clone()
+            .engine(EngineKind::Distributed)
+            .turns(turns)
+            .image_width(size)
+            .image_height(size)
+            .unbounded(unbounded);
+        log::debug!(target: "Test", "{} - {:?}", "Testing distributed run".cyan(), Params::from(args.clone()));
+        let (_key_presses_tx, key_presses_rx) = flume::bounded::<Keycode>(10);
+        let (events_tx, events_rx) = flume::bounded::<Event>(1000);
+        let gol = tokio::spawn(gol::run(args.clone(), events_tx, key_presses_rx));
+        let mut alive = None;
+        while let Ok(event) = events_rx.recv_async().await {
+            if let Event::FinalTurnComplete { completed_turns, alive: cells } = event {
+                assert_eq!(completed_turns, turns as u32);
+                alive = Some(cells);
+            }
+        }
+        gol.await??;
+        let alive = alive.context("No FinalTurnComplete event received")?;
+        if unbounded {
+            // The glider leaves the 16x16 image to the south east instead of wrapping.
+            let expected = read_alive_cells("images/16x16.pgm", 16, 16)?.into_iter()
+                .map(|cell| CellCoord::new(cell.x + 25, cell.y + 25))
+                .collect::<Vec<_>>();
+            assert_eq!(alive.len(), expected.len());
+            assert!(expected.iter().all(|cell| alive.contains(cell)), "The glider is not where it should be");
+        } else {
+            let expected = read_alive_cells(format!("check/images/{}x{}x{}.pgm", size, size, turns), size, size)?;
+            assert_eq_board(args, &alive, &expected);
+        }
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Running,
+    Pausing,
+    Saving,
+    Resuming,
+    Quitting,
+}
+
+/// `P`, `S` and `Q` work on the workers as they do locally, and `Q` leaves the workers running.
+async fn test_keys(args: Args, workers: &mut Workers) -> Result<usize> {
+    let args = args
+        .engine(EngineKind::Distributed)
+        .turns(10_000_000)
+        .image_width(64)
+        .image_height(64);
+    log::debug!(target: "Test", "{} - {:?}", "Testing distributed keys".cyan(), Params::from(args.clone()));
+    let deadline = deadline(Duration::from_secs(30), "The distributed keys should be handled within 30 seconds");
+    let (key_presses_tx, key_presses_rx) = flume::bounded::<Keycode>(10);
+    let (events_tx, events_rx) = flume::bounded::<Event>(1000);
+    let gol = tokio::spawn(gol::run(args, events_tx, key_presses_rx));
+    let mut phase = Phase::Running;
+    let mut paused_at = 0;
+    while let Ok(event) = events_rx.recv_async().await {
+        match event {
+            Event::TurnComplete { completed_turns } if phase == Phase::Running && completed_turns >= 10 => {
+                key_presses_tx.send_async(Keycode::P).await?;
+                phase = Phase::Pausing;
+            },
+            Event::StateChange { completed_turns, new_state: State::Pause } => {
+                assert_eq!(phase, Phase::Pausing);
+                paused_at = completed_turns;
+                key_presses_tx.send_async(Keycode::S).await?;
+                phase = Phase::Saving;
+            },
+            Event::ImageOutputComplete { completed_turns, .. } if phase == Phase::Saving => {
+                assert_eq!(completed_turns, paused_at, "Saved a different turn while paused");
+                key_presses_tx.send_async(Keycode::P).await?;
+                phase = Phase::Resuming;
+            },
+            Event::StateChange { completed_turns, new_state: State::Executing } if phase == Phase::Resuming => {
+                assert_eq!(completed_turns, paused_at, "Turns were taken while paused");
+                key_presses_tx.send_async(Keycode::Q).await?;
+                phase = Phase::Quitting;
+            },
+            Event::FinalTurnComplete { completed_turns, .. } => {
+                assert_eq!(phase, Phase::Quitting);
+                assert!(completed_turns >= paused_at);
+            },
+            _ => (),
+        }
+    }
+    gol.await??;
+    deadline.abort();
+    assert_eq!(phase, Phase::Quitting);
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(workers.running()?, "Quitting with Q should leave the workers running");
+    Ok(1)
+}
+
+/// The broker binary runs headless on the workers and writes the final image.
+/// Quitting at the end of the run leaves the workers running for the next broker.
+fn test_broker(workers: &mut Workers) -> Result<usize> {
+    log::debug!(target: "Test", "{}", "Testing broker binary".cyan());
+    let addresses = workers.addresses.iter().map(ToString::to_string).collect::<Vec<_>>().join(",");
+    let status = Command::new(env!("CARGO_BIN_EXE_broker"))
+        .args(["--headless", "--turns", "100", "-w", "64", "-h", "64", "--workers", &addresses])
+        .stdout(Stdio::null())
+        .status()?;
+    assert!(status.success(), "The broker exited with {}", status);
+    let expected = read_alive_cells("check/images/64x64x100.pgm", 64, 64)?;
+    let written = read_alive_cells("out/64x64x100.pgm", 64, 64)?;
+    assert_eq!(written, expected, "The broker wrote the wrong image");
+    assert!(workers.running()?, "The broker should leave the workers running");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_broker"))
+        .args(["--headless", "--turns", "1"])
+        .stdout(Stdio::null())
+        .output()?;
+    assert_eq!(output.status.code(), Some(2), "The broker should refuse to run without workers");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("required arguments were not provided") && stderr.contains("--workers"), "Unexpected error: {}", stderr);
+    Ok(2)
+}
+
+/// A run that loses one worker outright and has another stop responding carries on
+/// on the worker left and finishes with the same image as it would have without them.
+async fn test_faults(args: Args) -> Result<usize> {
+    let mut workers = Workers::start(3)?;
+    let args = args
+        .engine(EngineKind::Distributed)
+        .workers(workers.addresses.clone())
+        .worker_timeout(500)
+        .turns(100)
+        .image_width(512)
+        .image_height(512);
+    log::debug!(target: "Test", "{} - {:?}", "Testing distributed faults".cyan(), Params::from(args.clone()));
+    let deadline = deadline(Duration::from_secs(60), "Recovering from lost workers should complete within 60 seconds");
+    let (_key_presses_tx, key_presses_rx) = flume::bounded::<Keycode>(10);
+    let (events_tx, events_rx) = flume::bounded::<Event>(1000);
+    let gol = tokio::spawn(gol::run(args.clone(), events_tx, key_presses_rx));
+    let (mut killed, mut frozen, mut alive) = (false, false, None);
+    while let Ok(event) = events_rx.recv_async().await {
+        match event {
+            Event::TurnComplete { completed_turns } if !killed && completed_turns >= 10 => {
+                workers.kill(0)?;
+                killed = true;
+            },
+            Event::TurnComplete { completed_turns } if !frozen && completed_turns >= 40 => {
+                workers.freeze(1)?;
+                frozen = true;
+            },
+            Event::FinalTurnComplete { completed_turns, alive: cells } => {
+                assert_eq!(completed_turns, 100);
+                alive = Some(cells);
+            },
+            _ => (),
+        }
+    }
+    gol.await??;
+    deadline.abort();
+    assert!(killed && frozen, "The run finished before the workers could be lost");
+    let expected = read_alive_cells("check/images/512x512x100.pgm", 512, 512)?;
+    assert_eq_board(args, &alive.context("No FinalTurnComplete event received")?, &expected);
+    Ok(1)
+}
+
+/// A worker that stops responding while the broker is still sending it a strip too large for the
+/// socket buffers is dropped like one that stops during a step, instead of blocking the broker.
+fn test_frozen_load() -> Result<usize> {
+    log::debug!(target: "Test", "{}", "Testing a worker frozen during a load".cyan());
+    let mut workers = Workers::start(3)?;
+    let mut engine = Distributed::new(&workers.addresses, 0, Duration::from_secs(2), Rule::CONWAY, Topology::Torus)?;
+    engine.load(&soup(64, 64, 2))?;
+    engine.step(1)?;
+
+    // Each strip of the large world is megabytes more than loopback socket buffers hold.
+    let mut world = vec![vec![CellState::DEAD; 8192]; 6144];
+    for (x, y) in [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+        world[y + 100][x + 100] = CellState::ALIVE;
+    }
+    workers.freeze(1)?;
+    let deadline = deadline(Duration::from_secs(60), "Loading the workers left should not wait on the frozen worker");
+    engine.load(&world)?;
+    engine.step(1)?;
+    deadline.abort();
+    assert_eq!(engine.workers(), 2, "The frozen worker should have been dropped");
+    let expected = [(0, 1), (2, 1), (1, 2), (2, 2), (1, 3)].map(|(x, y)| CellCoord::new(x + 100, y + 100));
+    assert_eq!(engine.alive_cells(), expected, "The glider did not move as it should");
+    Ok(1)
+}
+
+/// Workers that fail a step without being lost, as when the halo link between two of them breaks,
+/// are loaded again and take the turns again, and workers that keep failing end the run.
+fn test_failures() -> Result<usize> {
+    log::debug!(target: "Test", "{}", "Testing distributed failures".cyan());
+    let params = Params::from(Args::default().image_width(64).image_height(64));
+    let world = soup(64, 64, 2);
+    let addresses = [failing_worker(2)?, failing_worker(0)?];
+    let mut distributed = Distributed::new(&addresses, 0, Duration::from_millis(500), Rule::CONWAY, Topology::Torus)?;
+    let mut naive = EngineKind::Naive.build(&params)?;
+    distributed.load(&world)?;
+    naive.load(&world)?;
+    for turn in 1..=10 {
+        distributed.step(1)?;
+        naive.step(1)?;
+        assert_eq!(distributed.world(), naive.world(), "The world differs at turn {}", turn);
+    }
+    assert_eq!(distributed.workers(), 2, "Failing workers should not be dropped");
+
+    let mut distributed = Distributed::new(&[failing_worker(usize::MAX)?], 0, Duration::from_millis(500), Rule::CONWAY, Topology::Torus)?;
+    distributed.load(&world)?;
+    let error = distributed.step(1).expect_err("A worker that always fails should end the run");
+    assert!(error.to_string().contains("failed 4 times in a row"), "Unexpected error: {}", error);
+    Ok(2)
+}
+
+/// Start a worker on threads of this process that answers its first `failures` steps with `Failed`,
+/// as a worker that lost a peer would, and otherwise passes requests and replies through to a real worker.
+fn failing_worker(failures: usize) -> Result<SocketAddr> {
+    let (listener, upstream) = (TcpListener::bind("127.0.0.1:0")?, TcpListener::bind("127.0.0.1:0")?);
+    let (address, upstream_address) = (listener.local_addr()?, upstream.local_addr()?);
+    std::thread::spawn(move || upstream.accept().map(|(stream, _)| worker::serve_connection(stream)));
+    std::thread::spawn(move || -> Result<()> {
+        let (mut broker, _) = listener.accept()?;
+        let mut upstream = TcpStream::connect(upstream_address)?;
+        let (mut from_broker, mut from_worker) = (BufReader::new(broker.try_clone()?), BufReader::new(upstream.try_clone()?));
+        protocol::write_address(&mut broker, protocol::read_address(&mut from_worker)?)?;
+        let mut failures = failures;
+        while let Some(request) = protocol::read_request(&mut from_broker)? {
+            if matches!(request, Request::Step { .. }) && failures > 0 {
+                failures -= 1;
+                protocol::write_reply(&mut broker, &Reply::Failed("Lost a peer".to_owned()))?;
+                continue;
+            }
+            protocol::write_request(&mut upstream, &request)?;
+            if request == Request::Shutdown {
+                return Ok(());
+            }
+            loop {
+                let reply = protocol::read_reply(&mut from_worker, 64 * 64)?;
+                protocol::write_reply(&mut broker, &reply)?;
+                if reply != Reply::Heartbeat {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    });
+    Ok(address)
+}
+
+/// `K` finishes the run as `Q` does and then shuts every worker down cleanly.
+async fn test_kill(args: Args) -> Result<usize> {
+    let mut workers = Workers::start(3)?;
+    let args = args
+        .engine(EngineKind::Distributed)
+        .workers(workers.addresses.clone())
+        .turns(10_000_000)
+        .image_width(64)
+        .image_height(64);
+    log::debug!(target: "Test", "{} - {:?}", "Testing distributed kill".cyan(), Params::from(args.clone()));
+    let deadline = deadline(Duration::from_secs(30), "Killing the workers should complete within 30 seconds");
+    let (key_presses_tx, key_presses_rx) = flume::bounded::<Keycode>(10);
+    let (events_tx, events_rx) = flume::bounded::<Event>(1000);
+    let gol = tokio::spawn(gol::run(args, events_tx, key_presses_rx));
+    let (mut killed, mut final_turn_complete, mut quitting) = (false, false, false);
+    while let Ok(event) = events_rx.recv_async().await {
+        match event {
+            Event::TurnComplete { completed_turns } if !killed && completed_turns >= 10 => {
+                key_presses_tx.send_async(Keycode::K).await?;
+                killed = true;
+            },
+            Event::FinalTurnComplete { .. } => final_turn_complete = true,
+            Event::StateChange { new_state: State::Quitting, .. } => quitting = final_turn_complete,
+            _ => (),
+        }
+    }
+    gol.await??;
+    assert!(quitting, "K should finish the run with FinalTurnComplete and Quitting");
+    let statuses = workers.wait(Duration::from_secs(10))?;
+    assert!(statuses.iter().all(ExitStatus::success), "The workers did not exit cleanly: {:?}", statuses);
+    deadline.abort();
+    Ok(1)
+}
@@ -0,0 +1,125 @@
+#[cfg(target_os = "linux")]
+use anyhow::Context;
+use anyhow::Result;
+use clap::ValueEnum;
+use colored::Colorize;
+use gol_rs::{args::Args, gol::{self, engine::EngineKind, event::{Event, State}, Params}, util::logger};
+#[cfg(target_os = "linux")]
+use gol_rs::gol::engine::{shared::SharedMemory, Engine};
+#[cfg(target_os = "linux")]
+use gol_rs::gol::{rule::Rule, topology::Topology};
+#[cfg(target_os = "linux")]
+use gol_rs::util::cell::CellState;
+use log::Level;
+use sdl2::keyboard::Keycode;
+#[cfg(target_os = "linux")]
+use std::process::{Command, Stdio};
+use utils::{io::read_alive_cells, visualise::assert_eq_board};
+
+mod utils;
+
+#[tokio::main]
+async fn main() {
+    let start = std::time::Instant::now();
+    logger::set_panic_hook();
+    logger::init(Level::Debug, false);
+
+    let passed_tests = test_engines(Args::default()).await.unwrap();
+    #[cfg(target_os = "linux")]
+    let passed_tests = passed_tests + test_processes().unwrap();
+
+    println!(
+        "\ntest result: {}. {} passed; finished in {:.2}s\n",
+        "ok".green(),
+        passed_tests,
+        start.elapsed().as_secs_f32()
+    );
+    std::process::exit(0);
+}
+
+/// Engine tests run every engine on 16x16, 64x64 and 512x512 images on 0, 1 and 100 turns
+/// using 1 and 3 worker threads.
+async fn test_engines(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    let size = [(16_usize, 16_usize), (64, 64), (512, 512)];
+    let turns = [0_usize, 1, 100];
+
+    for &engine in EngineKind::value_variants() {
+        if cfg!(not(target_os = "linux")) && matches!(engine, EngineKind::SharedMemory) {
+            continue;
+        }
+        for (width, height) in size {
+            for expected_turns in turns {
+                let path = format!("check/images/{}x{}x{}.pgm", width, height, expected_turns);
+                let expected_alive = read_alive_cells(path, width, height)?;
+                for thread in [1, 3] {
+                    let args = args.clone()
+                        .engine(engine)
+                        .turns(expected_turns)
+                        .threads(thread)
+                        .image_width(width)
+                        .image_height(height);
+                    log::debug!(target: "Test", "{} - {:?}", "Testing Engine".cyan(), Params::from(args.clone()));
+                    let (_key_presses_tx, key_presses_rx) = flume::bounded::<Keycode>(10);
+                    let (events_tx, events_rx) = flume::bounded::<Event>(1000);
+                    tokio::spawn(gol::run(args.clone(), events_tx, key_presses_rx));
+                    let mut final_turn_complete = false;
+                    loop {
+                        match events_rx.recv_async().await {
+                            Ok(Event::FinalTurnComplete { completed_turns, alive }) => {
+                                final_turn_complete = true;
+                                assert_eq!(
+                                    completed_turns, expected_turns as u32,
+                                    "Expected completed turns is {}, but got {}", expected_turns, completed_turns
+                                );
+                                assert_eq_board(args.clone(), &alive, &expected_alive);
+                            },
+                            Ok(Event::StateChange { new_state: State::Quitting, .. }) if final_turn_complete => break,
+                            Err(_) => panic!("No FinalTurnComplete events received {:?}", Params::from(args)),
+                            _ => (),
+                        };
+                    }
+                    passed_tests += 1;
+                }
+            }
+        }
+    }
+    Ok(passed_tests)
+}
+
+/// The shared memory engine forks a process per strip, forks them again when the world changes size,
+/// reports a process that exits rather than waiting on it, and leaves none behind when dropped.
+#[cfg(target_os = "linux")]
+fn test_processes() -> Result<usize> {
+    log::debug!(target: "Test", "{}", "Testing shared memory processes".cyan());
+    let world = |width: usize, height: usize| -> Result<Vec<Vec<CellState>>> {
+        let mut world = vec![vec![CellState::DEAD; width]; height];
+        let path = format!("check/images/{}x{}x0.pgm", width, height);
+        for cell in read_alive_cells(path, width, height)? {
+            world[cell.y as usize][cell.x as usize] = CellState::ALIVE;
+        }
+        Ok(world)
+    };
+    let running = |pid: i32| Command::new("kill").args(["-0", &pid.to_string()]).stderr(Stdio::null()).status().is_ok_and(|status| status.success());
+
+    let mut engine = SharedMemory::new(3, Rule::CONWAY, Topology::Torus)?;
+    engine.load(&world(16, 16)?)?;
+    let first = engine.children().to_vec();
+    assert_eq!(first.len(), 3);
+    engine.step(1)?;
+    assert_eq!(engine.population(), read_alive_cells("check/images/16x16x1.pgm", 16, 16)?.len() as u64);
+
+    engine.load(&world(64, 64)?)?;
+    assert!(first.iter().all(|&pid| !running(pid)), "The processes for the old size should be gone");
+    let second = engine.children().to_vec();
+    engine.step(100)?;
+    assert_eq!(engine.population(), read_alive_cells("check/images/64x64x100.pgm", 64, 64)?.len() as u64);
+
+    let status = Command::new("kill").args(["-KILL", &second[1].to_string()]).status()?;
+    assert!(status.success(), "Cannot kill the process: {}", status);
+    let error = engine.step(1).err().context("Stepping without one of the processes should fail")?;
+    assert!(error.to_string().contains("exited"), "Unexpected error: {}", error);
+    drop(engine);
+    assert!(second.iter().all(|&pid| !running(pid)), "Dropping the engine should leave no processes");
+    Ok(1)
+}
@@ -0,0 +1,45 @@
+use anyhow::Result;
+use colored::Colorize;
+use gol_rs::{args::Args, gol::{engine::EngineKind, Params}, util::logger};
+use log::Level;
+use utils::{io::read_alive_counts, run::{run, Outcome}};
+
+mod utils;
+
+#[tokio::main]
+async fn main() {
+    let start = std::time::Instant::now();
+    logger::set_panic_hook();
+    logger::init(Level::Debug, false);
+    let args = Args::default().engine(EngineKind::HashLife);
+
+    let passed_tests = test_long_run(args).await.unwrap();
+
+    println!(
+        "\ntest result: {}. {} passed; finished in {:.2}s\n",
+        "ok".green(),
+        passed_tests,
+        start.elapsed().as_secs_f32()
+    );
+    std::process::exit(0);
+}
+
+/// HashLife reaches long runs on 512x512 with the alive counts from check/alive.
+async fn test_long_run(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    let alive_map = read_alive_counts(512, 512)?;
+    for turns in [777_u32, 10000, 1_000_001, 10_000_000] {
+        let args = args.clone().turns(turns as usize).image_width(512).image_height(512);
+        log::debug!(target: "Test", "{} - {:?}", "Testing HashLife long run".cyan(), Params::from(args.clone()));
+        let Outcome { completed_turns, alive, .. } = run(args).await?;
+        let expected = alive_map.get(&turns).copied()
+            .unwrap_or(if turns.is_multiple_of(2) { 5565 } else { 5567 });
+        assert_eq!(completed_turns, turns, "Expected completed turns is {}, but got {}", turns, completed_turns);
+        assert_eq!(
+            alive.len(), expected as usize,
+            "At turn {} expected {} alive cells, got {} instead", turns, expected, alive.len()
+        );
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
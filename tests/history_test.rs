@@ -0,0 +1,219 @@
+use anyhow::Result;
+use colored::Colorize;
+use gol_rs::args::Args;
+use gol_rs::gol::{self, engine::EngineKind, event::{Event, State}, Params};
+use gol_rs::gol::history::{Delta, History};
+use gol_rs::gol::rule::Rule;
+use gol_rs::util::{cell::{CellCoord, CellState}, logger};
+use log::Level;
+use sdl2::keyboard::Keycode;
+use std::collections::HashMap;
+use std::time::Duration;
+use utils::{common::deadline, io::read_alive_cells};
+
+mod utils;
+
+#[tokio::main]
+async fn main() {
+    let start = std::time::Instant::now();
+    logger::set_panic_hook();
+    logger::init(Level::Debug, false);
+
+    let passed_tests = test_history()
+        + test_previous_state().unwrap()
+        + test_step_back(Args::default()).await.unwrap();
+
+    println!(
+        "\ntest result: {}. {} passed; finished in {:.2}s\n",
+        "ok".green(),
+        passed_tests,
+        start.elapsed().as_secs_f32()
+    );
+    std::process::exit(0);
+}
+
+/// The history keeps the most recent steps up to its capacity, and nothing at all with none.
+fn test_history() -> usize {
+    log::debug!(target: "Test", "{}", "Testing history ring buffer".cyan());
+    let delta = |turns| Delta { turns, cells: vec![(CellCoord::new(-1, 2), 1)] };
+    let mut history = History::new(3);
+    (1..=5).for_each(|turns| history.record(delta(turns)));
+    assert_eq!(history.len(), 3);
+    assert_eq!(history.pop(), Some(delta(5)));
+    assert_eq!(history.pop(), Some(delta(4)));
+    assert_eq!(history.pop(), Some(delta(3)));
+    assert_eq!(history.pop(), None);
+
+    let mut history = History::new(0);
+    history.record(delta(1));
+    assert!(!history.is_enabled() && history.is_empty());
+    1
+}
+
+/// Every state a cell can change to in one turn leads back to the state it changed from.
+fn test_previous_state() -> Result<usize> {
+    let mut passed_tests = 0;
+    for rulestring in ["B3/S23", "B2/S/C3", "B2/S345/C6", "B2/S34H", "B2-a/S12/C4"] {
+        log::debug!(target: "Test", "{} - {}", "Testing previous state".cyan(), rulestring);
+        let rule = rulestring.parse::<Rule>()?;
+        for state in (0..rule.states()).map(CellState::new) {
+            for neighbours in 0..=rule.max_neighbours() {
+                let next = rule.next_state(state, neighbours);
+                if next != state {
+                    assert_eq!(rule.previous_state(next), state, "{} does not undo {} to {}", rule, state, next);
+                }
+            }
+        }
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
+
+/// Pause, step back past the end of the history, save, resume and quit, checking that every turn
+/// stepped back to looks as it did the first time, that saving writes the turn stepped back to,
+/// and that the turns taken again after resuming come out the same.
+async fn test_step_back(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    let mut cases = vec![
+        (EngineKind::Parallel, Rule::CONWAY, false, 5),
+        (EngineKind::Naive, "B2/S/C3".parse::<Rule>()?, false, 5),
+        (EngineKind::BitPacked, Rule::CONWAY, false, 5),
+        (EngineKind::HashLife, Rule::CONWAY, false, 5),
+        (EngineKind::Parallel, "B2/S34H".parse::<Rule>()?, false, 5),
+        (EngineKind::Parallel, Rule::CONWAY, true, 5),
+        (EngineKind::Parallel, Rule::CONWAY, false, 0),
+    ];
+    if cfg!(target_os = "linux") {
+        cases.push((EngineKind::SharedMemory, Rule::CONWAY, false, 5));
+    }
+    for (engine, rule, unbounded, history) in cases {
+        let args = args.clone()
+            .engine(engine)
+            .rule(rule)
+            .unbounded(unbounded)
+            .history(history)
+            .turns(10_000_000)
+            .image_width(64)
+            .image_height(64);
+        log::debug!(target: "Test", "{} - {:?}", "Testing step back".cyan(), Params::from(args.clone()));
+        let deadline = deadline(
+            Duration::from_secs(20),
+            "Stepping back should complete within 20 seconds. Is the pause deadlocked?"
+        );
+        step_back(args, history).await?;
+        deadline.abort();
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Running,
+    Pausing,
+    SteppingBack { paused_at: u32, steps: usize },
+    Resumed { paused_at: u32 },
+    Quitting,
+}
+
+async fn step_back(args: Args, history: usize) -> Result<()> {
+    let (key_presses_tx, key_presses_rx) = flume::bounded::<Keycode>(10);
+    let (events_tx, events_rx) = flume::bounded::<Event>(1000);
+    let gol = tokio::spawn(gol::run(args.clone(), events_tx, key_presses_rx));
+
+    let mut world = HashMap::<CellCoord<i64>, CellState>::new();
+    let mut snapshots = HashMap::<u32, Vec<(CellCoord<i64>, CellState)>>::new();
+    let mut phase = Phase::Running;
+    let mut turn = 0;
+    loop {
+        match events_rx.recv_async().await {
+            Ok(Event::CellsFlipped { cells, .. }) => for cell in cells {
+                if world.remove(&cell).is_none() {
+                    world.insert(cell, CellState::ALIVE);
+                }
+            },
+            Ok(Event::CellsChanged { cells, .. }) => for (cell, state) in cells {
+                match state {
+                    CellState::DEAD => world.remove(&cell),
+                    state => world.insert(cell, state),
+                };
+            },
+            Ok(Event::TurnComplete { completed_turns }) => {
+                let snapshot = snapshot(&world);
+                match phase {
+                    Phase::SteppingBack { paused_at, ref mut steps } => {
+                        assert_eq!(completed_turns + 1, turn, "Stepped back from {} to {} rather than one turn", turn, completed_turns);
+                        *steps += 1;
+                        assert!(*steps <= history, "Stepped back {} times from {} with a history of {}", steps, paused_at, history);
+                        assert_eq!(
+                            snapshots.get(&completed_turns), Some(&snapshot),
+                            "Stepping back to turn {} did not restore it", completed_turns
+                        );
+                    },
+                    _ => {
+                        let expected = snapshots.entry(completed_turns).or_insert(snapshot.clone());
+                        assert_eq!(*expected, snapshot, "Turn {} came out differently the second time", completed_turns);
+                    },
+                }
+                turn = completed_turns;
+                match phase {
+                    Phase::Running if turn >= 20 => {
+                        key_presses_tx.send_async(Keycode::P).await?;
+                        phase = Phase::Pausing;
+                    },
+                    Phase::Resumed { paused_at } if turn > paused_at + 10 => {
+                        key_presses_tx.send_async(Keycode::Q).await?;
+                        phase = Phase::Quitting;
+                    },
+                    _ => (),
+                }
+            },
+            Ok(Event::StateChange { completed_turns: 0, new_state: State::Executing }) if phase == Phase::Running => {
+                snapshots.insert(0, snapshot(&world));
+            },
+            Ok(Event::StateChange { completed_turns, new_state: State::Pause }) => {
+                assert_eq!(phase, Phase::Pausing);
+                phase = Phase::SteppingBack { paused_at: completed_turns, steps: 0 };
+                for _ in 0..history + 2 {
+                    key_presses_tx.send_async(Keycode::B).await?;
+                }
+                key_presses_tx.send_async(Keycode::S).await?;
+            },
+            Ok(Event::ImageOutputComplete { completed_turns, filename }) => if let Phase::SteppingBack { paused_at, steps } = phase {
+                assert_eq!(steps, history, "Expected to step back {} times from {}", history, paused_at);
+                assert_eq!(completed_turns, turn, "Saved turn {} after stepping back to {}", completed_turns, turn);
+                if !args.unbounded && !args.rule.is_generations() {
+                    let (width, height) = args.size()?;
+                    let mut saved = read_alive_cells(format!("out/{}.pgm", filename), width, height)?
+                        .into_iter().map(|cell| (cell, CellState::ALIVE)).collect::<Vec<_>>();
+                    saved.sort_by_key(|&(cell, _)| (cell.y, cell.x));
+                    assert_eq!(saved, snapshot(&world), "The saved image is not the turn stepped back to");
+                }
+                key_presses_tx.send_async(Keycode::P).await?;
+                phase = Phase::Resumed { paused_at };
+            },
+            Ok(Event::FinalTurnComplete { alive, .. }) => {
+                assert_eq!(phase, Phase::Quitting);
+                let mut expected = world.iter()
+                    .filter(|(_, state)| state.is_alive())
+                    .map(|(&cell, _)| cell)
+                    .collect::<Vec<_>>();
+                expected.sort_by_key(|cell| (cell.y, cell.x));
+                let mut alive = alive;
+                alive.sort_by_key(|cell| (cell.y, cell.x));
+                assert_eq!(alive, expected, "The final world is not the one reported");
+            },
+            Ok(Event::StateChange { new_state: State::Quitting, .. }) => break,
+            Ok(_) => (),
+            Err(_) => panic!("Events stopped before quitting in phase {:?}", phase),
+        }
+    }
+    gol.await??;
+    Ok(())
+}
+
+fn snapshot(world: &HashMap<CellCoord<i64>, CellState>) -> Vec<(CellCoord<i64>, CellState)> {
+    let mut cells = world.iter().map(|(&cell, &state)| (cell, state)).collect::<Vec<_>>();
+    cells.sort_by_key(|&(cell, _)| (cell.y, cell.x));
+    cells
+}
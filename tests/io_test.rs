@@ -0,0 +1,150 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use gol_rs::args::Args;
+use gol_rs::gol::{io::{self, SizeError}, Params};
+use gol_rs::util::logger;
+use log::Level;
+use std::path::Path;
+use utils::{io::read_alive_cells, run::{run, Outcome}};
+
+mod utils;
+
+#[tokio::main]
+async fn main() {
+    let start = std::time::Instant::now();
+    logger::set_panic_hook();
+    logger::init(Level::Debug, false);
+
+    let directory = std::env::temp_dir().join(format!("gol-rs-io-{}", std::process::id()));
+    let passed_tests = test_output_name().unwrap()
+        + test_paths(Args::default(), &directory).await.unwrap()
+        + test_errors(Args::default(), &directory).await.unwrap()
+        + test_size(Args::default(), &directory).await.unwrap();
+    let _ = std::fs::remove_dir_all(directory);
+
+    println!(
+        "\ntest result: {}. {} passed; finished in {:.2}s\n",
+        "ok".green(),
+        passed_tests,
+        start.elapsed().as_secs_f32()
+    );
+    std::process::exit(0);
+}
+
+/// Output name templates fill in their placeholders and reject ones they do not know.
+fn test_output_name() -> Result<usize> {
+    log::debug!(target: "Test", "{}", "Testing output name templates".cyan());
+    assert_eq!(io::output_name("{w}x{h}x{turn}", 64, 32, 100)?, "64x32x100");
+    assert_eq!(io::output_name("glider", 64, 32, 100)?, "glider");
+    assert_eq!(io::output_name("runs/{turn}/{w}", 64, 32, 7)?, "runs/7/64");
+    let stamped = io::output_name("{timestamp}-{turn}", 64, 32, 7)?;
+    let (seconds, turn) = stamped.split_once('-').context("The timestamp should come before the turn")?;
+    assert!(seconds.parse::<u64>()? > 1_600_000_000, "Unexpected timestamp {}", seconds);
+    assert_eq!(turn, "7");
+    for (template, expected) in [("{x}", "unknown placeholder {x}"), ("{w", "unclosed"), ("", "cannot be empty")] {
+        let error = io::output_name(template, 64, 32, 7).expect_err("The template should be rejected");
+        assert!(error.to_string().contains(expected), "Unexpected error: {}", error);
+    }
+    Ok(1)
+}
+
+/// The input image can be any file of the right size, and images are written to the output
+/// directory under the name the template gives them, which is also the one reported.
+async fn test_paths(args: Args, directory: &Path) -> Result<usize> {
+    std::fs::create_dir_all(directory)?;
+    let input = directory.join("glider_gun.pgm");
+    std::fs::copy("images/64x64.pgm", &input)?;
+    let output_dir = directory.join("images");
+    let args = args
+        .input(&input)
+        .output_dir(&output_dir)
+        .output_name("gun-{w}-{h}/{turn}")
+        .turns(100)
+        .image_width(64)
+        .image_height(64);
+    log::debug!(target: "Test", "{} - {:?}", "Testing input and output paths".cyan(), Params::from(args.clone()));
+    let Outcome { alive, filename, .. } = run(args).await?;
+    let filename = filename.context("No image was written")?;
+    assert_eq!(filename, "gun-64-64/100");
+    let expected = read_alive_cells("check/images/64x64x100.pgm", 64, 64)?;
+    assert_eq!(alive.len(), expected.len());
+    assert_eq!(read_alive_cells(output_dir.join("gun-64-64/100.pgm"), 64, 64)?, expected);
+    Ok(1)
+}
+
+/// A missing input image, an output directory that cannot be made or an image that cannot be written
+/// ends the run with an error naming the file, rather than a panic.
+async fn test_errors(args: Args, directory: &Path) -> Result<usize> {
+    let mut passed_tests = 0;
+    std::fs::create_dir_all(directory)?;
+    let blocked = directory.join("file");
+    std::fs::write(&blocked, b"not a directory")?;
+    let missing = directory.join("missing.pgm");
+    let args = args.turns(1).image_width(16).image_height(16);
+    let cases = [
+        (args.clone().input(&missing), format!("Cannot open the input image {}", missing.display())),
+        (args.clone().output_dir(&blocked), format!("Cannot create the output directory {}", blocked.display())),
+        (args.clone().output_name("{turns}"), "unknown placeholder {turns}".to_owned()),
+    ];
+    for (args, expected) in cases {
+        log::debug!(target: "Test", "{} - {:?}", "Testing Io error".cyan(), Params::from(args.clone()));
+        let error = run(args).await.expect_err("The run should fail");
+        assert!(format!("{:#}", error).contains(&expected), "Unexpected error: {:#}", error);
+        passed_tests += 1;
+    }
+
+    // An image that fails to be written leaves no empty or partial file behind.
+    let args = args.rule("B2/S/C3".parse()?).output_dir(directory).output_name("partial.cells");
+    log::debug!(target: "Test", "{} - {:?}", "Testing Io error".cyan(), Params::from(args.clone()));
+    let error = run(args).await.expect_err("The run should fail");
+    assert!(format!("{:#}", error).contains("Cannot write the output pattern"), "Unexpected error: {:#}", error);
+    assert!(!directory.join("partial.cells").exists(), "The failed image was left behind");
+    assert!(!directory.join("partial.cells.part").exists(), "The partial image was left behind");
+    passed_tests += 1;
+    Ok(passed_tests)
+}
+
+/// A width or height left out is taken from the input image, and one given that does not agree
+/// with the image is a size error, whether it is found before the run or when the image is read.
+async fn test_size(args: Args, directory: &Path) -> Result<usize> {
+    let mut passed_tests = 0;
+    std::fs::create_dir_all(directory)?;
+    let input = directory.join("size.pgm");
+    std::fs::copy("images/64x64.pgm", &input)?;
+    let args = args.input(&input).turns(100).output_dir(directory);
+    assert_eq!((args.image_width, args.image_height), (None, None));
+    let expected = read_alive_cells("check/images/64x64x100.pgm", 64, 64)?;
+    for args in [args.clone(), args.clone().image_width(64), args.clone().image_height(64).image_width(64)] {
+        log::debug!(target: "Test", "{} - {:?}", "Testing size from the input image".cyan(), Params::from(args.clone()));
+        assert_eq!(args.size()?, (64, 64));
+        let params = Params::from(args.clone().image_width(64).image_height(64));
+        assert_eq!((params.image_width, params.image_height), (64, 64));
+        let Outcome { alive, filename, .. } = run(args).await?;
+        let filename = filename.context("No image was written")?;
+        assert_eq!(filename, "64x64x100");
+        assert_eq!(alive.len(), expected.len());
+        passed_tests += 1;
+    }
+
+    let mismatch = SizeError::Mismatch { path: input.clone(), given: (64, 32), found: (64, 64) };
+    let conflicting = args.clone().image_height(32);
+    assert_eq!(conflicting.size(), Err(mismatch.clone()));
+    let error = run(conflicting.clone()).await.expect_err("The run should not start");
+    assert_eq!(error.downcast_ref::<SizeError>(), Some(&mismatch), "Unexpected error: {:#}", error);
+    // Params copy the size without reading the image, so a run given one that was never settled
+    // finds the mismatch when the Io reads the image.
+    let params = Params::from(conflicting.clone());
+    assert_eq!((params.image_width, params.image_height), (512, 32));
+    let error = run(conflicting.image_width(64)).await.expect_err("The run should fail");
+    assert_eq!(error.downcast_ref::<SizeError>(), Some(&mismatch), "Unexpected error: {:#}", error);
+    passed_tests += 1;
+
+    let missing = directory.join("missing.pgm");
+    let error = args.clone().input(&missing).size().expect_err("A missing image has no size");
+    assert!(matches!(&error, SizeError::Unreadable { path, .. } if *path == missing), "Unexpected error: {}", error);
+    assert_eq!(Args::default().size()?, (512, 512));
+    assert_eq!(Args::default().image_width(16).size()?, (16, 512));
+    passed_tests += 1;
+    Ok(passed_tests)
+}
+
@@ -0,0 +1,239 @@
+use anyhow::Result;
+use colored::Colorize;
+use gol_rs::args::Args;
+use gol_rs::gol::{self, engine::EngineKind, event::Event, lattice::Lattice, Params};
+use gol_rs::gol::io::{start_io, IoChannels, IoCommand};
+use gol_rs::gol::rule::{ParseRuleError, Rule};
+use gol_rs::gol::topology::Topology;
+use gol_rs::util::{cell::{CellCoord, CellState}, logger};
+use log::Level;
+use sdl2::keyboard::Keycode;
+use std::collections::HashSet;
+use utils::io::{read_alive_cells, read_world};
+use utils::run::run;
+use utils::world::{alive_cells, reference_neighbours, reference_step, sorted};
+
+mod utils;
+
+#[tokio::main]
+async fn main() {
+    let start = std::time::Instant::now();
+    logger::set_panic_hook();
+    logger::init(Level::Debug, false);
+
+    let passed_tests = test_parse().unwrap()
+        + test_geometry().unwrap()
+        + test_engines(Args::default()).await.unwrap()
+        + test_unbounded(Args::default()).await.unwrap()
+        + test_round_trip(Args::default()).await.unwrap()
+        + test_unsupported(Args::default()).await.unwrap();
+
+    println!(
+        "\ntest result: {}. {} passed; finished in {:.2}s\n",
+        "ok".green(),
+        passed_tests,
+        start.elapsed().as_secs_f32()
+    );
+    std::process::exit(0);
+}
+
+/// Rulestrings ending in `H` or `L` run on hexagons or triangles, and print back the same way.
+fn test_parse() -> Result<usize> {
+    let mut passed_tests = 0;
+    let cases = [
+        ("B2/S34H", "B2/S34H", Lattice::Hexagonal, 2),
+        ("b2/s34h", "B2/S34H", Lattice::Hexagonal, 2),
+        ("34/2H", "B2/S34H", Lattice::Hexagonal, 2),
+        ("B2/S/C3H", "B2/S/C3H", Lattice::Hexagonal, 3),
+        ("B45/S34abL", "B45/S34abL", Lattice::Triangular, 2),
+        ("B4/S3456789ABCL", "B4/S3456789abcL", Lattice::Triangular, 2),
+        ("B3/S23", "B3/S23", Lattice::Square, 2),
+    ];
+    for (rulestring, expected, lattice, states) in cases {
+        log::debug!(target: "Test", "{} - {}", "Testing lattice rule parsing".cyan(), rulestring);
+        let rule = rulestring.parse::<Rule>()?;
+        assert_eq!(rule.to_string(), expected, "{} printed wrongly", rulestring);
+        assert_eq!(rule.lattice(), lattice, "{} is on the wrong lattice", rulestring);
+        assert_eq!(rule.max_neighbours(), lattice.neighbours(), "{} has the wrong neighbourhood", rulestring);
+        assert_eq!(rule.states(), states, "{} has the wrong states", rulestring);
+        assert_eq!(rule.to_string().parse::<Rule>()?, rule, "{} does not round trip", rulestring);
+        passed_tests += 1;
+    }
+    let triangular = "B45/S34abL".parse::<Rule>()?;
+    assert!(triangular.survives(11) && !triangular.survives(12) && triangular.births(5));
+    assert!(!triangular.is_life_like() && !triangular.is_larger_than_life());
+    passed_tests += 1;
+
+    let errors = [
+        ("B7/S2H", ParseRuleError::InvalidLatticeCount(Lattice::Hexagonal, '7')),
+        ("B2/S2dL", ParseRuleError::InvalidLatticeCount(Lattice::Triangular, 'd')),
+        ("B2-a/S2H", ParseRuleError::InvalidLatticeCount(Lattice::Hexagonal, '-')),
+        ("B22/S2H", ParseRuleError::RepeatedCount(2)),
+        ("B2H", ParseRuleError::MissingSeparator("B2".to_string())),
+    ];
+    for (rulestring, expected) in errors {
+        log::debug!(target: "Test", "{} - {}", "Testing lattice rule parse error".cyan(), rulestring);
+        assert_eq!(rulestring.parse::<Rule>(), Err(expected), "Wrong error for {}", rulestring);
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
+
+/// The stored neighbours of hexagons are their axial neighbours, the stored neighbours of triangles
+/// are the triangles sharing a corner, and both are symmetric on an evenly sized torus.
+fn test_geometry() -> Result<usize> {
+    let mut passed_tests = 0;
+    for q in -5..5 {
+        for r in -5..5 {
+            let (x, y) = Lattice::axial_to_raster(q, r);
+            assert_eq!(Lattice::raster_to_axial(x, y), (q, r), "Axial ({}, {}) does not round trip", q, r);
+        }
+    }
+    assert_eq!(Lattice::axial_to_raster(3, -1), (2, -1));
+    assert_eq!(Lattice::axial_to_raster(-1, 2), (0, 2));
+    assert_eq!(Lattice::axial_to_raster(0, 1), (0, 1));
+    passed_tests += 1;
+
+    let (width, height) = (8_usize, 6_usize);
+    for lattice in [Lattice::Hexagonal, Lattice::Triangular] {
+        log::debug!(target: "Test", "{} - {}", "Testing lattice neighbours".cyan(), lattice);
+        for y in 0..height {
+            for x in 0..width {
+                let stored = lattice.offsets(x, y).iter()
+                    .map(|&(dx, dy)| (x as i64 + dx as i64, y as i64 + dy as i64))
+                    .collect::<HashSet<_>>();
+                let expected = reference_neighbours(lattice, x as i64, y as i64).into_iter().collect::<HashSet<_>>();
+                assert_eq!(stored, expected, "Wrong {} neighbours of ({}, {})", lattice, x, y);
+                assert_eq!(stored.len(), lattice.neighbours() as usize);
+                for (nx, ny) in stored {
+                    let (nx, ny) = Topology::Torus.map(nx as isize, ny as isize, width, height).unwrap();
+                    let back = lattice.offsets(nx, ny).iter().any(|&(dx, dy)|
+                        Topology::Torus.map(nx as isize + dx, ny as isize + dy, width, height) == Some((x, y)));
+                    assert!(back, "({}, {}) is not a {} neighbour of its neighbour ({}, {})", x, y, lattice, nx, ny);
+                }
+            }
+        }
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
+
+/// The byte engines match the reference on a torus and on a plane, for two-state and Generations rules.
+async fn test_engines(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    let turns = 50;
+    for rulestring in ["B2/S34H", "B2/S/C3H", "B45/S34L", "B4/S456/C4L"] {
+        let rule = rulestring.parse::<Rule>()?;
+        for topology in [Topology::Torus, Topology::Plane] {
+            let world = read_world("images/64x64.pgm", &rule)?;
+            let expected = (0..turns).fold(world, |world, _| reference_step(&rule, topology, &world));
+            let expected = alive_cells(&expected);
+            for engine in [EngineKind::Naive, EngineKind::Parallel] {
+                for threads in [1, 3] {
+                    let args = args.clone()
+                        .rule(rule)
+                        .topology(topology)
+                        .engine(engine)
+                        .threads(threads)
+                        .turns(turns)
+                        .image_width(64)
+                        .image_height(64);
+                    log::debug!(target: "Test", "{} - {:?}", "Testing lattice engine".cyan(), Params::from(args.clone()));
+                    let alive = run(args).await?.alive;
+                    assert_eq!(alive, expected, "{} on a {} differs from the reference", rule, topology);
+                    passed_tests += 1;
+                }
+            }
+        }
+    }
+    Ok(passed_tests)
+}
+
+/// An unbounded world of hexagons or triangles keeps growing with the parity of its rows and columns intact.
+async fn test_unbounded(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    let turns = 30;
+    for rulestring in ["B2/S2H", "B3/S234L"] {
+        let rule = rulestring.parse::<Rule>()?;
+        let input = read_world("images/64x64.pgm", &rule)?;
+        // Padding by an even amount keeps the parity of every cell, so nothing is redrawn the other way up.
+        let padding = 2 * turns + 2;
+        let mut world = vec![vec![CellState::DEAD; 64 + 2 * padding]; 64 + 2 * padding];
+        for (y, row) in input.iter().enumerate() {
+            world[padding + y][padding..padding + 64].copy_from_slice(row);
+        }
+        let expected = (0..turns).fold(world, |world, _| reference_step(&rule, Topology::Plane, &world));
+        let padding = padding as i64;
+        let expected: Vec<_> = alive_cells(&expected).into_iter().map(|cell| CellCoord::new(cell.x - padding, cell.y - padding)).collect();
+
+        let args = args.clone().rule(rule).unbounded(true).turns(turns).image_width(64).image_height(64);
+        log::debug!(target: "Test", "{} - {:?}", "Testing unbounded lattice".cyan(), Params::from(args.clone()));
+        let alive = run(args).await?.alive;
+        assert_eq!(alive, expected, "{} differs from the padded plane", rule);
+        assert!(alive.iter().any(|cell| cell.x < 0 || cell.y < 0), "{} did not grow north or west", rule);
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
+
+/// Hexagons written by the Io read back through the Io to the same cells.
+async fn test_round_trip(args: Args) -> Result<usize> {
+    let rule = "B2/S34H".parse::<Rule>()?;
+    let args = args.rule(rule).turns(10).image_width(16).image_height(16);
+    log::debug!(target: "Test", "{} - {:?}", "Testing lattice round trip".cyan(), Params::from(args.clone()));
+    let alive = run(args.clone()).await?.alive;
+    let written = read_alive_cells("out/16x16x10.pgm", 16, 16)?;
+    assert_eq!(sorted(written), alive, "The output image does not hold the final world");
+
+    let (command_tx, command_rx) = flume::unbounded();
+    let (idle_tx, _idle_rx) = flume::unbounded();
+    let (filename_tx, filename_rx) = flume::unbounded();
+    let (input_tx, input_rx) = flume::unbounded();
+    let (_output_tx, output_rx) = flume::unbounded();
+    tokio::spawn(start_io(Params::from(args), IoChannels {
+        command: Some(command_rx),
+        idle: Some(idle_tx),
+        filename: Some(filename_rx),
+        input: Some(input_tx),
+        output: Some(output_rx),
+        snapshot: None,
+    }));
+    command_tx.send_async(IoCommand::IoInput).await?;
+    filename_tx.send_async("../out/16x16x10".to_string()).await?;
+    let mut read = Vec::new();
+    for i in 0..16 * 16 {
+        if input_rx.recv_async().await?.is_alive() {
+            read.push(CellCoord::new(i % 16, i / 16));
+        }
+    }
+    assert_eq!(sorted(read), alive, "The Io did not read back the cells it wrote");
+    Ok(1)
+}
+
+/// Hexagons and triangles cannot be mirrored across a seam, need evenly sized tori,
+/// and only run on the byte engines.
+async fn test_unsupported(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    let hexagonal = "B2/S34H".parse::<Rule>()?;
+    let triangular = "B45/S34L".parse::<Rule>()?;
+    let cases = [
+        (args.clone().rule(hexagonal).topology(Topology::Klein), "cannot use the klein topology"),
+        (args.clone().rule(triangular).topology(Topology::TwistedTorus(1)), "cannot use the twisted-torus:1 topology"),
+        (args.clone().rule(hexagonal).image_height(15), "cannot use the torus topology"),
+        (args.clone().rule(triangular).image_width(15), "cannot use the torus topology"),
+        (args.clone().rule(hexagonal).engine(EngineKind::BitPacked), "does not support the rule"),
+        (args.clone().rule(triangular).engine(EngineKind::HashLife), "does not support the rule"),
+    ];
+    for (args, expected) in cases {
+        let args = args.turns(1);
+        log::debug!(target: "Test", "{} - {:?}", "Testing unsupported lattice".cyan(), Params::from(args.clone()));
+        let (_key_presses_tx, key_presses_rx) = flume::bounded::<Keycode>(10);
+        let (events_tx, _events_rx) = flume::bounded::<Event>(1000);
+        let error = gol::run(args, events_tx, key_presses_rx).await
+            .expect_err("The lattice should not run");
+        assert!(error.to_string().contains(expected), "Unexpected error: {}", error);
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
+
@@ -0,0 +1,501 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use gol_rs::args::Args;
+use gol_rs::gol::Params;
+use gol_rs::gol::io::{life, macrocell::{self, Quadtree}, pattern::{Format, Pattern, Placement}, plaintext, rle};
+use gol_rs::gol::rule::Rule;
+use gol_rs::util::{cell::{CellCoord, CellState}, logger};
+use log::Level;
+use std::collections::HashSet;
+use std::path::Path;
+use utils::{io::read_alive_cells, run::{run, Outcome}};
+
+mod utils;
+
+const GLIDER: &str = "\
+#N Glider
+#C The smallest, most common, and first discovered spaceship.
+#C www.conwaylife.com/wiki/index.php?title=Glider
+x = 3, y = 3, rule = B3/S23
+bob$2bo$3o!
+";
+
+#[tokio::main]
+async fn main() {
+    let start = std::time::Instant::now();
+    logger::set_panic_hook();
+    logger::init(Level::Debug, false);
+
+    let directory = std::env::temp_dir().join(format!("gol-rs-pattern-{}", std::process::id()));
+    let passed_tests = test_rle_parse().unwrap()
+        + test_rle_round_trip().unwrap()
+        + test_placement().unwrap()
+        + test_rle_run(Args::default(), &directory).await.unwrap()
+        + test_text_parse().unwrap()
+        + test_sniff().unwrap()
+        + test_formats_round_trip().unwrap()
+        + test_formats_run(Args::default(), &directory).await.unwrap()
+        + test_macrocell_parse().unwrap()
+        + test_macrocell_round_trip().unwrap()
+        + test_macrocell_run(Args::default(), &directory).await.unwrap();
+    let _ = std::fs::remove_dir_all(directory);
+
+    println!(
+        "\ntest result: {}. {} passed; finished in {:.2}s\n",
+        "ok".green(),
+        passed_tests,
+        start.elapsed().as_secs_f32()
+    );
+    std::process::exit(0);
+}
+
+/// RLE headers, comments, rules and runs spanning lines are read, and malformed patterns are rejected.
+fn test_rle_parse() -> Result<usize> {
+    log::debug!(target: "Test", "{}", "Testing RLE parsing".cyan());
+    let glider = rle::parse(GLIDER)?;
+    assert_eq!((glider.width, glider.height), (3, 3));
+    assert_eq!(glider.name.as_deref(), Some("Glider"));
+    assert_eq!(glider.comments.len(), 2);
+    assert_eq!(glider.rule, Some(Rule::CONWAY));
+    let expected = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)].map(|(x, y)| CellCoord::new(x, y));
+    assert_eq!(glider.alive_cells(), expected);
+
+    // Runs can be split anywhere between tags, counts can skip rows and the rule can be left out.
+    let split = rle::parse("#R -4 7\nx=3,y=5\nb\no$2b\no\n3$\n3o\n!")?;
+    assert_eq!(split.rule, None);
+    assert_eq!(split.origin, Some(CellCoord::new(-4, 7)));
+    assert_eq!(split.alive_cells(), [(1, 0), (2, 1), (0, 4), (1, 4), (2, 4)].map(|(x, y)| CellCoord::new(x, y)));
+
+    // Generations states are letters, and the rule runs to the end of the header even with commas in it.
+    let states = rle::parse("x = 4, y = 1, rule = B2/S/C3\n.AB!")?;
+    assert_eq!(states.rule, Some("B2/S/C3".parse()?));
+    assert_eq!(states.cells, [0, 1, 2, 0].map(CellState::new));
+    let high = rle::parse("x = 2, y = 1\npAyO!")?;
+    assert_eq!(high.cells, [25, 255].map(CellState::new));
+    let ltl = rle::parse("x = 1, y = 1, rule = R5,C0,M1,S34..58,B34..45,NM:T100,100\no!")?;
+    assert_eq!(ltl.rule, Some("R5,C0,M1,S34..58,B34..45,NM".parse()?));
+
+    let cases = [
+        ("bo$2bo!", "invalid header on line 1"),
+        ("#C only a comment", "no header"),
+        ("x = 3\nbo!", "no y"),
+        ("x = 2, y = 2\n3o!", "run past"),
+        ("x = 2, y = 2\n$$o!", "run past"),
+        ("x = 2, y = 2\nbz!", "unexpected 'z'"),
+        ("x = 2, y = 2, rule = B9/S\no!", "invalid header"),
+        ("x = 100000, y = 100000\n!", "RLE pattern is too large"),
+    ];
+    for (text, expected) in cases {
+        let error = rle::parse(text).expect_err("The pattern should be rejected");
+        assert!(format!("{:#}", error).contains(expected), "Unexpected error for {:?}: {:#}", text, error);
+    }
+    Ok(1)
+}
+
+/// Writing a board as RLE and reading it back gives the same cells, rule and comments.
+fn test_rle_round_trip() -> Result<usize> {
+    log::debug!(target: "Test", "{}", "Testing RLE round trips".cyan());
+    let mut passed_tests = 0;
+    for path in ["check/images/64x64x100.pgm", "check/images/512x512x100.pgm"] {
+        let (width, height) = if path.contains("512") { (512, 512) } else { (64, 64) };
+        let pattern = Pattern { rule: Some(Rule::CONWAY), ..board(read_alive_cells(path, width, height)?, width, height) };
+        let text = rle::write(&pattern);
+        assert!(text.lines().all(|line| line.len() <= 70), "The RLE of {} has overlong lines", path);
+        assert_eq!(rle::parse(&text)?, pattern, "{} did not survive a round trip", path);
+        passed_tests += 1;
+    }
+
+    let glider = rle::parse(GLIDER)?;
+    assert_eq!(rle::parse(&rle::write(&glider))?, glider);
+    let written = rle::write(&Pattern::new(5, 3, [0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0].map(CellState::new).to_vec()));
+    assert_eq!(written, "x = 5, y = 3\nbo2$b2o!\n");
+    let states = Pattern { rule: Some("B2/S/C4".parse()?), origin: Some(CellCoord::new(-8, 3)), ..Pattern::new(
+        4, 2, [3, 0, 1, 2, 0, 0, 0, 0].map(CellState::new).to_vec()
+    ) };
+    let written = rle::write(&states);
+    assert_eq!(written, "#R -8 3\nx = 4, y = 2, rule = B2/S/C4\nC.AB!\n");
+    assert_eq!(rle::parse(&written)?, states);
+    passed_tests += 1;
+    Ok(passed_tests)
+}
+
+/// Patterns are centred or put at a given cell, and ones that do not fit are rejected.
+fn test_placement() -> Result<usize> {
+    log::debug!(target: "Test", "{}", "Testing pattern placement".cyan());
+    assert_eq!("centre".parse::<Placement>()?, Placement::Centre);
+    assert_eq!("Center".parse::<Placement>()?, Placement::Centre);
+    assert_eq!("3, 4".parse::<Placement>()?, Placement::At(CellCoord::new(3, 4)));
+    assert_eq!(Placement::At(CellCoord::new(3, 4)).to_string().parse::<Placement>()?, Placement::At(CellCoord::new(3, 4)));
+    for placement in ["middle", "3", "-1,2", "1,2,3"] {
+        assert!(placement.parse::<Placement>().is_err(), "{} should not be a placement", placement);
+    }
+    assert_eq!(Format::from_path(Path::new("patterns/Glider.RLE")), Some(Format::Rle));
+    assert_eq!(Format::from_path(Path::new("images/64x64.pgm")), None);
+
+    let glider = rle::parse(GLIDER)?;
+    let alive = |board: Vec<CellState>, width: usize| board.iter().enumerate()
+        .filter(|(_, cell)| cell.is_alive())
+        .map(|(i, _)| CellCoord::new(i % width, i / width))
+        .collect::<Vec<_>>();
+    let shifted = |dx: usize, dy: usize| glider.alive_cells().into_iter()
+        .map(|cell| CellCoord::new(cell.x + dx, cell.y + dy))
+        .collect::<Vec<_>>();
+    assert_eq!(alive(glider.place(Placement::Centre, 8, 7)?, 8), shifted(2, 2));
+    assert_eq!(alive(glider.place(Placement::At(CellCoord::new(5, 0)), 8, 7)?, 8), shifted(5, 0));
+    assert_eq!(alive(glider.place(Placement::Centre, 3, 3)?, 3), shifted(0, 0));
+    for (placement, width) in [(Placement::Centre, 2), (Placement::At(CellCoord::new(6, 0)), 8)] {
+        let error = glider.place(placement, width, 8).expect_err("The glider should not fit");
+        assert!(error.to_string().contains("does not fit"), "Unexpected error: {}", error);
+    }
+    let error = glider.place(Placement::Centre, 1 << 16, 1 << 16).expect_err("The board should be too large");
+    assert!(format!("{:#}", error).contains("board is too large"), "Unexpected error: {:#}", error);
+    Ok(1)
+}
+
+/// A run reads an RLE input pattern, taking the size of the board from it when none is given,
+/// and writes RLE when the output name ends in .rle.
+async fn test_rle_run(args: Args, directory: &Path) -> Result<usize> {
+    let mut passed_tests = 0;
+    std::fs::create_dir_all(directory)?;
+    let input = directory.join("glider_gun.rle");
+    std::fs::write(&input, rle::write(&board(read_alive_cells("images/64x64.pgm", 64, 64)?, 64, 64)))?;
+    let args = args.input(&input).turns(100).output_dir(directory).output_name("{w}x{h}x{turn}.rle");
+    log::debug!(target: "Test", "{} - {:?}", "Testing RLE input and output".cyan(), Params::from(args.clone()));
+    assert_eq!(args.size()?, (64, 64));
+    let Outcome { alive, filename, .. } = run(args.clone()).await?;
+    let filename = filename.context("No image was written")?;
+    assert_eq!(filename, "64x64x100.rle");
+    let expected = read_alive_cells("check/images/64x64x100.pgm", 64, 64)?;
+    let expected = expected.into_iter().collect::<HashSet<_>>();
+    assert_eq!(alive.into_iter().collect::<HashSet<_>>(), expected);
+    let written = rle::parse(&std::fs::read_to_string(directory.join(&filename))?)?;
+    assert_eq!((written.width, written.height, written.rule), (64, 64, Some(Rule::CONWAY)));
+    let origin = CellCoord::new(0, 0);
+    assert_eq!(written.alive_cells().into_iter().map(|cell| cell.offset(origin)).collect::<HashSet<_>>(), expected);
+    passed_tests += 1;
+
+    // A glider on a larger board, centred or where it is asked to be.
+    let input = directory.join("glider.rle");
+    std::fs::write(&input, GLIDER)?;
+    let args = args.input(&input).turns(0).image_width(16).image_height(10).output_name("glider-{w}x{h}.rle");
+    for (placement, at) in [(Placement::Centre, (6, 3)), (Placement::At(CellCoord::new(13, 7)), (13, 7))] {
+        let args = args.clone().place(placement);
+        log::debug!(target: "Test", "{} - {:?}", "Testing RLE placement".cyan(), Params::from(args.clone()));
+        assert_eq!(args.size()?, (16, 10));
+        let Outcome { alive, filename, .. } = run(args).await?;
+        let filename = filename.context("No image was written")?;
+        let expected = rle::parse(GLIDER)?.alive_cells().into_iter()
+            .map(|cell| CellCoord::new((cell.x + at.0) as i64, (cell.y + at.1) as i64))
+            .collect::<HashSet<_>>();
+        assert_eq!(alive.into_iter().collect::<HashSet<_>>(), expected);
+        let written = rle::parse(&std::fs::read_to_string(directory.join(filename))?)?;
+        assert_eq!((written.width, written.height), (16, 10));
+        passed_tests += 1;
+    }
+
+    let error = run(args.place(Placement::At(CellCoord::new(14, 0)))).await.expect_err("The glider should not fit");
+    assert!(format!("{:#}", error).contains("does not fit on the 16x10 board at 14,0"), "Unexpected error: {:#}", error);
+    passed_tests += 1;
+    Ok(passed_tests)
+}
+
+/// Plaintext, Life 1.05 and Life 1.06 patterns are read with their comments, and malformed ones are rejected.
+fn test_text_parse() -> Result<usize> {
+    log::debug!(target: "Test", "{}", "Testing plaintext and Life parsing".cyan());
+    let glider = rle::parse(GLIDER)?.alive_cells();
+    let cells = plaintext::parse("!Name: Glider\n!The smallest spaceship.\n.O\n..O\nOOO\n\n")?;
+    assert_eq!((cells.width, cells.height), (3, 3));
+    assert_eq!(cells.name.as_deref(), Some("Glider"));
+    assert_eq!(cells.comments, ["The smallest spaceship."]);
+    assert_eq!(cells.alive_cells(), glider);
+
+    let life_105 = life::parse_105("#Life 1.05\n#D Two gliders\n#R 23/3\n#P -1 -1\n.*\n..*\n***\n#P 10 20\n*\n")?;
+    assert_eq!(life_105.rule, Some(Rule::CONWAY));
+    assert_eq!(life_105.comments, ["Two gliders"]);
+    assert_eq!(life_105.origin, Some(CellCoord::new(-1, -1)));
+    assert_eq!((life_105.width, life_105.height), (12, 22));
+    let expected = [(0, -1), (1, 0), (-1, 1), (0, 1), (1, 1), (10, 20)].map(|(x, y)| CellCoord::new(x, y));
+    assert_eq!(life_105.alive_world_cells(), expected);
+
+    let life_106 = life::parse_106("#Life 1.06\n#D Name: Glider\n0 -1\n1 0\n-1 1\n0 1\n1 1\n")?;
+    assert_eq!(life_106.name.as_deref(), Some("Glider"));
+    assert_eq!(life_106.alive_world_cells(), expected[..5]);
+    assert_eq!(life_106.alive_cells(), glider);
+    assert_eq!(life::parse_106("#Life 1.06\n")?, Pattern::default());
+
+    // Far apart cells or a long row over many short ones describe more cells than a pattern can have.
+    let tall = format!("{}\n{}O", "O".repeat(1 << 16), "\n".repeat(1 << 16));
+    let cases = [
+        (Format::Plaintext, ".O.\n.X.", "unexpected 'X' on line 2"),
+        (Format::Life105, "#Life 1.05\n#P 1\n*", "line 2"),
+        (Format::Life105, "#Life 1.05\n#R 9/3\n*", "line 2"),
+        (Format::Life106, "#Life 1.06\n1 2 3", "not an x y coordinate"),
+        (Format::Life106, "#Life 1.06\n1 two", "line 2"),
+        (Format::Life106, "#Life 1.06\n0 0\n1000000000 1000000000", "pattern is too large"),
+        (Format::Life105, "#Life 1.05\n#P 0 0\n*\n#P 1000000000 1000000000\n*", "pattern is too large"),
+        (Format::Plaintext, tall.as_str(), "pattern is too large"),
+    ];
+    for (format, text, expected) in cases {
+        let error = format.parse(text).expect_err("The pattern should be rejected");
+        assert!(format!("{:#}", error).contains(expected), "Unexpected error for {:?}: {:#}", text, error);
+    }
+    Ok(1)
+}
+
+/// Formats are chosen by extension, with `.lif` and unknown extensions told apart by the content.
+fn test_sniff() -> Result<usize> {
+    log::debug!(target: "Test", "{}", "Testing pattern format detection".cyan());
+    let cases = [
+        ("glider.rle", "x = 3, y = 3\nbo$2bo$3o!", Some(Format::Rle)),
+        ("glider.cells", "!Name: Glider", Some(Format::Plaintext)),
+        ("glider.lif", "#Life 1.05\n#P 0 0\n*", Some(Format::Life105)),
+        ("glider.LIF", "#Life 1.06\n0 0", Some(Format::Life106)),
+        ("glider.life", "0 0", Some(Format::Life106)),
+        ("glider", "#N Glider\n#C comment\nx = 3, y = 3\n3o!", Some(Format::Rle)),
+        ("glider.txt", "\n.O.\n..O\nOOO\n", Some(Format::Plaintext)),
+        ("glider.txt", "!Glider", Some(Format::Plaintext)),
+        ("glider", "#Life 1.06\n0 0", Some(Format::Life106)),
+        ("glider.mc", "[M2] (golly 4.2)\n.*$..*$***$", Some(Format::Macrocell)),
+        ("glider", "[M2] (golly 4.2)\n.*$..*$***$", Some(Format::Macrocell)),
+        ("64x64.pgm", "x = 3, y = 3", None),
+        ("64x64", "P5\n64 64\n255\n", None),
+        ("empty", "", None),
+    ];
+    for (path, head, expected) in cases {
+        assert_eq!(Format::detect(Path::new(path), head.as_bytes()), expected, "Detected the wrong format for {}", path);
+    }
+    assert_eq!(Format::from_path(Path::new("out/glider.cells")), Some(Format::Plaintext));
+    assert_eq!(Format::from_path(Path::new("out/glider.lif")), Some(Format::Life106));
+    assert_eq!(Format::of_file(Path::new("images/64x64.pgm"))?, None);
+
+    let states = Pattern::new(2, 1, vec![CellState::ALIVE, CellState::new(2)]);
+    assert!(Format::Rle.write(&states).is_ok());
+    for format in [Format::Plaintext, Format::Life105, Format::Life106] {
+        let error = format.write(&states).expect_err("Only RLE holds more than two states");
+        assert!(error.to_string().contains("state 2"), "Unexpected error: {}", error);
+    }
+    Ok(1)
+}
+
+/// Converting each board in check/images to every format and back gives the same alive cells.
+fn test_formats_round_trip() -> Result<usize> {
+    log::debug!(target: "Test", "{}", "Testing pattern format round trips".cyan());
+    let mut passed_tests = 0;
+    for entry in std::fs::read_dir("check/images")? {
+        let path = entry?.path();
+        let name = path.file_stem().and_then(|stem| stem.to_str()).context("Unexpected image name")?;
+        let size = name.split('x').next().context("Unexpected image name")?.parse::<usize>()?;
+        let alive = read_alive_cells(&path, size, size)?;
+        let expected = alive.iter().copied().collect::<HashSet<_>>();
+        let pattern = Pattern { name: Some(name.to_owned()), rule: Some(Rule::CONWAY), ..board(alive, size, size) };
+        for format in [Format::Rle, Format::Plaintext, Format::Life105, Format::Life106, Format::Macrocell] {
+            let text = format.write(&pattern)?;
+            assert_eq!(Format::sniff(text.as_bytes()), Some(format), "The {} of {} was not recognised", format, name);
+            let read = format.parse(&text)?;
+            assert_eq!(
+                read.alive_world_cells().into_iter().collect::<HashSet<_>>(), expected,
+                "{} did not survive a round trip through {}", name, format
+            );
+            assert_eq!(read.name.as_deref(), Some(name));
+            passed_tests += 1;
+        }
+    }
+    Ok(passed_tests)
+}
+
+/// A run reads a pattern whose format is only known from its content, and writes each format
+/// its output name asks for.
+async fn test_formats_run(args: Args, directory: &Path) -> Result<usize> {
+    let mut passed_tests = 0;
+    std::fs::create_dir_all(directory)?;
+    let input = directory.join("glider_gun.txt");
+    let gun = board(read_alive_cells("images/64x64.pgm", 64, 64)?, 64, 64);
+    let text = life::write_105(&gun);
+    std::fs::write(&input, &text)?;
+    // The pattern is only the bounding box of the gun, so it goes back where the gun was on the board.
+    let origin = life::parse_105(&text)?.origin.context("The gun should have alive cells")?;
+    let expected = read_alive_cells("check/images/64x64x100.pgm", 64, 64)?.into_iter().collect::<HashSet<_>>();
+    for extension in ["cells", "lif", "rle"] {
+        let args = args.clone()
+            .input(&input)
+            .turns(100)
+            .image_width(64)
+            .image_height(64)
+            .place(Placement::At(CellCoord::new(origin.x as usize, origin.y as usize)))
+            .output_dir(directory)
+            .output_name(format!("gun-{{turn}}.{}", extension));
+        log::debug!(target: "Test", "{} - {:?}", "Testing pattern formats in a run".cyan(), Params::from(args.clone()));
+        let Outcome { alive, filename, .. } = run(args).await?;
+        let filename = filename.context("No image was written")?;
+        assert_eq!(filename, format!("gun-100.{}", extension));
+        assert_eq!(alive.into_iter().collect::<HashSet<_>>(), expected);
+        let path = directory.join(&filename);
+        let format = Format::of_file(&path)?.context("The output pattern was not recognised")?;
+        let written = format.parse(&std::fs::read_to_string(&path)?)?;
+        assert_eq!(written.alive_world_cells().into_iter().collect::<HashSet<_>>(), expected);
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
+
+/// Macrocell files as Golly writes them are read into quadtrees centred on the world origin,
+/// and malformed ones are rejected.
+fn test_macrocell_parse() -> Result<usize> {
+    log::debug!(target: "Test", "{}", "Testing macrocell parsing".cyan());
+    let glider = [(0, -1), (1, 0), (-1, 1), (0, 1), (1, 1)].map(|(x, y)| CellCoord::new(x, y));
+    let shifted = |dx: i64, dy: i64| glider.map(|cell| CellCoord::new(cell.x + dx, cell.y + dy)).to_vec();
+
+    // A lone leaf is the root, with its top left cell at -4, -4.
+    let leaf = macrocell::parse("[M2] (golly 4.2)\n#R B3/S23\n#C Name: Glider\n#C A spaceship\n.*$..*$***$\n")?;
+    assert_eq!((leaf.level(), leaf.population()), (3, 5));
+    assert_eq!((leaf.rule, leaf.name.as_deref()), (Some(Rule::CONWAY), Some("Glider")));
+    assert_eq!(leaf.comments, ["A spaceship"]);
+    assert_eq!(leaf.bounds(), Some((CellCoord::new(-4, -4), 3, 3)));
+    assert_eq!(leaf.to_pattern(3, 3)?.alive_world_cells(), shifted(-3, -3));
+    assert!(leaf.state(CellCoord::new(-3, -4)).is_alive());
+    assert!(leaf.state(CellCoord::new(-4, -4)).is_dead());
+    assert!(leaf.state(CellCoord::new(1000, -4)).is_dead());
+
+    // A glider split between two leaves of a level 4 root, with 0 for the empty quadrants.
+    let split = macrocell::parse("[M2] (golly 2.0)\n$$$$$$$.*$\n..*$***$\n4 1 0 2 0\n")?;
+    assert_eq!((split.level(), split.population()), (4, 5));
+    assert_eq!(split.to_pattern(64, 64)?.alive_world_cells(), shifted(-7, 0));
+
+    // Patterns with more states have 2x2 leaves of states.
+    let states = macrocell::parse("[M2] (golly 4.2)\n#R B2/S/C3\n1 0 1 2 0\n1 2 0 0 1\n2 1 0 0 2\n")?;
+    assert_eq!(states.rule, Some("B2/S/C3".parse()?));
+    let pattern = states.to_pattern(8, 8)?;
+    assert_eq!((pattern.origin, pattern.width, pattern.height), (Some(CellCoord::new(-2, -2)), 4, 4));
+    assert_eq!(pattern.cells.iter().map(CellState::get).collect::<Vec<_>>(), [0, 1, 0, 0, 2, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 1]);
+
+    let cases = [
+        ("x = 3, y = 3\n3o!", "not a macrocell pattern"),
+        ("[M2]\n4 1 0 0 0", "comes after it"),
+        ("[M2]\n.*$\n5 1 0 0 0", "not at level 4"),
+        ("[M2]\n.........*$", "wider or taller"),
+        ("[M2]\n$$$$$$$$.*$", "wider or taller"),
+        ("[M2]\n.*o$", "unexpected 'o'"),
+        ("[M2]\n1 0 256 0 0", "state above 255"),
+        ("[M2]\n63 0 0 0 0", "no nodes at level 63"),
+        ("[M2]\n4 0 0 0", "a level and four children"),
+        ("[M2]\n#R B9/S\n.*$", "line 2"),
+    ];
+    for (text, expected) in cases {
+        let error = macrocell::parse(text).expect_err("The pattern should be rejected");
+        assert!(format!("{:#}", error).contains(expected), "Unexpected error for {:?}: {:#}", text, error);
+    }
+    Ok(1)
+}
+
+/// Known patterns survive being written as macrocell and read back, and huge regular patterns
+/// are round tripped as quadtrees without ever being expanded.
+fn test_macrocell_round_trip() -> Result<usize> {
+    log::debug!(target: "Test", "{}", "Testing macrocell round trips".cyan());
+    let mut passed_tests = 0;
+    let glider = Pattern { rule: Some(Rule::CONWAY), ..rle::parse(GLIDER)? };
+    let gun = board(read_alive_cells("images/64x64.pgm", 64, 64)?, 64, 64);
+    let gun = Pattern { origin: Some(CellCoord::new(-20, 7)), ..gun };
+    let generations = Pattern { rule: Some("B2/S/C4".parse()?), ..rle::parse("x = 5, y = 2, rule = B2/S/C4\nA.BC$3.CA!")? };
+    for pattern in [glider, gun, generations] {
+        let tree = Quadtree::from_pattern(&pattern)?;
+        let text = macrocell::write(&tree);
+        let read = macrocell::parse(&text)?;
+        assert_eq!(macrocell::write(&read), text, "The macrocell of a canonical quadtree should be unique");
+        assert_eq!(read.population(), pattern.alive_cells().len() as u64);
+        let expanded = read.to_pattern(64, 64)?;
+        assert_eq!(expanded.alive_world_cells(), pattern.alive_world_cells());
+        let occupied = |pattern: &Pattern| pattern.cells.iter().filter(|cell| !cell.is_dead()).count();
+        assert_eq!(occupied(&expanded), occupied(&pattern));
+        assert_eq!(expanded.rule, pattern.rule);
+        passed_tests += 1;
+    }
+
+    // Two gliders 2^40 cells apart: a root of level 42 whose north-west and south-east quadrants
+    // are the same chain of nodes leading down to a glider.
+    let mut text = String::from("[M2] (golly 4.2)\n#R B3/S23\n.*$..*$***$\n");
+    for level in 4..=41 {
+        text.push_str(&format!("{} {} 0 0 0\n", level, level - 3));
+    }
+    text.push_str("42 39 0 0 39\n");
+    let far = macrocell::parse(&text)?;
+    assert_eq!((far.level(), far.population()), (42, 10));
+    assert!(far.node_count() < 100, "The quadtree has {} nodes", far.node_count());
+    let half = 1_i64 << 41;
+    assert_eq!(far.bounds(), Some((CellCoord::new(-half, -half), (1 << 41) + 3, (1 << 41) + 3)));
+    assert!(far.state(CellCoord::new(-half + 1, -half)).is_alive());
+    assert!(far.state(CellCoord::new(1, 0)).is_alive());
+    let error = far.to_pattern(512, 512).expect_err("The gliders are too far apart to expand");
+    assert!(error.to_string().contains("does not fit in 512x512"), "Unexpected error: {}", error);
+    let error = Format::Macrocell.parse(&text).expect_err("The gliders are too far apart to expand at all");
+    assert!(format!("{:#}", error).contains("pattern is too large"), "Unexpected error: {:#}", error);
+    let written = macrocell::write(&far);
+    assert_eq!(written.lines().count(), text.lines().count());
+    let read = macrocell::parse(&written)?;
+    assert_eq!((read.bounds(), read.population()), (far.bounds(), far.population()));
+    passed_tests += 1;
+
+    // A 2^30 square tiled with blocks, sharing one node per level.
+    let mut text = String::from("[M2] (golly 4.2)\n**$**$\n");
+    for level in 4..=30 {
+        text.push_str(&format!("{} {1} {1} {1} {1}\n", level, level - 3));
+    }
+    let tiled = macrocell::parse(&text)?;
+    assert_eq!(tiled.population(), 4 << (2 * 27));
+    assert_eq!(tiled.bounds(), Some((CellCoord::new(-(1 << 29), -(1 << 29)), (1 << 30) - 6, (1 << 30) - 6)));
+    assert_eq!(macrocell::write(&tiled).lines().count(), text.lines().count());
+    let blocks = (0..256_i64).flat_map(|y| (0..256).map(move |x| CellCoord::new(x, y)))
+        .filter(|cell| cell.x % 8 < 2 && cell.y % 8 < 2)
+        .collect();
+    let small = Quadtree::from_pattern(&Pattern { origin: Some(CellCoord::new(-128, -128)), ..board(blocks, 256, 256) })?;
+    assert!(small.node_count() < 32, "The tiled board has {} nodes", small.node_count());
+    passed_tests += 1;
+    Ok(passed_tests)
+}
+
+/// A run reads a macrocell pattern that fits on the board into a flat world, and refuses one that does not.
+async fn test_macrocell_run(args: Args, directory: &Path) -> Result<usize> {
+    let mut passed_tests = 0;
+    std::fs::create_dir_all(directory)?;
+    let input = directory.join("glider_gun.mc");
+    let gun = Pattern { rule: Some(Rule::CONWAY), ..board(read_alive_cells("images/64x64.pgm", 64, 64)?, 64, 64) };
+    std::fs::write(&input, Format::Macrocell.write(&gun)?)?;
+    let origin = Quadtree::from_pattern(&gun)?.bounds().context("The gun should have alive cells")?.0;
+    let args = args
+        .input(&input)
+        .turns(100)
+        .image_width(64)
+        .image_height(64)
+        .place(Placement::At(CellCoord::new(origin.x as usize, origin.y as usize)))
+        .output_dir(directory)
+        .output_name("gun-{turn}.mc");
+    log::debug!(target: "Test", "{} - {:?}", "Testing macrocell input and output".cyan(), Params::from(args.clone()));
+    let Outcome { alive, filename, .. } = run(args.clone()).await?;
+    let filename = filename.context("No image was written")?;
+    assert_eq!(filename, "gun-100.mc");
+    let expected = read_alive_cells("check/images/64x64x100.pgm", 64, 64)?.into_iter().collect::<HashSet<_>>();
+    assert_eq!(alive.into_iter().collect::<HashSet<_>>(), expected);
+    let written = macrocell::parse(&std::fs::read_to_string(directory.join(&filename))?)?;
+    assert_eq!(written.population(), expected.len() as u64);
+    assert!(expected.iter().all(|&cell| written.state(cell).is_alive()));
+    passed_tests += 1;
+
+    // The size of a macrocell pattern is its bounding box, and one larger than the board is refused.
+    let large = directory.join("large.mc");
+    std::fs::write(&large, "[M2] (golly 4.2)\n*$\n4 1 0 0 1\n")?;
+    let args = args.input(&large).turns(1).place(Placement::Centre);
+    assert_eq!(Args::default().input(&large).size()?, (9, 9));
+    let error = run(args.image_width(8).image_height(8)).await.expect_err("The pattern should not fit");
+    assert!(format!("{:#}", error).contains("The 9x9 macrocell pattern does not fit in 8x8"), "Unexpected error: {:#}", error);
+    passed_tests += 1;
+    Ok(passed_tests)
+}
+
+/// A two state pattern of the `width` by `height` board with these alive cells.
+fn board(alive: Vec<CellCoord<i64>>, width: usize, height: usize) -> Pattern {
+    let mut cells = vec![CellState::DEAD; width * height];
+    for cell in alive {
+        cells[cell.y as usize * width + cell.x as usize] = CellState::ALIVE;
+    }
+    Pattern::new(width, height, cells)
+}
+
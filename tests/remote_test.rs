@@ -0,0 +1,196 @@
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use gol_rs::args::Args;
+use gol_rs::gol::{event::{Event, State}, remote::{self, Hello}, Params};
+use gol_rs::gol::rule::Rule;
+use gol_rs::util::{cell::{CellCoord, CellState}, logger};
+use log::Level;
+use sdl2::keyboard::Keycode;
+use std::collections::HashSet;
+use std::io::Cursor;
+use std::net::TcpListener;
+use std::time::Duration;
+use utils::{common::deadline, io::read_alive_cells};
+
+mod utils;
+
+#[tokio::main]
+async fn main() {
+    let start = std::time::Instant::now();
+    logger::set_panic_hook();
+    logger::init(Level::Debug, false);
+
+    let passed_tests = test_protocol().unwrap()
+        + test_attach(Args::default()).await.unwrap();
+
+    println!(
+        "\ntest result: {}. {} passed; finished in {:.2}s\n",
+        "ok".green(),
+        passed_tests,
+        start.elapsed().as_secs_f32()
+    );
+    std::process::exit(0);
+}
+
+/// Hellos, events and keys read back as they were written, and a closed connection reads as none.
+fn test_protocol() -> Result<usize> {
+    log::debug!(target: "Test", "{}", "Testing remote protocol".cyan());
+    let hello = Hello { width: 70, height: 33, rule: "B2/S/C3".parse::<Rule>()?, unbounded: true };
+    let mut bytes = Vec::new();
+    remote::write_hello(&mut bytes, &hello)?;
+    assert_eq!(remote::read_hello(&mut Cursor::new(bytes))?, hello);
+
+    let cells = vec![CellCoord::new(-3, 4), CellCoord::new(i64::MAX, i64::MIN)];
+    let events = [
+        Event::AliveCellsCount { completed_turns: 1, cells_count: 20 },
+        Event::ImageOutputComplete { completed_turns: 2, filename: "64x64x2".to_owned() },
+        Event::StateChange { completed_turns: 3, new_state: State::Pause },
+        Event::StateChange { completed_turns: 3, new_state: State::Quitting },
+        Event::CellFlipped { completed_turns: 4, cell: CellCoord::new(-1, -2) },
+        Event::CellsFlipped { completed_turns: 5, cells: cells.clone() },
+        Event::CellsChanged { completed_turns: 6, cells: vec![(CellCoord::new(0, 7), CellState::new(2))] },
+        Event::TurnComplete { completed_turns: u32::MAX },
+        Event::Stabilised { completed_turns: 8, period: 15 },
+        Event::FinalTurnComplete { completed_turns: 9, alive: cells },
+    ];
+    let mut bytes = Vec::new();
+    for event in &events {
+        remote::write_event(&mut bytes, event)?;
+    }
+    let mut reader = Cursor::new(bytes);
+    for event in &events {
+        let read = remote::read_event(&mut reader, 2)?.context("The event was not read back")?;
+        assert_eq!(format!("{:?}", read), format!("{:?}", event));
+    }
+    assert!(remote::read_event(&mut reader, 2)?.is_none());
+    let error = remote::read_event(&mut Cursor::new(vec![42, 0, 0, 0, 0]), 2).expect_err("The event should be unknown");
+    assert!(error.to_string().contains("unknown event"), "Unexpected error: {}", error);
+
+    // A list longer than the world is refused before anything is read into it.
+    assert_eq!(Hello { unbounded: false, ..hello.clone() }.max_cells(), 70 * 33);
+    assert_eq!(hello.max_cells(), usize::MAX);
+    let error = remote::read_event(&mut Cursor::new(vec![5, 0, 0, 0, 0, 255, 255, 255, 255]), 70 * 33)
+        .expect_err("The list should be too long");
+    assert!(error.to_string().contains("at most 2310"), "Unexpected error: {}", error);
+
+    let keys = [Keycode::S, Keycode::P, Keycode::Q, Keycode::K, Keycode::B];
+    let mut bytes = Vec::new();
+    for key in &keys {
+        remote::write_key(&mut bytes, key)?;
+    }
+    let mut reader = Cursor::new(bytes);
+    for key in keys {
+        assert_eq!(remote::read_key(&mut reader)?, Some(key));
+    }
+    assert_eq!(remote::read_key(&mut reader)?, None);
+    assert!(remote::write_key(&mut Vec::new(), &Keycode::X).is_err(), "Only the keys a run handles can be sent");
+    Ok(1)
+}
+
+/// Q detaches the controller and leaves the run going. A controller attaching later gets the board
+/// as it is now and then every event after it, and can detach again, or finish the run with K.
+async fn test_attach(args: Args) -> Result<usize> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let address = listener.local_addr()?;
+    let args = args
+        .turns(10_000_000)
+        .image_width(64)
+        .image_height(64);
+    log::debug!(target: "Test", "{} - {:?}", "Testing detach and attach".cyan(), Params::from(args.clone()));
+    let deadline = deadline(Duration::from_secs(60), "Detaching and attaching should complete within 60 seconds");
+    let (key_presses_tx, key_presses_rx) = flume::bounded::<Keycode>(10);
+    let (events_tx, events_rx) = flume::bounded::<Event>(1000);
+    let gol = tokio::spawn(remote::run(args, listener, events_tx, key_presses_rx));
+
+    // The controller that started the run detaches without the run finishing.
+    let mut detached = false;
+    while let Ok(event) = events_rx.recv_async().await {
+        match event {
+            Event::TurnComplete { completed_turns } if !detached && completed_turns >= 10 => {
+                key_presses_tx.send_async(Keycode::Q).await?;
+                detached = true;
+            },
+            Event::FinalTurnComplete { .. } | Event::StateChange { new_state: State::Quitting, .. } =>
+                bail!("Q should detach the controller rather than finish the run"),
+            _ => (),
+        }
+    }
+    assert!(detached);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(!gol.is_finished(), "The run should carry on without a controller");
+
+    // The first controller to attach sees the run carry on, pauses it, saves it and detaches.
+    let (hello, events, key_presses) = remote::attach(address)?;
+    assert_eq!(hello, Hello { width: 64, height: 64, rule: Rule::CONWAY, unbounded: false });
+    let mut board = HashSet::new();
+    let attached_at = match events.recv_async().await? {
+        Event::CellsFlipped { cells, .. } => {
+            flip(&mut board, &cells);
+            match events.recv_async().await? {
+                Event::TurnComplete { completed_turns } => completed_turns,
+                event => bail!("The board should be followed by TurnComplete, not {:?}", event),
+            }
+        },
+        event => bail!("Attaching should start with the board, not {:?}", event),
+    };
+    assert!(attached_at >= 10, "Attached at turn {}, before the controller detached", attached_at);
+    let (mut pausing, mut paused_at, mut saved) = (false, None, None);
+    while let Ok(event) = events.recv_async().await {
+        match event {
+            Event::CellsFlipped { cells, .. } => flip(&mut board, &cells),
+            // More turns complete before the pause takes effect, and pressing P again would resume the run.
+            Event::TurnComplete { completed_turns } if !pausing && completed_turns >= attached_at + 10 => {
+                key_presses.send_async(Keycode::P).await?;
+                pausing = true;
+            },
+            Event::StateChange { completed_turns, new_state: State::Pause } => {
+                paused_at = Some(completed_turns);
+                key_presses.send_async(Keycode::S).await?;
+            },
+            Event::ImageOutputComplete { filename, .. } => {
+                saved = Some(filename);
+                key_presses.send_async(Keycode::Q).await?;
+            },
+            _ => (),
+        }
+    }
+    let paused_at = paused_at.context("The run did not pause")?;
+    let saved = read_alive_cells(format!("out/{}.pgm", saved.context("The run was not saved")?), 64, 64)?;
+    assert_eq!(board, saved.into_iter().collect(), "The board followed from the events is not the saved one");
+    assert!(!gol.is_finished(), "The run should carry on after detaching again");
+
+    // The next controller to attach gets the same board, sees the run is paused, and finishes it with K.
+    let (_, events, key_presses) = remote::attach(address)?;
+    let mut next = HashSet::new();
+    let mut final_turn_complete = false;
+    match events.recv_async().await? {
+        Event::CellsFlipped { cells, .. } => flip(&mut next, &cells),
+        event => bail!("Attaching should start with the board, not {:?}", event),
+    }
+    assert_eq!(next, board, "The board sent on attaching is not the one the run is paused on");
+    while let Ok(event) = events.recv_async().await {
+        match event {
+            Event::TurnComplete { completed_turns } => assert_eq!(completed_turns, paused_at),
+            Event::StateChange { new_state: State::Pause, .. } => key_presses.send_async(Keycode::K).await?,
+            Event::FinalTurnComplete { completed_turns, alive } => {
+                assert_eq!(completed_turns, paused_at);
+                assert_eq!(alive.into_iter().collect::<HashSet<_>>(), board);
+                final_turn_complete = true;
+            },
+            Event::StateChange { new_state: State::Quitting, .. } => assert!(final_turn_complete),
+            _ => (),
+        }
+    }
+    assert!(final_turn_complete, "K should finish the run");
+    gol.await??;
+    deadline.abort();
+    Ok(1)
+}
+
+fn flip(board: &mut HashSet<CellCoord<i64>>, cells: &[CellCoord<i64>]) {
+    for &cell in cells {
+        if !board.remove(&cell) {
+            board.insert(cell);
+        }
+    }
+}
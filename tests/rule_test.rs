@@ -0,0 +1,356 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use colored::Colorize;
+use gol_rs::{args::Args, gol::{self, engine::EngineKind, event::Event, rule::{Neighbourhood, ParseRuleError, Rule}, Params}, util::{cell::CellCoord, logger}};
+use log::Level;
+use sdl2::keyboard::Keycode;
+use utils::{io::read_alive_cells, run::{run, Outcome}, visualise::assert_eq_board};
+
+mod utils;
+
+#[tokio::main]
+async fn main() {
+    let start = std::time::Instant::now();
+    logger::set_panic_hook();
+    logger::init(Level::Debug, false);
+
+    let passed_tests = test_parse().unwrap()
+        + test_parse_larger_than_life().unwrap()
+        + test_hensel().unwrap()
+        + test_parse_errors().unwrap()
+        + test_rules(Args::default().threads(4)).await.unwrap()
+        + test_generations(Args::default().threads(4)).await.unwrap()
+        + test_larger_than_life(Args::default().threads(4)).await.unwrap()
+        + test_unsupported(Args::default()).await.unwrap();
+
+    println!(
+        "\ntest result: {}. {} passed; finished in {:.2}s\n",
+        "ok".green(),
+        passed_tests,
+        start.elapsed().as_secs_f32()
+    );
+    std::process::exit(0);
+}
+
+/// Rulestrings in B/S, S/B and bare S/B notation parse to the same rule.
+fn test_parse() -> Result<usize> {
+    let mut passed_tests = 0;
+    let cases = [
+        ("B3/S23", Rule::CONWAY),
+        ("b3/s23", Rule::CONWAY),
+        ("S23/B3", Rule::CONWAY),
+        ("23/3", Rule::CONWAY),
+        ("B36/S23", Rule::new(&[3, 6], &[2, 3])),
+        ("23/36", Rule::new(&[3, 6], &[2, 3])),
+        ("B2/S", Rule::new(&[2], &[])),
+        ("/2", Rule::new(&[2], &[])),
+        ("B3678/S34678", Rule::new(&[3, 6, 7, 8], &[3, 4, 6, 7, 8])),
+        ("B2/S/C3", Rule::new(&[2], &[]).with_states(3)),
+        ("/2/3", Rule::new(&[2], &[]).with_states(3)),
+        ("345/2/4", Rule::new(&[2], &[3, 4, 5]).with_states(4)),
+        ("B3/S23/C2", Rule::CONWAY),
+    ];
+    for (rulestring, expected) in cases {
+        log::debug!(target: "Test", "{} - {}", "Testing rule parsing".cyan(), rulestring);
+        let rule = rulestring.parse::<Rule>()?;
+        assert_eq!(rule, expected, "{} parsed to {}", rulestring, rule);
+        assert_eq!(rule.to_string().parse::<Rule>()?, rule, "{} does not round trip", rule);
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
+
+/// Larger than Life rulestrings parse in Golly's notation, and equivalent spellings parse to the same rule.
+fn test_parse_larger_than_life() -> Result<usize> {
+    let mut passed_tests = 0;
+    let cases = [
+        ("R5,C0,M1,S34..58,B34..45,NM", 5, Neighbourhood::Moore, true, 2, 121),
+        ("R3,C0,M0,S6..10,B7..9,NN", 3, Neighbourhood::VonNeumann, false, 2, 24),
+        ("R10,C4,M0,S150..300,B200..280,NM", 10, Neighbourhood::Moore, false, 4, 440),
+    ];
+    for (rulestring, range, neighbourhood, middle, states, max_neighbours) in cases {
+        log::debug!(target: "Test", "{} - {}", "Testing rule parsing".cyan(), rulestring);
+        let rule = rulestring.parse::<Rule>()?;
+        assert!(rule.is_larger_than_life(), "{} is not Larger than Life", rule);
+        assert_eq!(
+            (rule.range(), rule.neighbourhood(), rule.middle(), rule.states(), rule.max_neighbours()),
+            (range, neighbourhood, middle, states, max_neighbours),
+            "{} parsed to {:?}", rulestring, rule
+        );
+        assert_eq!(rule.to_string(), rulestring, "{} does not round trip", rulestring);
+        passed_tests += 1;
+    }
+
+    let bosco = "R5,C0,M1,S34..58,B34..45,NM".parse::<Rule>()?;
+    assert!(bosco.next(false, 34) && bosco.next(false, 45) && !bosco.next(false, 46));
+    assert!(bosco.next(true, 58) && !bosco.next(true, 33));
+    for rulestring in ["r5,c0,m1,s34..58,b34..45,nm", "R5,C1,M1,S34..58,B34..45", "R5,C2,M1,S34..58,B34..45,NM"] {
+        assert_eq!(rulestring.parse::<Rule>()?, bosco, "{} should parse to {}", rulestring, bosco);
+        passed_tests += 1;
+    }
+    let conway = "R1,C0,M0,S2..3,B3..3,NM".parse::<Rule>()?;
+    assert_eq!(conway, Rule::CONWAY, "A range 1 Moore rule without its middle cell should be Life-like");
+    passed_tests += 1;
+    Ok(passed_tests)
+}
+
+/// Hensel letters expand to the neighbourhoods of the known letter chart, in every rotation and
+/// reflection, and to nothing else. Letters for 5 to 7 neighbours are the complements of those for 3 to 1.
+fn test_hensel() -> Result<usize> {
+    let mut passed_tests = 0;
+    // One drawing of each letter around the cell `x`, with how many distinct rotations and reflections it has.
+    let chart = [
+        ("1c", ["...", ".x.", "..O"], 4),
+        ("1e", ["...", ".xO", "..."], 4),
+        ("2c", ["...", ".x.", "O.O"], 4),
+        ("2e", ["...", ".xO", ".O."], 4),
+        ("2k", ["...", ".xO", "O.."], 8),
+        ("2a", ["...", ".xO", "..O"], 8),
+        ("2i", ["...", "OxO", "..."], 2),
+        ("2n", ["O..", ".x.", "..O"], 2),
+        ("3c", ["O..", ".x.", "O.O"], 4),
+        ("3e", ["...", "OxO", ".O."], 4),
+        ("3k", ["O..", ".xO", ".O."], 4),
+        ("3a", ["...", ".xO", ".OO"], 4),
+        ("3i", ["..O", ".xO", "..O"], 4),
+        ("3n", ["...", ".xO", "O.O"], 8),
+        ("3y", ["O..", ".xO", "O.."], 4),
+        ("3q", ["O..", ".xO", "..O"], 8),
+        ("3j", [".O.", ".xO", "..O"], 8),
+        ("3r", ["...", "OxO", "..O"], 8),
+        ("4c", ["O.O", ".x.", "O.O"], 1),
+        ("4e", [".O.", "OxO", ".O."], 1),
+        ("4k", [".O.", ".xO", "O.O"], 8),
+        ("4a", ["...", ".xO", "OOO"], 8),
+        ("4i", ["...", "OxO", "O.O"], 4),
+        ("4n", ["..O", ".xO", "O.O"], 8),
+        ("4y", ["O..", ".xO", "O.O"], 8),
+        ("4q", ["O..", ".xO", ".OO"], 4),
+        ("4j", [".O.", "OxO", "..O"], 8),
+        ("4r", ["...", "OxO", ".OO"], 8),
+        ("4t", ["..O", "OxO", "..O"], 4),
+        ("4w", ["OO.", ".xO", "..O"], 4),
+        ("4z", ["O..", "OxO", "..O"], 4),
+    ];
+    for (name, picture, symmetries) in chart {
+        let neighbourhood = picture.concat().chars().enumerate()
+            .filter(|&(_, cell)| cell == 'O')
+            .fold(0_u16, |index, (i, _)| index | 1 << i);
+        let (n, letter) = (name[..1].parse::<u8>()?, &name[1..]);
+        let mut cases = vec![(n, neighbourhood)];
+        if n < 4 {
+            cases.push((8 - n, !neighbourhood & 0b111_101_111));
+        }
+        for (n, neighbourhood) in cases {
+            log::debug!(target: "Test", "{} - {}{}", "Testing Hensel letter".cyan(), n, letter);
+            let rule = format!("B{}{}/S", n, letter).parse::<Rule>()?;
+            assert!(rule.transition(neighbourhood), "B{}{} does not give birth to {:?}", n, letter, picture);
+            let births = (0..512).filter(|&index| rule.transition(index)).count();
+            assert_eq!(births, symmetries, "B{}{} gives birth to the wrong number of neighbourhoods", n, letter);
+            let rule = format!("B{}-{}/S", n, letter).parse::<Rule>()?;
+            assert!(!rule.transition(neighbourhood), "B{}-{} gives birth to {:?}", n, letter, picture);
+            passed_tests += 1;
+        }
+    }
+
+    for rulestring in ["B2-a/S12", "B3/S2-i34q", "B2ce/S", "B3-cnyq/S23-a4iyt", "B2a/S12/C3"] {
+        log::debug!(target: "Test", "{} - {}", "Testing rule parsing".cyan(), rulestring);
+        let rule = rulestring.parse::<Rule>()?;
+        assert!(!rule.is_totalistic(), "{} should not be totalistic", rulestring);
+        assert_eq!(rule.to_string(), rulestring, "{} does not round trip", rulestring);
+        passed_tests += 1;
+    }
+    let equivalent = [
+        ("B2ceikn/S12", "B2-a/S12"),
+        ("b2-a/s1ce2", "B2-a/S12"),
+        ("12/2-a", "B2-a/S12"),
+    ];
+    for (rulestring, expected) in equivalent {
+        assert_eq!(rulestring.parse::<Rule>()?, expected.parse::<Rule>()?, "{} should parse to {}", rulestring, expected);
+        passed_tests += 1;
+    }
+    let conway = "B3cekainyqjr/S2cekain3".parse::<Rule>()?;
+    assert!(conway.is_totalistic(), "Every letter of a count should be the same as the count alone");
+    assert_eq!(conway, Rule::CONWAY);
+    passed_tests += 1;
+    Ok(passed_tests)
+}
+
+/// Invalid rulestrings return the matching parse error.
+fn test_parse_errors() -> Result<usize> {
+    let mut passed_tests = 0;
+    let cases = [
+        ("", ParseRuleError::Empty),
+        ("B3S23", ParseRuleError::MissingSeparator("B3S23".to_string())),
+        ("B3/S2/3/4", ParseRuleError::MissingSeparator("B3/S2/3/4".to_string())),
+        ("B3/B23", ParseRuleError::DuplicatePart("b".to_string())),
+        ("B3/23", ParseRuleError::MixedNotation("B3/23".to_string())),
+        ("B39/S23", ParseRuleError::InvalidCount('9')),
+        ("B3/S2!", ParseRuleError::InvalidCount('!')),
+        ("B3/S2x", ParseRuleError::InvalidLetter(2, 'x')),
+        ("B1a/S", ParseRuleError::InvalidLetter(1, 'a')),
+        ("B0c/S", ParseRuleError::InvalidLetter(0, 'c')),
+        ("B4-tq/S5z", ParseRuleError::InvalidLetter(5, 'z')),
+        ("B2-A/S12", ParseRuleError::InvalidLetter(2, 'A')),
+        ("B2-/S12", ParseRuleError::InvalidLetter(2, '-')),
+        ("B2a2e/S", ParseRuleError::RepeatedCount(2)),
+        ("B33/S23", ParseRuleError::RepeatedCount(3)),
+        ("B2/S/C1", ParseRuleError::InvalidStates("C1".to_string())),
+        ("B2/S/Cx", ParseRuleError::InvalidStates("Cx".to_string())),
+        ("B2/S/256", ParseRuleError::InvalidStates("256".to_string())),
+        ("R0,C0,M1,S34..58,B34..45,NM", ParseRuleError::InvalidRange("0".to_string())),
+        ("R11,C0,M1,S34..58,B34..45,NM", ParseRuleError::InvalidRange("11".to_string())),
+        ("R5,C0,M2,S34..58,B34..45,NM", ParseRuleError::InvalidLargerThanLife("M2".to_string())),
+        ("R5,C0,S34..58,B34..45,NM", ParseRuleError::InvalidLargerThanLife("S34..58".to_string())),
+        ("R5,C0,M1,S34..58", ParseRuleError::InvalidLargerThanLife("B".to_string())),
+        ("R5,C0,M1,S34..58,B34..45,NX", ParseRuleError::InvalidLargerThanLife("NX".to_string())),
+        ("R5,C0,M1,S34..58,B34..45,NM,X", ParseRuleError::InvalidLargerThanLife("X".to_string())),
+        ("R5,C0,M1,S58..34,B34..45,NM", ParseRuleError::InvalidInterval("58..34".to_string())),
+        ("R5,C0,M1,S34..58,B34..122,NM", ParseRuleError::InvalidInterval("34..122".to_string())),
+        ("R1,C0,M0,S2..9,B3..3,NM", ParseRuleError::InvalidInterval("2..9".to_string())),
+        ("R5,C0,M1,S34,B34..45,NM", ParseRuleError::InvalidInterval("34".to_string())),
+        ("R5,C1000,M1,S34..58,B34..45,NM", ParseRuleError::InvalidStates("1000".to_string())),
+    ];
+    for (rulestring, expected) in cases {
+        log::debug!(target: "Test", "{} - {:?}", "Testing rule parse error".cyan(), rulestring);
+        assert_eq!(rulestring.parse::<Rule>(), Err(expected), "Wrong error for {:?}", rulestring);
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
+
+/// Every engine reproduces the HighLife, Seeds and isotropic non-totalistic goldens in check/rules.
+async fn test_rules(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    let cases = [
+        ("highlife", "B36/S23", 16_usize),
+        ("highlife", "B36/S23", 64),
+        ("seeds", "B2/S", 16),
+        ("b2-a-s12", "B2-a/S12", 16),
+        ("b2-a-s12", "B2-a/S12", 64),
+        ("tlife", "B3/S2-i34q", 16),
+        ("tlife", "B3/S2-i34q", 64),
+    ];
+    for (name, rulestring, size) in cases {
+        for expected_turns in [1_usize, 100] {
+            let path = format!("check/rules/{}/{}x{}x{}.pgm", name, size, size, expected_turns);
+            let expected_alive = read_alive_cells(path, size, size)?;
+            for &engine in EngineKind::value_variants() {
+                let args = args.clone()
+                    .rule(rulestring.parse()?)
+                    .engine(engine)
+                    .turns(expected_turns)
+                    .image_width(size)
+                    .image_height(size);
+                log::debug!(target: "Test", "{} - {:?}", "Testing Rule".cyan(), Params::from(args.clone()));
+                let Outcome { alive, .. } = run(args.clone()).await?;
+                assert_eq_board(args, &alive, &expected_alive);
+                passed_tests += 1;
+            }
+        }
+    }
+    Ok(passed_tests)
+}
+
+/// Engines that support Generations rules reproduce the Brian's Brain goldens in check/rules,
+/// both in the `CellsChanged` events and in the grey levels of the output image.
+async fn test_generations(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    let size = 16;
+    let rule = "B2/S/C3".parse::<Rule>()?;
+    for expected_turns in [1_usize, 100] {
+        let path = format!("check/rules/briansbrain/{}x{}x{}.pgm", size, size, expected_turns);
+        let expected = read_pgm_bytes(&path)?;
+        let expected_alive = expected.iter().enumerate()
+            .filter(|&(_, &grey)| grey == 255)
+            .map(|(i, _)| CellCoord::new((i % size) as i64, (i / size) as i64))
+            .collect::<Vec<_>>();
+        for engine in EngineKind::value_variants().iter().filter(|engine| engine.supports(&rule)) {
+            let args = args.clone()
+                .rule(rule)
+                .engine(*engine)
+                .turns(expected_turns)
+                .image_width(size)
+                .image_height(size);
+            log::debug!(target: "Test", "{} - {:?}", "Testing Generations".cyan(), Params::from(args.clone()));
+            let Outcome { alive, changed, .. } = run(args.clone()).await?;
+            assert_eq_board(args, &alive, &expected_alive);
+            let mut greys = vec![0_u8; size * size];
+            for (cell, state) in changed {
+                greys[cell.y as usize * size + cell.x as usize] = state.grey(rule.states());
+            }
+            assert_eq!(greys, expected, "CellsChanged events do not match {}", path);
+            let output = read_pgm_bytes(format!("out/{}x{}x{}.pgm", size, size, expected_turns))?;
+            assert_eq!(output, expected, "Output image does not match {}", path);
+            passed_tests += 1;
+        }
+    }
+    Ok(passed_tests)
+}
+
+/// Engines that support range rules reproduce the Larger than Life goldens in check/rules,
+/// which cover Moore and von Neumann neighbourhoods up to range 10, with and without refractory states.
+async fn test_larger_than_life(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    let size = 64;
+    let cases = [
+        ("bosco", "R5,C0,M1,S34..58,B34..45,NM", [1_usize, 10]),
+        ("vonneumann", "R3,C0,M0,S6..10,B7..9,NN", [1, 100]),
+        ("range10", "R10,C4,M0,S150..300,B200..280,NM", [1, 5]),
+    ];
+    for (name, rulestring, turns) in cases {
+        let rule = rulestring.parse::<Rule>()?;
+        for expected_turns in turns {
+            let path = format!("check/rules/{}/{}x{}x{}.pgm", name, size, size, expected_turns);
+            let expected = read_pgm_bytes(&path)?;
+            let expected_alive = expected.iter().enumerate()
+                .filter(|&(_, &grey)| grey == 255)
+                .map(|(i, _)| CellCoord::new((i % size) as i64, (i / size) as i64))
+                .collect::<Vec<_>>();
+            for engine in EngineKind::value_variants().iter().filter(|engine| engine.supports(&rule)) {
+                let args = args.clone()
+                    .rule(rule)
+                    .engine(*engine)
+                    .turns(expected_turns)
+                    .image_width(size)
+                    .image_height(size);
+                log::debug!(target: "Test", "{} - {:?}", "Testing Larger than Life".cyan(), Params::from(args.clone()));
+                let Outcome { alive, .. } = run(args.clone()).await?;
+                assert_eq_board(args, &alive, &expected_alive);
+                let output = read_pgm_bytes(format!("out/{}x{}x{}.pgm", size, size, expected_turns))?;
+                assert_eq!(output, expected, "Output image does not match {}", path);
+                passed_tests += 1;
+            }
+        }
+    }
+    Ok(passed_tests)
+}
+
+/// Engines that only step Life-like rules refuse other rules instead of running them as Life-like.
+async fn test_unsupported(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    for rulestring in ["B2/S/C3", "R5,C0,M1,S34..58,B34..45,NM"] {
+        let rule = rulestring.parse::<Rule>()?;
+        for engine in EngineKind::value_variants().iter().filter(|engine| !engine.supports(&rule)) {
+            let args = args.clone()
+                .rule(rule)
+                .engine(*engine)
+                .turns(1)
+                .image_width(16)
+                .image_height(16);
+            log::debug!(target: "Test", "{} - {:?} {}", "Testing unsupported rule".cyan(), engine, rule);
+            let (_key_presses_tx, key_presses_rx) = flume::bounded::<Keycode>(10);
+            let (events_tx, _events_rx) = flume::bounded::<Event>(1000);
+            let error = gol::run(args, events_tx, key_presses_rx).await
+                .expect_err("The rule should not run on this engine");
+            assert!(error.to_string().contains("does not support"), "Unexpected error: {}", error);
+            passed_tests += 1;
+        }
+    }
+    Ok(passed_tests)
+}
+
+/// The raw grey levels of a PGM image.
+fn read_pgm_bytes<P: AsRef<std::path::Path>>(path: P) -> Result<Vec<u8>> {
+    Ok(image::ImageReader::open(path)?.decode()?.into_bytes())
+}
@@ -104,14 +104,15 @@ impl Tester {
         gol_done: Receiver<()>,
     ) -> Result<()> {
         let (watcher_tx, watcher_rx) = flume::unbounded::<Event>();
+        let (width, height) = args.size()?;
         let mut tester = Tester {
             args: args.clone(),
             key_presses,
             events,
             events_watcher: watcher_rx,
             turn: 0,
-            world: vec![vec![CellValue::Dead; args.image_width]; args.image_height],
-            alive_map: read_alive_counts(args.image_width as u32, args.image_height as u32)?,
+            world: vec![vec![CellValue::Dead; width]; height],
+            alive_map: read_alive_counts(width as u32, height as u32)?,
         };
 
         tokio::spawn(tester.test_pause(Duration::from_secs(3)));
@@ -133,13 +134,13 @@ impl Tester {
                             cell_flipped_received = true;
                             assert!(completed_turns == tester.turn || completed_turns == tester.turn + 1,
                                 "Expected completed {} turns, got {} instead", tester.turn, completed_turns);
-                            tester.world[cell.y][cell.x].flip();
+                            tester.world[cell.y as usize][cell.x as usize].flip();
                         },
                         Ok(Event::CellsFlipped { completed_turns, cells }) => {
                             cell_flipped_received = true;
                             assert!(completed_turns == tester.turn || completed_turns == tester.turn + 1,
                                 "Expected completed {} turns, got {} instead", tester.turn, completed_turns);
-                            cells.iter().for_each(|cell| tester.world[cell.y][cell.x].flip());
+                            cells.iter().for_each(|cell| tester.world[cell.y as usize][cell.x as usize].flip());
                         },
                         Ok(Event::TurnComplete { completed_turns }) => {
                             turn_complete_received = true;
@@ -176,6 +177,7 @@ impl Tester {
     fn test_alive(&self) {
         let alive_count = self.world.iter()
             .flatten().filter(|&&cell| cell.is_alive()).count();
+        #[allow(clippy::manual_is_multiple_of)]
         let expected = if self.turn <= 10000 { *self.alive_map.get(&self.turn).unwrap() }
             else if self.turn % 2 == 0 { 5565 } else { 5567 };
         assert_eq!(
@@ -186,24 +188,16 @@ impl Tester {
 
     fn test_gol(&self) {
         if self.turn == 0 || self.turn == 1 || self.turn == 100 {
-            let path = format!(
-                "check/images/{}x{}x{}.pgm",
-                self.args.image_width,
-                self.args.image_height,
-                self.turn
-            );
-            let expected_alive = read_alive_cells(
-                path,
-                self.args.image_width,
-                self.args.image_height
-            ).unwrap();
+            let (width, height) = self.args.size().unwrap();
+            let path = format!("check/images/{}x{}x{}.pgm", width, height, self.turn);
+            let expected_alive = read_alive_cells(path, width, height).unwrap();
 
             let alive_cells = self.world.iter().enumerate()
                 .flat_map(|(y, row)|
                     row.iter().enumerate()
                         .filter(|&(_, &cell)| cell.is_alive())
-                        .map(move |(x, _)| CellCoord::new(x, y)))
-                .collect::<Vec<CellCoord>>();
+                        .map(move |(x, _)| CellCoord::new(x as i64, y as i64)))
+                .collect::<Vec<CellCoord<i64>>>();
             assert_eq_board(self.args.clone(), &alive_cells, &expected_alive);
         }
     }
@@ -211,7 +205,7 @@ impl Tester {
     fn test_output(&self, delay: Duration) -> impl Future<Output = ()> {
         let key_presses = self.key_presses.clone();
         let event_watcher = self.events_watcher.clone();
-        let (width, height) = (self.args.image_width, self.args.image_height);
+        let (width, height) = self.args.size().unwrap();
         async move {
             tokio::time::sleep(delay).await;
             log::debug!(target: "Test", "{}", "Testing image output".cyan());
@@ -0,0 +1,138 @@
+use anyhow::Result;
+use colored::Colorize;
+use gol_rs::args::Args;
+use gol_rs::gol::{engine::EngineKind, Params};
+use gol_rs::gol::rule::Rule;
+use gol_rs::gol::snapshot::{self, SnapshotHeader, MAGIC, VERSION};
+use gol_rs::gol::topology::Topology;
+use gol_rs::util::{cell::{CellCoord, CellState}, logger};
+use log::Level;
+use std::path::Path;
+use utils::run::{run, Outcome};
+
+mod utils;
+
+#[tokio::main]
+async fn main() {
+    let start = std::time::Instant::now();
+    logger::set_panic_hook();
+    logger::init(Level::Debug, false);
+
+    let passed_tests = test_format().unwrap()
+        + test_resume(Args::default()).await.unwrap()
+        + test_resume_errors(Args::default()).await.unwrap();
+
+    println!(
+        "\ntest result: {}. {} passed; finished in {:.2}s\n",
+        "ok".green(),
+        passed_tests,
+        start.elapsed().as_secs_f32()
+    );
+    std::process::exit(0);
+}
+
+/// Snapshots decode to what was encoded, and anything that is not a whole snapshot
+/// of this version or older is rejected.
+fn test_format() -> Result<usize> {
+    let mut passed_tests = 0;
+    let params = Params::from(Args::default()
+        .rule("B2/S/C4".parse::<Rule>()?)
+        .topology(Topology::Plane)
+        .unbounded(true));
+    let header = SnapshotHeader::new(&params, 1234, CellCoord::new(-64, -128), 5, 3);
+    let cells = (0..15).map(|i| CellState::new(i % 4)).collect::<Vec<_>>();
+    let bytes = snapshot::encode(&header, &cells)?;
+    log::debug!(target: "Test", "{} - {:?}", "Testing snapshot round trip".cyan(), header);
+    assert!(bytes.starts_with(MAGIC));
+    assert_eq!(snapshot::decode(&bytes)?, (header.clone(), cells.clone()));
+    assert!(header.check(&params).is_ok());
+    passed_tests += 1;
+
+    let mut future = bytes.clone();
+    future[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&(VERSION + 1).to_le_bytes());
+    let mut bad_state = header.clone();
+    bad_state.rule = Rule::CONWAY;
+    let cases = [
+        (b"P5\n5 3\n255\n".to_vec(), "not a snapshot"),
+        (future, "only versions 1"),
+        (bytes[..bytes.len() - 1].to_vec(), "truncated"),
+        ([bytes.as_slice(), &[0]].concat(), "after its payload"),
+        (snapshot::encode(&SnapshotHeader { width: 4, ..header.clone() }, &cells)?, "should have 4x3"),
+        (snapshot::encode(&bad_state, &cells)?, "only has 2 states"),
+    ];
+    for (bytes, expected) in cases {
+        log::debug!(target: "Test", "{} - {}", "Testing invalid snapshot".cyan(), expected);
+        let error = snapshot::decode(&bytes).expect_err("The snapshot should not decode");
+        assert!(error.to_string().contains(expected), "Unexpected error: {}", error);
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
+
+/// A run that stops part way and is resumed from its last snapshot ends with the same board
+/// as a run that was never interrupted.
+async fn test_resume(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    let (interrupted, turns, every) = (50, 120, 25);
+    let cases = [
+        (EngineKind::Parallel, Rule::CONWAY, Topology::Torus, false),
+        (EngineKind::Naive, "B2/S/C3".parse::<Rule>()?, Topology::Klein, false),
+        (EngineKind::BitPacked, Rule::CONWAY, Topology::Plane, false),
+        (EngineKind::HashLife, Rule::CONWAY, Topology::Torus, false),
+        (EngineKind::Parallel, "B2/S34H".parse::<Rule>()?, Topology::Torus, false),
+        (EngineKind::Parallel, Rule::CONWAY, Topology::Plane, true),
+    ];
+    for (engine, rule, topology, unbounded) in cases {
+        let args = args.clone()
+            .engine(engine)
+            .rule(rule)
+            .topology(topology)
+            .unbounded(unbounded)
+            .image_width(64)
+            .image_height(64);
+        log::debug!(target: "Test", "{} - {:?}", "Testing resume".cyan(), Params::from(args.clone()));
+        let expected = run(args.clone().turns(turns)).await?.alive;
+
+        for turn in [every, interrupted] {
+            let _ = std::fs::remove_file(format!("out/64x64x{}.snap", turn));
+        }
+        run(args.clone().turns(interrupted).snapshot_every(every)).await?;
+        // Engines that jump many turns only snapshot the turn they land on, but always land on the last.
+        let path = [every, interrupted].into_iter()
+            .map(|turn| format!("out/64x64x{}.snap", turn))
+            .find(|path| Path::new(path).exists())
+            .expect("No snapshots were written");
+        assert!(!Path::new(&format!("{}.part", path)).exists(), "A partial snapshot was left behind");
+
+        let Outcome { completed_turns, alive, .. } = run(args.clone().turns(turns).resume(&path)).await?;
+        assert_eq!(completed_turns as usize, turns, "The resumed run did not finish every turn");
+        assert_eq!(alive, expected, "Resuming from {} changed the final board", path);
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
+
+/// Resuming fails clearly from a missing or invalid snapshot, or with options it was not taken with.
+async fn test_resume_errors(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    let args = args.image_width(16).image_height(16);
+    let _ = std::fs::remove_file("out/16x16x10.snap");
+    run(args.clone().turns(10).snapshot_every(10)).await?;
+    std::fs::write("out/16x16.snap", b"P5\n16 16\n255\n")?;
+    let cases = [
+        (args.clone().resume("out/missing.snap"), "Cannot read the snapshot out/missing.snap"),
+        (args.clone().resume("out/16x16.snap"), "not a snapshot"),
+        (args.clone().resume("out/16x16x10.snap").rule("B36/S23".parse::<Rule>()?), "was taken under the rule B3/S23"),
+        (args.clone().resume("out/16x16x10.snap").topology(Topology::Plane), "was taken on the torus topology"),
+        (args.clone().resume("out/16x16x10.snap").unbounded(true).topology(Topology::Plane), "was taken on the torus topology"),
+        (args.clone().resume("out/16x16x10.snap").turns(5), "past the 5 turns"),
+    ];
+    for (args, expected) in cases {
+        log::debug!(target: "Test", "{} - {:?}", "Testing resume error".cyan(), Params::from(args.clone()));
+        let error = run(args).await.expect_err("The run should not resume");
+        assert!(format!("{:#}", error).contains(expected), "Unexpected error: {:#}", error);
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
+
@@ -0,0 +1,189 @@
+use anyhow::Result;
+use colored::Colorize;
+use gol_rs::args::Args;
+use gol_rs::gol::{self, engine::EngineKind, event::{Event, State}, Params};
+use gol_rs::gol::{rule::Rule, topology::Topology};
+use gol_rs::gol::stability::{Stability, MAX_PERIOD};
+use gol_rs::util::{cell::{CellCoord, CellState}, logger};
+use log::Level;
+use sdl2::keyboard::Keycode;
+use std::collections::HashMap;
+use utils::io::read_world;
+use utils::run::run;
+use utils::world::{alive_cells, reference_step, sorted};
+
+mod utils;
+
+#[tokio::main]
+async fn main() {
+    let start = std::time::Instant::now();
+    logger::set_panic_hook();
+    logger::init(Level::Debug, false);
+
+    let passed_tests = test_hash()
+        + test_stop_on_stable(Args::default()).await.unwrap()
+        + test_report_once(Args::default()).await.unwrap()
+        + test_step_back(Args::default()).await.unwrap();
+
+    println!(
+        "\ntest result: {}. {} passed; finished in {:.2}s\n",
+        "ok".green(),
+        passed_tests,
+        start.elapsed().as_secs_f32()
+    );
+    std::process::exit(0);
+}
+
+/// The hash only depends on the cells, however they were changed to get there,
+/// and a repeat across a jump settles instead of giving a multiple of the period.
+fn test_hash() -> usize {
+    log::debug!(target: "Test", "{}", "Testing stability hash".cyan());
+    let blinker = [[(0, 1), (1, 1), (2, 1)], [(1, 0), (1, 1), (1, 2)]];
+    let mut stability = Stability::new();
+    blinker[0].iter().for_each(|&(x, y)| stability.change(CellCoord::new(x, y), CellState::DEAD, CellState::ALIVE));
+    assert_eq!(stability.seen(0, 0), None);
+    for turn in 1..=2 {
+        let (before, after) = (blinker[(turn as usize + 1) % 2], blinker[turn as usize % 2]);
+        before.iter().for_each(|&(x, y)| stability.change(CellCoord::new(x, y), CellState::ALIVE, CellState::DEAD));
+        after.iter().for_each(|&(x, y)| stability.change(CellCoord::new(x, y), CellState::DEAD, CellState::ALIVE));
+        assert_eq!(stability.seen(turn, 1), if turn == 2 { Some(2) } else { None });
+    }
+    assert_eq!(stability.seen(3, 1), None, "Stabilising should only be reported once");
+    stability.rewind(1);
+    assert_eq!(stability.seen(2, 1), Some(2), "Stabilising again after stepping back should be reported again");
+
+    let mut stability = Stability::new();
+    stability.change(CellCoord::new(-5, 7), CellState::DEAD, CellState::new(2));
+    assert_eq!(stability.seen(0, 0), None);
+    assert_eq!(stability.seen(4, 4), None);
+    assert!(stability.is_settling(), "A repeat across a jump should settle");
+    assert_eq!(stability.seen(5, 1), Some(1));
+    assert!(!stability.is_settling());
+    1
+}
+
+/// Soups stop on the first turn that repeats, with the period the reference finds.
+/// Engines that jump many turns stop a little later, once they have settled on the exact period.
+async fn test_stop_on_stable(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    let cases = [
+        (16, Rule::CONWAY),
+        (64, Rule::CONWAY),
+        (64, "B36/S23".parse::<Rule>()?),
+        (16, "B2/S/C3".parse::<Rule>()?),
+        (64, "B8/S".parse::<Rule>()?),
+    ];
+    for (size, rule) in cases {
+        let world = read_world(format!("images/{}x{}.pgm", size, size), &rule)?;
+        let (stabilised, period, turns) = reference_stabilised(&rule, world);
+        for engine in [EngineKind::Naive, EngineKind::Parallel, EngineKind::BitPacked, EngineKind::HashLife] {
+            if !engine.supports(&rule) {
+                continue;
+            }
+            let args = args.clone()
+                .engine(engine)
+                .rule(rule)
+                .stop_on_stable(true)
+                .image_width(size)
+                .image_height(size);
+            log::debug!(target: "Test", "{} - {:?}", "Testing stop on stable".cyan(), Params::from(args.clone()));
+            let events = run(args).await?.events;
+            let reported = events.iter().filter_map(|event| match event {
+                Event::Stabilised { completed_turns, period } => Some((*completed_turns, *period)),
+                _ => None,
+            }).collect::<Vec<_>>();
+            let [(completed_turns, reported_period)] = reported[..] else {
+                panic!("Expected one Stabilised event, got {:?}", reported);
+            };
+            assert_eq!(reported_period, period, "{} on {}x{} has the wrong period", rule, size, size);
+            if engine == EngineKind::HashLife {
+                assert!(completed_turns >= stabilised, "Stabilised at {} before {}", completed_turns, stabilised);
+            } else {
+                assert_eq!(completed_turns, stabilised, "{} on {}x{} stabilised at the wrong turn", rule, size, size);
+            }
+            let Some(Event::FinalTurnComplete { completed_turns: final_turns, alive }) = events.iter()
+                .find(|event| matches!(event, Event::FinalTurnComplete { .. })) else {
+                panic!("No FinalTurnComplete events received");
+            };
+            assert_eq!(*final_turns, completed_turns, "The run did not stop when it stabilised");
+            let expected = &turns[(stabilised + (completed_turns - stabilised) % period - period) as usize];
+            assert_eq!(sorted(alive.clone()), alive_cells(expected), "The final world is not the stabilised one");
+            passed_tests += 1;
+        }
+    }
+    Ok(passed_tests)
+}
+
+/// Without `--stop-on-stable` stabilising is reported once and every turn is still run.
+async fn test_report_once(args: Args) -> Result<usize> {
+    let world = read_world("images/64x64.pgm", &Rule::CONWAY)?;
+    let (stabilised, period, _) = reference_stabilised(&Rule::CONWAY, world);
+    let args = args.turns(stabilised as usize + 3 * period as usize).image_width(64).image_height(64);
+    log::debug!(target: "Test", "{} - {:?}", "Testing stabilised once".cyan(), Params::from(args.clone()));
+    let events = run(args.clone()).await?.events;
+    let reported = events.iter()
+        .filter(|event| matches!(event, Event::Stabilised { .. }))
+        .map(|event| event.get_completed_turns())
+        .collect::<Vec<_>>();
+    assert_eq!(reported, vec![stabilised], "Stabilising should be reported once");
+    let last = events.iter().rev().find(|event| matches!(event, Event::FinalTurnComplete { .. })).unwrap();
+    assert_eq!(last.get_completed_turns() as usize, args.turns, "Every turn should still be run");
+    Ok(1)
+}
+
+/// Stepping back past the turn that stabilised reports it again once it is reached again.
+async fn test_step_back(args: Args) -> Result<usize> {
+    let world = read_world("images/16x16.pgm", &Rule::CONWAY)?;
+    let (stabilised, period, _) = reference_stabilised(&Rule::CONWAY, world);
+    let args = args.turns(10_000_000).history(4).image_width(16).image_height(16);
+    log::debug!(target: "Test", "{} - {:?}", "Testing stabilised after stepping back".cyan(), Params::from(args.clone()));
+    let (key_presses_tx, key_presses_rx) = flume::bounded::<Keycode>(10);
+    let (events_tx, events_rx) = flume::bounded::<Event>(1000);
+    let gol = tokio::spawn(gol::run(args, events_tx, key_presses_rx));
+    let mut reported = Vec::new();
+    let mut stepped_back = false;
+    loop {
+        match events_rx.recv_async().await {
+            Ok(Event::Stabilised { completed_turns, period: reported_period }) => {
+                assert_eq!((completed_turns, reported_period), (stabilised, period));
+                reported.push(completed_turns);
+                if stepped_back {
+                    key_presses_tx.send_async(Keycode::Q).await?;
+                } else {
+                    key_presses_tx.send_async(Keycode::P).await?;
+                }
+            },
+            Ok(Event::StateChange { new_state: State::Pause, .. }) => {
+                for key in [Keycode::B, Keycode::B, Keycode::B, Keycode::P] {
+                    key_presses_tx.send_async(key).await?;
+                }
+                stepped_back = true;
+            },
+            Ok(Event::StateChange { new_state: State::Quitting, .. }) => break,
+            Ok(_) => (),
+            Err(_) => panic!("Events stopped before quitting"),
+        }
+    }
+    gol.await??;
+    assert_eq!(reported, vec![stabilised, stabilised], "Stabilising should be reported again after stepping back");
+    Ok(1)
+}
+
+/// Step `rule` on a torus until the world repeats, returning the turn it first repeats at,
+/// the period and every turn up to then.
+fn reference_stabilised(rule: &Rule, world: Vec<Vec<CellState>>) -> (u32, u32, Vec<Vec<Vec<CellState>>>) {
+    let mut seen = HashMap::from([(world.clone(), 0)]);
+    let mut turns = vec![world];
+    loop {
+        let next = reference_step(rule, Topology::Torus, turns.last().unwrap());
+        let turn = turns.len() as u32;
+        if let Some(&earlier) = seen.get(&next) {
+            assert!((turn - earlier) as usize <= MAX_PERIOD, "The reference period {} is too long to detect", turn - earlier);
+            turns.push(next);
+            return (turn, turn - earlier, turns);
+        }
+        seen.insert(next.clone(), turn);
+        turns.push(next);
+    }
+}
+
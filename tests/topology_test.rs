@@ -0,0 +1,218 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use colored::Colorize;
+use gol_rs::args::Args;
+use gol_rs::gol::{engine::EngineKind, rule::Rule, topology::{ParseTopologyError, Topology}, Params};
+use gol_rs::util::{cell::{CellCoord, CellState}, logger};
+use log::Level;
+use utils::world::reference_step;
+
+mod utils;
+
+#[tokio::main]
+async fn main() {
+    let start = std::time::Instant::now();
+    logger::set_panic_hook();
+    logger::init(Level::Debug, false);
+
+    let passed_tests = test_parse().unwrap()
+        + test_map().unwrap()
+        + test_engines(Args::default()).unwrap()
+        + test_klein_glider(Args::default()).unwrap()
+        + test_unsupported(Args::default()).unwrap();
+
+    println!(
+        "\ntest result: {}. {} passed; finished in {:.2}s\n",
+        "ok".green(),
+        passed_tests,
+        start.elapsed().as_secs_f32()
+    );
+    std::process::exit(0);
+}
+
+/// Every topology name parses and round trips, and unknown names return the matching error.
+fn test_parse() -> Result<usize> {
+    let mut passed_tests = 0;
+    let cases = [
+        ("torus", Topology::Torus),
+        ("plane", Topology::Plane),
+        ("Klein", Topology::Klein),
+        ("cross-surface", Topology::CrossSurface),
+        ("twisted-torus:5", Topology::TwistedTorus(5)),
+    ];
+    for (name, expected) in cases {
+        log::debug!(target: "Test", "{} - {}", "Testing topology parsing".cyan(), name);
+        let topology = name.parse::<Topology>()?;
+        assert_eq!(topology, expected, "{} parsed to {}", name, topology);
+        assert_eq!(topology.to_string().parse::<Topology>()?, topology, "{} does not round trip", topology);
+        passed_tests += 1;
+    }
+    let errors = [
+        ("sphere", ParseTopologyError::UnknownTopology("sphere".to_string())),
+        ("torus:5", ParseTopologyError::UnknownTopology("torus:5".to_string())),
+        ("twisted-torus:-1", ParseTopologyError::InvalidOffset("-1".to_string())),
+    ];
+    for (name, expected) in errors {
+        log::debug!(target: "Test", "{} - {:?}", "Testing topology parse error".cyan(), name);
+        assert_eq!(name.parse::<Topology>(), Err(expected), "Wrong error for {:?}", name);
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
+
+/// Cells past the edges of a 16x12 world map to the cells each topology joins them to.
+fn test_map() -> Result<usize> {
+    let mut passed_tests = 0;
+    let cases = [
+        (Topology::Torus, (-1, -1), Some((15, 11))),
+        (Topology::Torus, (16, 5), Some((0, 5))),
+        (Topology::Plane, (3, 4), Some((3, 4))),
+        (Topology::Plane, (-1, 4), None),
+        (Topology::Plane, (3, 12), None),
+        (Topology::Klein, (-1, 4), Some((15, 4))),
+        (Topology::Klein, (3, -1), Some((12, 11))),
+        (Topology::Klein, (3, 12), Some((12, 0))),
+        (Topology::Klein, (-1, -1), Some((0, 11))),
+        (Topology::CrossSurface, (3, -1), Some((12, 11))),
+        (Topology::CrossSurface, (-1, 4), Some((15, 7))),
+        (Topology::CrossSurface, (16, 0), Some((0, 11))),
+        (Topology::CrossSurface, (-1, -1), None),
+        (Topology::TwistedTorus(5), (3, 12), Some((8, 0))),
+        (Topology::TwistedTorus(5), (3, -1), Some((14, 11))),
+        (Topology::TwistedTorus(5), (-1, 4), Some((15, 4))),
+    ];
+    for (topology, (x, y), expected) in cases {
+        log::debug!(target: "Test", "{} - {} ({}, {})", "Testing topology map".cyan(), topology, x, y);
+        assert_eq!(topology.map(x, y, 16, 12), expected, "{} mapped ({}, {}) wrongly", topology, x, y);
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
+
+/// Every engine that supports a topology matches a reference that looks up every neighbour
+/// through `Topology::map`, on worlds whose widths are and are not multiples of 64.
+fn test_engines(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    let topologies = [
+        Topology::Torus,
+        Topology::Plane,
+        Topology::Klein,
+        Topology::CrossSurface,
+        Topology::TwistedTorus(5),
+    ];
+    let rules = ["B3/S23", "B2-a/S12", "R2,C0,M1,S4..7,B5..6,NM", "R3,C0,M0,S3..5,B4..5,NN"];
+    for topology in topologies {
+        for rulestring in rules {
+            let rule = rulestring.parse::<Rule>()?;
+            for (width, height) in [(16_usize, 16_usize), (70, 33)] {
+                let world = soup(width, height);
+                let expected = (0..50).fold(world.clone(), |world, _| reference_step(&rule, topology, &world));
+                for &engine in EngineKind::value_variants() {
+                    if !engine.supports(&rule) || !engine.supports_topology(topology) {
+                        continue;
+                    }
+                    // HashLife only loads square power-of-two worlds.
+                    if matches!(engine, EngineKind::HashLife) && (width != height || !width.is_power_of_two()) {
+                        continue;
+                    }
+                    for threads in [1, 3] {
+                        let params = Params::from(args.clone()
+                            .rule(rule)
+                            .topology(topology)
+                            .engine(engine)
+                            .threads(threads)
+                            .image_width(width)
+                            .image_height(height));
+                        log::debug!(target: "Test", "{} - {:?}", "Testing topology".cyan(), params);
+                        let mut gol = engine.build(&params)?;
+                        gol.load(&world)?;
+                        for _ in 0..50 {
+                            gol.step(1)?;
+                        }
+                        assert!(gol.world() == expected, "{:?} differs from the reference", params);
+                        passed_tests += 1;
+                    }
+                }
+            }
+        }
+    }
+    Ok(passed_tests)
+}
+
+/// A glider flying north into the seam of a Klein bottle comes back from the bottom mirrored,
+/// so it flies north-east instead of north-west, unlike on a torus.
+fn test_klein_glider(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    let (width, height) = (16_usize, 16_usize);
+    let mut world = vec![vec![CellState::DEAD; width]; height];
+    // A glider flying north-west.
+    for (x, y) in [(8, 2), (9, 2), (8, 3), (10, 3), (8, 4)] {
+        world[y][x] = CellState::ALIVE;
+    }
+    let mirror = |cells: Vec<CellCoord>| {
+        let mut cells = cells.into_iter().map(|cell| CellCoord::new(width - 1 - cell.x, cell.y)).collect::<Vec<_>>();
+        cells.sort_by_key(|cell| (cell.y, cell.x));
+        cells
+    };
+    let leftmost = |cells: &[CellCoord]| cells.iter().map(|cell| cell.x).min();
+    for &engine in EngineKind::value_variants() {
+        if !engine.supports_topology(Topology::Klein) {
+            continue;
+        }
+        let params = |topology| Params::from(args.clone()
+            .topology(topology)
+            .engine(engine)
+            .threads(3)
+            .image_width(width)
+            .image_height(height));
+        log::debug!(target: "Test", "{} - {:?}", "Testing Klein glider".cyan(), params(Topology::Klein));
+        let mut klein = engine.build(&params(Topology::Klein))?;
+        let mut torus = engine.build(&params(Topology::Torus))?;
+        klein.load(&world)?;
+        torus.load(&world)?;
+
+        // Before reaching the seam both worlds agree.
+        klein.step(4)?;
+        torus.step(4)?;
+        assert_eq!(klein.alive_cells(), torus.alive_cells(), "The glider changed before reaching the seam");
+
+        // Once it has crossed, the Klein bottle glider is the mirror image of the torus one.
+        klein.step(20)?;
+        torus.step(20)?;
+        let crossed = klein.alive_cells();
+        assert_eq!(crossed, mirror(torus.alive_cells()), "The glider was not reflected by the seam");
+        assert!(crossed.iter().all(|cell| cell.y > height / 2), "The glider did not come back from the bottom");
+
+        // And it now drifts east while the torus glider keeps drifting west.
+        klein.step(4)?;
+        torus.step(4)?;
+        assert_eq!(leftmost(&klein.alive_cells()), leftmost(&crossed).map(|x| x + 1), "The glider is not flying east");
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
+
+/// HashLife wraps by tiling the world, so it refuses every topology other than a torus.
+fn test_unsupported(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    for topology in [Topology::Plane, Topology::Klein, Topology::CrossSurface, Topology::TwistedTorus(1)] {
+        log::debug!(target: "Test", "{} - {}", "Testing unsupported topology".cyan(), topology);
+        let params = Params::from(args.clone().engine(EngineKind::HashLife).topology(topology));
+        let error = EngineKind::HashLife.build(&params).err().expect("HashLife should not support the topology");
+        assert!(error.to_string().contains("does not support"), "Unexpected error: {}", error);
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
+
+/// A reproducible random soup with roughly a third of the cells alive.
+fn soup(width: usize, height: usize) -> Vec<Vec<CellState>> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    (0..height).map(|_| (0..width).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        if state.is_multiple_of(3) { CellState::ALIVE } else { CellState::DEAD }
+    }).collect()).collect()
+}
+
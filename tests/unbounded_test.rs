@@ -0,0 +1,159 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use colored::Colorize;
+use gol_rs::args::Args;
+use gol_rs::gol::{self, engine::EngineKind, event::Event, rule::Rule, topology::Topology, Params};
+use gol_rs::util::{cell::{CellCoord, CellState}, logger};
+use image::ImageReader;
+use log::Level;
+use sdl2::keyboard::Keycode;
+use utils::io::read_alive_cells;
+use utils::run::{run, Outcome};
+use utils::world::sorted;
+
+mod utils;
+
+#[tokio::main]
+async fn main() {
+    let start = std::time::Instant::now();
+    logger::set_panic_hook();
+    logger::init(Level::Debug, false);
+
+    let passed_tests = test_glider(Args::default()).await.unwrap()
+        + test_reference(Args::default().threads(4)).await.unwrap()
+        + test_output(Args::default()).await.unwrap()
+        + test_unsupported(Args::default()).await.unwrap();
+
+    println!(
+        "\ntest result: {}. {} passed; finished in {:.2}s\n",
+        "ok".green(),
+        passed_tests,
+        start.elapsed().as_secs_f32()
+    );
+    std::process::exit(0);
+}
+
+/// The glider in the 16x16 image flies off the south east corner instead of wrapping,
+/// moving one cell diagonally every 4 turns on every engine that supports an unbounded world.
+async fn test_glider(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    let initial = read_alive_cells("images/16x16.pgm", 16, 16)?;
+    for &engine in EngineKind::value_variants() {
+        if !engine.supports_topology(Topology::Plane) {
+            continue;
+        }
+        for (threads, turns) in [(1, 100_i64), (3, 100), (3, 400)] {
+            let args = args.clone()
+                .unbounded(true)
+                .engine(engine)
+                .threads(threads)
+                .turns(turns as usize)
+                .image_width(16)
+                .image_height(16);
+            log::debug!(target: "Test", "{} - {:?}", "Testing unbounded glider".cyan(), Params::from(args.clone()));
+            let Outcome { alive, flipped, .. } = run(args).await?;
+            let shift = turns / 4;
+            let expected = initial.iter()
+                .map(|cell| CellCoord::new(cell.x + shift, cell.y + shift))
+                .collect::<Vec<_>>();
+            assert_eq!(alive, sorted(expected), "The glider did not fly off after {} turns", turns);
+            assert_eq!(flipped, alive.into_iter().collect(), "The flipped cells do not add up to the final world");
+            passed_tests += 1;
+        }
+    }
+    Ok(passed_tests)
+}
+
+/// An unbounded world matches a plane padded far enough that nothing can reach its edges,
+/// under rules that reach further than one cell and rules with refractory states.
+async fn test_reference(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    let turns = 100;
+    let input = ImageReader::open("images/64x64.pgm")?.decode()?.into_bytes();
+    for rulestring in ["B3/S23", "B36/S23", "B2/S/C3", "R2,C0,M1,S4..7,B5..6,NM"] {
+        let rule = rulestring.parse::<Rule>()?;
+        let padding = turns * rule.range() as usize + 1;
+        let size = 64 + 2 * padding;
+        let mut world = vec![vec![CellState::DEAD; size]; size];
+        for (i, &byte) in input.iter().enumerate() {
+            world[padding + i / 64][padding + i % 64] = CellState::from_grey(byte, rule.states());
+        }
+        let params = Params::from(args.clone().rule(rule).topology(Topology::Plane));
+        let mut reference = EngineKind::Parallel.build(&params)?;
+        reference.load(&world)?;
+        reference.step(turns as u64)?;
+        let origin = CellCoord::new(-(padding as i64), -(padding as i64));
+        let expected = reference.alive_cells().into_iter().map(|cell| cell.offset(origin)).collect::<Vec<_>>();
+
+        for &engine in EngineKind::value_variants() {
+            if !engine.supports(&rule) || !engine.supports_topology(Topology::Plane) {
+                continue;
+            }
+            let args = args.clone()
+                .unbounded(true)
+                .rule(rule)
+                .engine(engine)
+                .turns(turns)
+                .image_width(64)
+                .image_height(64);
+            log::debug!(target: "Test", "{} - {:?}", "Testing unbounded world".cyan(), Params::from(args.clone()));
+            let Outcome { alive, flipped, .. } = run(args).await?;
+            assert_eq!(alive, sorted(expected.clone()), "{} differs from the padded plane", rule);
+            assert_eq!(flipped, alive.into_iter().collect(), "The changed cells do not add up to the final world");
+            passed_tests += 1;
+        }
+    }
+    Ok(passed_tests)
+}
+
+/// The output image is the bounding box of the world, with its origin in a header comment.
+async fn test_output(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    for turns in [0_i64, 100] {
+        let args = args.clone()
+            .unbounded(true)
+            .turns(turns as usize)
+            .image_width(16)
+            .image_height(16);
+        log::debug!(target: "Test", "{} - {:?}", "Testing unbounded output".cyan(), Params::from(args.clone()));
+        let alive = run(args).await?.alive;
+
+        // The glider fits in a 3x3 box, whose top left corner starts at (3, 5).
+        let path = format!("out/3x3x{}.pgm", turns);
+        let header = std::fs::read(&path)?;
+        let origin = String::from_utf8_lossy(&header).lines()
+            .find_map(|line| line.strip_prefix("# origin "))
+            .context("The output image has no origin comment")?
+            .split(' ')
+            .map(|n| n.parse::<i64>())
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(origin, [3 + turns / 4, 5 + turns / 4], "Wrong origin in {}", path);
+        let output = read_alive_cells(&path, 3, 3)?.into_iter()
+            .map(|cell| CellCoord::new(cell.x + origin[0], cell.y + origin[1]))
+            .collect::<Vec<_>>();
+        assert_eq!(sorted(output), alive, "{} does not hold the final world", path);
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
+
+/// Topologies that join edges, HashLife and rules that give birth in empty space cannot run unbounded.
+async fn test_unsupported(args: Args) -> Result<usize> {
+    let mut passed_tests = 0;
+    let cases = [
+        (args.clone().topology(Topology::Klein), "cannot use the klein topology"),
+        (args.clone().engine(EngineKind::HashLife), "does not support an unbounded world"),
+        (args.clone().rule("B03/S23".parse()?), "gives birth with no neighbours"),
+    ];
+    for (args, expected) in cases {
+        let args = args.unbounded(true).turns(1).image_width(16).image_height(16);
+        log::debug!(target: "Test", "{} - {:?}", "Testing unsupported unbounded world".cyan(), Params::from(args.clone()));
+        let (_key_presses_tx, key_presses_rx) = flume::bounded::<Keycode>(10);
+        let (events_tx, _events_rx) = flume::bounded::<Event>(1000);
+        let error = gol::run(args, events_tx, key_presses_rx).await
+            .expect_err("The world should not run unbounded");
+        assert!(error.to_string().contains(expected), "Unexpected error: {}", error);
+        passed_tests += 1;
+    }
+    Ok(passed_tests)
+}
@@ -1,7 +1,8 @@
 #[allow(dead_code)]
 pub mod io {
     use std::{path::Path, collections::HashMap, fs::File};
-    use gol_rs::util::cell::{CellCoord, CellValue};
+    use gol_rs::gol::rule::Rule;
+    use gol_rs::util::cell::{CellCoord, CellState, CellValue};
     use image::ImageReader;
     use anyhow::Result;
     use serde::Deserialize;
@@ -10,7 +11,7 @@ pub mod io {
         path: P,
         width: usize,
         height: usize
-    ) -> Result<Vec<CellCoord>> {
+    ) -> Result<Vec<CellCoord<i64>>> {
         let pgm = ImageReader::open(path)?.decode()?;
         assert_eq!(
             pgm.width(),
@@ -27,11 +28,20 @@ pub mod io {
             .flat_map(|(y, row)|
                 row.iter().enumerate()
                     .filter(|&(_, &cell)| CellValue::from(cell).is_alive())
-                    .map(move |(x, _)| CellCoord::new(x, y)))
+                    .map(move |(x, _)| CellCoord::new(x as i64, y as i64)))
             .collect()
         )
     }
 
+    /// Read the world in the image at `path`, taking each grey level as the state of `rule` it stands for.
+    pub fn read_world<P: AsRef<Path>>(path: P, rule: &Rule) -> Result<Vec<Vec<CellState>>> {
+        let image = ImageReader::open(path)?.decode()?;
+        let width = image.width() as usize;
+        Ok(image.into_bytes().chunks(width)
+            .map(|row| row.iter().map(|&grey| CellState::from_grey(grey, rule.states())).collect())
+            .collect())
+    }
+
     #[derive(Debug, Deserialize)]
     struct Check {
         completed_turns: u32,
@@ -58,8 +68,8 @@ pub mod visualise {
 
     pub fn assert_eq_board(
         args: Args,
-        input_cells: &[CellCoord],
-        expected_cells: &[CellCoord]
+        input_cells: &[CellCoord<i64>],
+        expected_cells: &[CellCoord<i64>]
     ) {
         let all_match =
             input_cells.len() == expected_cells.len()
@@ -69,11 +79,12 @@ pub mod visualise {
             return
         }
 
-        if args.image_width == 16 && args.image_height == 16 {
-            let mut input_matrix = vec![vec![CellValue::Dead; args.image_width]; args.image_height];
+        let (width, height) = args.size().unwrap();
+        if width == 16 && height == 16 {
+            let mut input_matrix = vec![vec![CellValue::Dead; width]; height];
             let mut expected_matrix = input_matrix.clone();
-            input_cells.iter().for_each(|cell| input_matrix[cell.y][cell.x] = CellValue::Alive);
-            expected_cells.iter().for_each(|cell| expected_matrix[cell.y][cell.x] = CellValue::Alive);
+            input_cells.iter().for_each(|cell| input_matrix[cell.y as usize][cell.x as usize] = CellValue::Alive);
+            expected_cells.iter().for_each(|cell| expected_matrix[cell.y as usize][cell.x as usize] = CellValue::Alive);
             let mut input = matrix_to_strings(&input_matrix);
             let mut expected = matrix_to_strings(&expected_matrix);
             input.insert(0, get_centered_banner(39, "Your result", ' '));
@@ -94,6 +105,7 @@ pub mod visualise {
         format!("{}{}{}", filling, str, filling)
     }
 
+    #[allow(clippy::len_zero)]
     fn fold_strings(items: &[&[String]]) -> String {
         assert!(items.len() > 0, "nothing to fold");
         assert!(
@@ -109,6 +121,7 @@ pub mod visualise {
         })
     }
 
+    #[allow(clippy::len_zero, clippy::ptr_arg)]
     fn matrix_to_strings(cells: &Vec<Vec<CellValue>>) -> Vec<String> {
         assert!(cells.len() > 0);
         let width = cells[0].len();
@@ -131,6 +144,7 @@ pub mod sdl {
     use anyhow::Result;
     use flume::{Receiver, Sender};
     use sdl2::keyboard::Keycode;
+    use sdl2::pixels::Color;
     use gol_rs::{args::Args, gol::event::{Event, State}, sdl::window::Window, util::avgturns::AvgTurns};
     use tokio::select;
 
@@ -142,10 +156,11 @@ pub mod sdl {
         events_forward: Sender<Event>,
         key_presses_forward: Sender<Keycode>,
     ) -> Result<()> {
+        let (width, height) = args.size()?;
         let mut sdl = Window::new(
             title,
-            args.image_width as u32,
-            args.image_height as u32,
+            width as u32,
+            height as u32,
         )?;
         let fps = 60;
         let mut event_pump = sdl.take_event_pump()?;
@@ -178,12 +193,19 @@ pub mod sdl {
                             sdl.flip_pixel(cell.x as u32, cell.y as u32),
                         Ok(Event::CellsFlipped { cells, ..}) =>
                             cells.iter().for_each(|cell| sdl.flip_pixel(cell.x as u32, cell.y as u32)),
+                        Ok(Event::CellsChanged { cells, .. }) =>
+                            cells.iter().for_each(|(cell, state)| {
+                                let grey = state.grey(args.rule.states());
+                                sdl.set_pixel(cell.x as u32, cell.y as u32, Color::RGBA(grey, grey, grey, grey))
+                            }),
                         Ok(Event::TurnComplete { .. }) =>
                             dirty = true,
                         Ok(Event::AliveCellsCount { completed_turns, .. }) =>
                             log::info!(target: "Test", "{} Avg{:>5} turns/s", gol_event?, avg_turns.get(completed_turns)),
                         Ok(Event::ImageOutputComplete { .. }) =>
                             log::info!(target: "Test", "{}", gol_event?),
+                        Ok(Event::Stabilised { .. }) =>
+                            log::info!(target: "Test", "{}", gol_event?),
                         Ok(Event::FinalTurnComplete { .. }) =>
                             log::info!(target: "Test", "{}", gol_event?),
                         Ok(Event::StateChange { new_state, .. }) => {
@@ -223,6 +245,8 @@ pub mod sdl {
                             log::info!(target: "Test", "{} Avg{:>5} turns/s", gol_event?, avg_turns.get(completed_turns)),
                         Ok(Event::ImageOutputComplete { .. }) =>
                             log::info!(target: "Test", "{}", gol_event?),
+                        Ok(Event::Stabilised { .. }) =>
+                            log::info!(target: "Test", "{}", gol_event?),
                         Ok(Event::FinalTurnComplete { .. }) =>
                             log::info!(target: "Test", "{}", gol_event?),
                         Ok(Event::StateChange { new_state, .. }) => {
@@ -241,6 +265,177 @@ pub mod sdl {
     }
 }
 
+#[allow(dead_code)]
+pub mod run {
+    use std::collections::HashSet;
+    use anyhow::{Context, Result};
+    use gol_rs::{args::Args, gol::{self, event::Event, Params}, util::cell::{CellCoord, CellState}};
+    use sdl2::keyboard::Keycode;
+
+    /// Outcome is what a run reported on its way to completion.
+    #[derive(Debug)]
+    pub struct Outcome {
+        pub completed_turns: u32,
+        /// The final alive cells, sorted by row and then column.
+        pub alive: Vec<CellCoord<i64>>,
+        /// The alive cells rebuilt from the cell events, which should be the final alive cells.
+        pub flipped: HashSet<CellCoord<i64>>,
+        /// The cells and states of the `CellsChanged` events, in the order they were sent.
+        pub changed: Vec<(CellCoord<i64>, CellState)>,
+        /// The name of the last image written, if any was.
+        pub filename: Option<String>,
+        /// Every event but the cell events and turn completions, in the order they were sent.
+        pub events: Vec<Event>,
+    }
+
+    /// Run to completion, taking a width or height left out from the input as `app::run` does,
+    /// as the run takes the size as given. Fails with the error the run ended with, if any.
+    pub async fn run(args: Args) -> Result<Outcome> {
+        let args = match (args.image_width, args.image_height) {
+            (Some(_), Some(_)) => args,
+            _ => {
+                let (width, height) = args.size()?;
+                args.image_width(width).image_height(height)
+            },
+        };
+        let (_key_presses_tx, key_presses_rx) = flume::bounded::<Keycode>(10);
+        let (events_tx, events_rx) = flume::bounded::<Event>(1000);
+        let gol = tokio::spawn(gol::run(args.clone(), events_tx, key_presses_rx));
+        let (mut flipped, mut changed) = (HashSet::new(), Vec::new());
+        let (mut result, mut filename, mut events) = (None, None, Vec::new());
+        while let Ok(event) = events_rx.recv_async().await {
+            match event {
+                Event::CellFlipped { cell, .. } => if !flipped.remove(&cell) {
+                    flipped.insert(cell);
+                },
+                Event::CellsFlipped { cells, .. } => for cell in cells {
+                    if !flipped.remove(&cell) {
+                        flipped.insert(cell);
+                    }
+                },
+                Event::CellsChanged { cells, .. } => for (cell, state) in cells {
+                    if state.is_alive() { flipped.insert(cell) } else { flipped.remove(&cell) };
+                    changed.push((cell, state));
+                },
+                Event::TurnComplete { .. } => (),
+                event => {
+                    match &event {
+                        Event::FinalTurnComplete { completed_turns, alive } => result = Some((*completed_turns, alive.clone())),
+                        Event::ImageOutputComplete { filename: name, .. } => filename = Some(name.clone()),
+                        _ => (),
+                    }
+                    events.push(event);
+                },
+            }
+        }
+        gol.await??;
+        let (completed_turns, mut alive) = result
+            .with_context(|| format!("No FinalTurnComplete events received {:?}", Params::from(args)))?;
+        alive.sort_by_key(|cell| (cell.y, cell.x));
+        Ok(Outcome { completed_turns, alive, flipped, changed, filename, events })
+    }
+}
+
+#[allow(dead_code)]
+pub mod world {
+    use gol_rs::gol::{lattice::Lattice, rule::{Neighbourhood, Rule}, topology::Topology};
+    use gol_rs::util::cell::{CellCoord, CellState};
+
+    /// The axial offsets of the six neighbours of a hexagon.
+    const AXIAL: [(i64, i64); 6] = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, -1), (-1, 1)];
+
+    /// Sort cells by row and then column, so that worlds can be compared as lists.
+    pub fn sorted(mut cells: Vec<CellCoord<i64>>) -> Vec<CellCoord<i64>> {
+        cells.sort_by_key(|cell| (cell.y, cell.x));
+        cells
+    }
+
+    /// The alive cells of `world`, sorted.
+    pub fn alive_cells(world: &[Vec<CellState>]) -> Vec<CellCoord<i64>> {
+        sorted(world.iter().enumerate()
+            .flat_map(|(y, row)| row.iter().enumerate()
+                .filter(|(_, cell)| cell.is_alive())
+                .map(move |(x, _)| CellCoord::new(x as i64, y as i64)))
+            .collect())
+    }
+
+    /// A Gosper glider gun in the top left corner of an otherwise empty world.
+    pub fn glider_gun(width: usize, height: usize) -> Vec<Vec<CellState>> {
+        const GUN: [(usize, usize); 36] = [
+            (24, 0), (22, 1), (24, 1), (12, 2), (13, 2), (20, 2), (21, 2), (34, 2), (35, 2),
+            (11, 3), (15, 3), (20, 3), (21, 3), (34, 3), (35, 3), (0, 4), (1, 4), (10, 4),
+            (16, 4), (20, 4), (21, 4), (0, 5), (1, 5), (10, 5), (14, 5), (16, 5), (17, 5),
+            (22, 5), (24, 5), (10, 6), (16, 6), (24, 6), (11, 7), (15, 7), (12, 8), (13, 8),
+        ];
+        let mut world = vec![vec![CellState::DEAD; width]; height];
+        for (x, y) in GUN {
+            world[y + 1][x + 1] = CellState::ALIVE;
+        }
+        world
+    }
+
+    /// One turn of `rule`, looking up every neighbour of every cell through `topology`.
+    /// Hexagons and triangles find their neighbours from the geometry of the lattice rather than its stored offsets.
+    pub fn reference_step(rule: &Rule, topology: Topology, world: &[Vec<CellState>]) -> Vec<Vec<CellState>> {
+        let (width, height) = (world[0].len(), world.len());
+        let alive = |x: isize, y: isize|
+            topology.map(x, y, width, height).is_some_and(|(x, y)| world[y][x].is_alive());
+        let range = rule.range() as isize;
+        (0..height).map(|y| (0..width).map(|x| {
+            let (x, y) = (x as isize, y as isize);
+            let state = world[y as usize][x as usize];
+            if rule.lattice() != Lattice::Square {
+                let neighbours = reference_neighbours(rule.lattice(), x as i64, y as i64).into_iter()
+                    .filter(|&(nx, ny)| alive(nx as isize, ny as isize))
+                    .count();
+                rule.next_state(state, neighbours as u16)
+            } else if rule.is_larger_than_life() {
+                let neighbours = (-range..=range)
+                    .flat_map(|dy| (-range..=range).map(move |dx| (dx, dy)))
+                    .filter(|&(dx, dy)| rule.neighbourhood() == Neighbourhood::Moore || dx.abs() + dy.abs() <= range)
+                    .filter(|&(dx, dy)| rule.middle() || (dx, dy) != (0, 0))
+                    .filter(|&(dx, dy)| alive(x + dx, y + dy))
+                    .count();
+                rule.next_state(state, neighbours as u16)
+            } else {
+                let neighbourhood = (0..9)
+                    .filter(|i| alive(x + i % 3 - 1, y + i / 3 - 1))
+                    .fold(0, |index, i| index | 1 << i);
+                rule.next_state_isotropic(state, neighbourhood)
+            }
+        }).collect()).collect()
+    }
+
+    /// The neighbours of a hexagon or triangle worked out from the geometry of the lattice:
+    /// the axial neighbours of a hexagon, or every triangle sharing a corner with a triangle.
+    pub fn reference_neighbours(lattice: Lattice, x: i64, y: i64) -> Vec<(i64, i64)> {
+        match lattice {
+            Lattice::Hexagonal => {
+                let (q, r) = Lattice::raster_to_axial(x, y);
+                AXIAL.iter().map(|&(dq, dr)| Lattice::axial_to_raster(q + dq, r + dr)).collect()
+            },
+            Lattice::Triangular => {
+                let own = corners(x, y);
+                (y - 1..=y + 1)
+                    .flat_map(|ny| (x - 3..=x + 3).map(move |nx| (nx, ny)))
+                    .filter(|&cell| cell != (x, y))
+                    .filter(|&(nx, ny)| corners(nx, ny).iter().any(|corner| own.contains(corner)))
+                    .collect()
+            },
+            Lattice::Square => unreachable!("Square cells are covered by the rule tests"),
+        }
+    }
+
+    /// The corners of the triangle at raster `(x, y)`, on a grid of half-cell columns and whole rows.
+    fn corners(x: i64, y: i64) -> [(i64, i64); 3] {
+        if Lattice::points_up(x, y) {
+            [(x + 1, y), (x, y + 1), (x + 2, y + 1)]
+        } else {
+            [(x, y), (x + 2, y), (x + 1, y + 1)]
+        }
+    }
+}
+
 #[allow(dead_code)]
 pub mod common {
     use std::{time::Duration, fmt::Display};